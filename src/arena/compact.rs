@@ -0,0 +1,85 @@
+//! Squeezing the empty slots out of an arena
+
+use std::marker::PhantomData;
+
+use crate::arena::{Arena, Key};
+
+/// Where every value of an arena went when it was compacted
+pub struct KeyRemap<K> {
+    /// the new key of every old slot, none for slots that were empty
+    keys: Vec<Option<Key<K>>>,
+    phantom: PhantomData<K>,
+}
+
+impl<K> KeyRemap<K> {
+    /// the new key of an old key, none if nothing was behind it
+    pub fn get(&self, old: Key<K>) -> Option<Key<K>> {
+        self.keys.get(old.get() as usize).copied().flatten()
+    }
+
+    /// rewrite a key in place, returns false and leaves the key alone if nothing was behind it
+    pub fn apply(&self, key: &mut Key<K>) -> bool {
+        match self.get(*key) {
+            Some(new) => {
+                *key = new;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// every old key, with its new key
+    pub fn iter(&self) -> impl '_ + Iterator<Item = (Key<K>, Key<K>)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, new)| Some((Key::new(idx as u32)?, (*new)?)))
+    }
+
+    /// whether no key moved
+    pub fn is_identity(&self) -> bool {
+        self.iter().all(|(old, new)| old == new)
+    }
+}
+
+impl<T, K> Arena<T, K> {
+    /// move every value to the front, in key order, and drop the empty slots. every key made
+    /// before, generational ones included, has to go through the returned remap
+    pub fn compact(&mut self) -> KeyRemap<K> {
+        self.compact_by_key(|key, _| key)
+    }
+
+    /// compact, placing the values in the order of the sort key `f` gives them. values with the
+    /// same sort key keep their key order
+    pub fn compact_by_key<S: Ord>(&mut self, mut f: impl FnMut(Key<K>, &T) -> S) -> KeyRemap<K> {
+        let mut live: Vec<(S, u32)> = self
+            .iter()
+            .map(|(value, key)| (f(key, value), key.get()))
+            .collect();
+        live.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut keys = vec![None; self.inner.len()];
+        let mut inner = Vec::with_capacity(live.len() + 1);
+        inner.push(self.inner[0].take());
+        for (_, idx) in live {
+            keys[idx as usize] = Key::new(inner.len() as u32);
+            inner.push(self.inner[idx as usize].take());
+        }
+
+        // keys from before may now point at other values, so every slot starts a generation past
+        // the last one any of them could have
+        let generation = self
+            .generations
+            .iter()
+            .max()
+            .map_or(0, |generation| generation.wrapping_add(1));
+        self.generations = vec![generation; inner.len()];
+        self.inner = inner;
+        self.free.clear();
+
+        KeyRemap {
+            keys,
+            phantom: PhantomData,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Iterators over the values of an arena, with their keys
+
+use std::{iter::Enumerate, marker::PhantomData};
+
+use crate::arena::{Arena, Key};
+
+/// the key of the slot at `idx`, slot zero is never handed out
+fn key_at<K>(idx: usize) -> Option<Key<K>> {
+    match idx {
+        0 => None,
+        idx => Key::new(idx as u32),
+    }
+}
+
+macro_rules! iterator {
+    ($name:ident<$($lt:lifetime,)? T, K>, $inner:ty, $item:ty) => {
+        pub struct $name<$($lt,)? T, K> {
+            inner: Enumerate<$inner>,
+            phantom: PhantomData<K>,
+        }
+
+        impl<$($lt,)? T, K> Iterator for $name<$($lt,)? T, K> {
+            type Item = ($item, Key<K>);
+
+            fn next(&mut self) -> Option<Self::Item> {
+                loop {
+                    let (idx, slot) = self.inner.next()?;
+                    if let (Some(value), Some(key)) = (slot, key_at(idx)) {
+                        return Some((value, key));
+                    }
+                }
+            }
+        }
+    };
+}
+
+iterator!(Iter<'a, T, K>, std::slice::Iter<'a, Option<T>>, &'a T);
+iterator!(
+    IterMut<'a, T, K>,
+    std::slice::IterMut<'a, Option<T>>,
+    &'a mut T
+);
+iterator!(IntoIter<T, K>, std::vec::IntoIter<Option<T>>, T);
+
+/// Removes the values of an arena in key order. Whatever is left when it is dropped is removed
+/// then, so the arena ends up empty however far it was iterated, like [`Vec::drain`]
+pub struct Drain<'a, T, K> {
+    arena: &'a mut Arena<T, K>,
+    idx: usize,
+}
+
+impl<T, K> Iterator for Drain<'_, T, K> {
+    type Item = (T, Key<K>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.idx < self.arena.inner.len() {
+            let key = key_at(self.idx);
+            self.idx += 1;
+            if let Some(value) = key.and_then(|key| self.arena.remove(key)) {
+                return Some((value, key.unwrap()));
+            }
+        }
+        None
+    }
+}
+
+impl<T, K> Drop for Drain<'_, T, K> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T, K> Arena<T, K> {
+    pub fn iter(&self) -> Iter<'_, T, K> {
+        Iter {
+            inner: self.inner.iter().enumerate(),
+            phantom: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, K> {
+        IterMut {
+            inner: self.inner.iter_mut().enumerate(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// remove every value, in key order
+    pub fn drain(&mut self) -> Drain<'_, T, K> {
+        Drain {
+            arena: self,
+            idx: 1,
+        }
+    }
+
+    pub fn keys(&self) -> impl '_ + Iterator<Item = Key<K>> {
+        self.iter().map(|(_, key)| key)
+    }
+
+    pub fn values(&self) -> impl '_ + Iterator<Item = &T> {
+        self.iter().map(|(value, _)| value)
+    }
+
+    pub fn values_mut(&mut self) -> impl '_ + Iterator<Item = &mut T> {
+        self.iter_mut().map(|(value, _)| value)
+    }
+}
+
+impl<T, K> IntoIterator for Arena<T, K> {
+    type Item = (T, Key<K>);
+    type IntoIter = IntoIter<T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.inner.into_iter().enumerate(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, T, K> IntoIterator for &'a Arena<T, K> {
+    type Item = (&'a T, Key<K>);
+    type IntoIter = Iter<'a, T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, K> IntoIterator for &'a mut Arena<T, K> {
+    type Item = (&'a mut T, Key<K>);
+    type IntoIter = IterMut<'a, T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, K> FromIterator<T> for Arena<T, K> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut arena = Self::new();
+        arena.extend(iter);
+        arena
+    }
+}
+
+impl<T, K> Extend<T> for Arena<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
@@ -6,6 +6,7 @@ use std::{
     ops::Deref,
 };
 
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 pub struct Key<T = ()>(NonZeroU32, PhantomData<T>);
@@ -20,6 +21,7 @@ impl<T> Deref for Key<T> {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<T> Serialize for Key<T> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -29,6 +31,7 @@ impl<T> Serialize for Key<T> {
     }
 }
 
+#[cfg(feature = "serde")]
 impl<'de, T> Deserialize<'de> for Key<T> {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -131,6 +134,100 @@ impl<T> Key<T> {
     }
 }
 
+/// A key that also remembers the generation of its slot. Once the slot is freed, every key to it
+/// goes stale, even after the slot is filled again
+pub struct GenKey<T = ()> {
+    pub key: Key<T>,
+    pub generation: u32,
+}
+
+impl<T> std::fmt::Display for GenKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}", self.key, self.generation)
+    }
+}
+
+impl<T> std::fmt::Debug for GenKey<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}#{}", self.key, self.generation)
+    }
+}
+
+impl<T> PartialEq for GenKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for GenKey<T> {}
+
+impl<T> Hash for GenKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&self.key, state);
+        Hash::hash(&self.generation, state);
+    }
+}
+
+impl<T> Clone for GenKey<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GenKey<T> {}
+
+impl<T> From<GenKey<T>> for Key<T> {
+    fn from(key: GenKey<T>) -> Self {
+        key.key
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> Serialize for GenKey<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.key, self.generation).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> Deserialize<'de> for GenKey<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (key, generation) = Deserialize::deserialize(deserializer)?;
+        Ok(Self { key, generation })
+    }
+}
+
+/// Anything that picks a slot of an arena. A plain [`Key`] matches whatever is in the slot, a
+/// [`GenKey`] only matches the generation it was made for
+pub trait ArenaKey<T>: Copy + std::fmt::Display {
+    fn key(&self) -> Key<T>;
+    fn generation(&self) -> Option<u32>;
+}
+
+impl<T> ArenaKey<T> for Key<T> {
+    fn key(&self) -> Key<T> {
+        *self
+    }
+    fn generation(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl<T> ArenaKey<T> for GenKey<T> {
+    fn key(&self) -> Key<T> {
+        self.key
+    }
+    fn generation(&self) -> Option<u32> {
+        Some(self.generation)
+    }
+}
+
 #[macro_export]
 macro_rules! key {
     ($name:ident) => {};
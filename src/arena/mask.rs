@@ -2,43 +2,169 @@ use std::marker::PhantomData;
 
 use crate::arena::{Arena, Key};
 
-/// A compact visit flag for an arena more effecient than a HashSet
+const WORD: usize = u64::BITS as usize;
+
+/// A compact visit flag for an arena more effecient than a HashSet. It covers every slot of the
+/// arena it was made for, and grows when a key past its end is set
 pub struct ArenaBitMask<K> {
     bits: Vec<u64>,
+    /// how many slots are covered
+    len: usize,
     phantom: PhantomData<K>,
 }
 
-impl<K> ArenaBitMask<K> {
-    pub fn new<T>(arena: &Arena<T, K>) -> Self {
-        let bits = Vec::with_capacity(arena.size() / 64);
+impl<K> Clone for ArenaBitMask<K> {
+    fn clone(&self) -> Self {
         Self {
-            bits,
+            bits: self.bits.clone(),
+            len: self.len,
             phantom: PhantomData,
         }
     }
+}
 
-    pub fn reset(&mut self) {
-        self.bits.fill(0);
+impl<K> Default for ArenaBitMask<K> {
+    fn default() -> Self {
+        Self::with_capacity(0)
     }
+}
 
+impl<K> ArenaBitMask<K> {
+    pub fn new<T>(arena: &Arena<T, K>) -> Self {
+        Self::with_capacity(arena.size())
+    }
+
+    /// a mask covering the first `capacity` keys
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            bits: Vec::with_capacity(capacity),
+            bits: vec![0; capacity.div_ceil(WORD)],
+            len: capacity,
             phantom: PhantomData,
         }
     }
 
+    /// how many keys are covered, setting a key past the end grows the mask
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// cover every slot of the arena, after it grew
+    pub fn resize_for<T>(&mut self, arena: &Arena<T, K>) {
+        self.grow(arena.size());
+    }
+
+    fn grow(&mut self, len: usize) {
+        if len > self.len {
+            self.len = len;
+            self.bits.resize(len.div_ceil(WORD), 0);
+        }
+    }
+
+    /// the word and bit of a key, growing the mask to cover it
+    fn slot_mut(&mut self, key: Key<K>) -> (&mut u64, u64) {
+        let idx = key.get() as usize - 1;
+        self.grow(idx + 1);
+        (&mut self.bits[idx / WORD], 1 << (idx % WORD))
+    }
+
+    pub fn reset(&mut self) {
+        self.bits.fill(0);
+    }
+
     pub fn flip(&mut self, key: Key<K>) {
-        let idx = key.get() - 1;
-        let word = &mut self.bits[(idx / 64) as usize];
-        let bit = idx % 64;
-        *word ^= 1 << bit;
+        let (word, bit) = self.slot_mut(key);
+        *word ^= bit;
+    }
+
+    /// set the flag, returns false if it was already set
+    pub fn set(&mut self, key: Key<K>) -> bool {
+        let (word, bit) = self.slot_mut(key);
+        let was_set = *word & bit != 0;
+        *word |= bit;
+        !was_set
+    }
+
+    /// clear the flag, returns false if it was not set
+    pub fn clear(&mut self, key: Key<K>) -> bool {
+        let idx = key.get() as usize - 1;
+        let Some(word) = self.bits.get_mut(idx / WORD) else {
+            return false;
+        };
+        let bit = 1 << (idx % WORD);
+        let was_set = *word & bit != 0;
+        *word &= !bit;
+        was_set
     }
 
     pub fn is_flipped(&self, key: Key<K>) -> bool {
-        let idx = key.get() - 1;
-        let word = self.bits[(idx / 64) as usize];
-        let bit = idx % 64;
-        word & 1 << bit != 0
+        let idx = key.get() as usize - 1;
+        match self.bits.get(idx / WORD) {
+            Some(word) => word & 1 << (idx % WORD) != 0,
+            None => false,
+        }
+    }
+
+    /// how many flags are set
+    pub fn count(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// every key with its flag set, in key order
+    pub fn iter_set(&self) -> impl '_ + Iterator<Item = Key<K>> {
+        self.bits.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Key::new((i * WORD + bit + 1) as u32)
+            })
+        })
+    }
+
+    /// set every flag that is set in `other`
+    pub fn union_with(&mut self, other: &Self) {
+        self.grow(other.len);
+        for (word, other) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+    }
+
+    /// clear every flag that is not set in `other`
+    pub fn intersect_with(&mut self, other: &Self) {
+        for (i, word) in self.bits.iter_mut().enumerate() {
+            *word &= other.bits.get(i).copied().unwrap_or(0);
+        }
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut union = self.clone();
+        union.union_with(other);
+        union
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut intersection = self.clone();
+        intersection.intersect_with(other);
+        intersection
+    }
+}
+
+impl<K> FromIterator<Key<K>> for ArenaBitMask<K> {
+    fn from_iter<I: IntoIterator<Item = Key<K>>>(iter: I) -> Self {
+        let mut mask = Self::default();
+        for key in iter {
+            mask.set(key);
+        }
+        mask
     }
 }
@@ -1,14 +1,23 @@
 use std::{
+    marker::PhantomData,
     mem::ManuallyDrop,
     ops::{Index, IndexMut},
     slice::GetDisjointMutError,
 };
 
+mod compact;
+mod iter;
 mod key;
 mod mask;
+mod secondary;
+#[cfg(feature = "serde")]
+mod serialize;
 
-pub use key::Key;
+pub use compact::KeyRemap;
+pub use iter::{Drain, IntoIter, Iter, IterMut};
+pub use key::{ArenaKey, GenKey, Key};
 pub use mask::ArenaBitMask;
+pub use secondary::{ComponentStore, SecondaryMap};
 
 pub enum Slot<T, K> {
     Filled(T),
@@ -17,16 +26,22 @@ pub enum Slot<T, K> {
 
 pub struct Arena<T, K = ()> {
     inner: Vec<Option<T>>,
+    /// the generation of every slot, bumped every time the slot is freed
+    generations: Vec<u32>,
     free: Vec<u32>,
-    next_key: Key<K>,
+    /// how many values are in the arena, the zero slot left out
+    len: usize,
+    phantom: PhantomData<K>,
 }
 
 impl<T, K> Default for Arena<T, K> {
     fn default() -> Self {
         Self {
             inner: vec![None],
+            generations: vec![0],
             free: vec![],
-            next_key: unsafe { Key::new_unchecked(1) },
+            len: 0,
+            phantom: PhantomData,
         }
     }
 }
@@ -43,6 +58,18 @@ impl<T, K> Arena<T, K> {
         }
     }
 
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut inner = Vec::with_capacity(capacity + 1);
+        inner.push(None);
+        let mut generations = Vec::with_capacity(capacity + 1);
+        generations.push(0);
+        Self {
+            inner,
+            generations,
+            ..Default::default()
+        }
+    }
+
     pub fn zero(&self) -> &Option<T> {
         &self.inner[0]
     }
@@ -51,39 +78,63 @@ impl<T, K> Arena<T, K> {
         &mut self.inner[0]
     }
 
-    pub fn from_iter(iter: impl Iterator<Item = T>) -> Self {
-        let mut inner = Vec::new();
-        inner.push(None);
-        inner.extend(iter.map(Some));
-
-        Self {
-            inner,
-            free: vec![],
-            next_key: unsafe { Key::new_unchecked(1) },
-        }
+    /// the key past the last slot
+    fn new_key(&mut self) -> Key<K> {
+        self.inner.push(None);
+        self.generations.push(0);
+        Key::new(self.inner.len() as u32 - 1).expect("an arena holds at most u32::MAX slots")
     }
 
-    fn new_key(&mut self) -> Key<K> {
-        let key = self.next_key;
-        self.next_key = unsafe { Key::new_unchecked(key.get() + 1) };
-        key
+    /// how many values are in the arena
+    pub fn len(&self) -> usize {
+        self.len
     }
 
-    fn len(&self) -> usize {
-        self.inner.len()
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
+    /// how many slots there are, empty ones included
     pub fn size(&self) -> usize {
         self.inner.len() - 1
     }
 
-    pub fn get(&self, key: Key<K>) -> Option<&T> {
-        match self.inner.get(key.get() as usize) {
+    /// whether `key` was made for the current generation of its slot. plain keys never go stale
+    pub fn is_stale(&self, key: impl ArenaKey<K>) -> bool {
+        match key.generation() {
+            Some(generation) => self.generation(key.key()) != Some(generation),
+            None => false,
+        }
+    }
+
+    /// how many times the slot has been freed
+    pub fn generation(&self, key: Key<K>) -> Option<u32> {
+        self.generations.get(key.get() as usize).copied()
+    }
+
+    /// a key that goes stale once the value it points at is removed
+    pub fn gen_key(&self, key: Key<K>) -> Option<GenKey<K>> {
+        self.get(key)?;
+        Some(GenKey {
+            key,
+            generation: self.generation(key)?,
+        })
+    }
+
+    pub fn get(&self, key: impl ArenaKey<K>) -> Option<&T> {
+        if self.is_stale(key) {
+            return None;
+        }
+        match self.inner.get(key.key().get() as usize) {
             Some(Some(t)) => Some(t),
             _ => None,
         }
     }
 
+    pub fn contains_key(&self, key: impl ArenaKey<K>) -> bool {
+        self.get(key).is_some()
+    }
+
     pub fn get_many<const N: usize>(&self, keys: [Key<K>; N]) -> [Option<&T>; N] {
         let mut out: [Option<&T>; N] = [None; N];
         for (i, key) in keys.iter().enumerate() {
@@ -92,8 +143,11 @@ impl<T, K> Arena<T, K> {
         return out;
     }
 
-    pub fn get_mut(&mut self, key: Key<K>) -> Option<&mut T> {
-        match self.inner.get_mut(key.get() as usize) {
+    pub fn get_mut(&mut self, key: impl ArenaKey<K>) -> Option<&mut T> {
+        if self.is_stale(key) {
+            return None;
+        }
+        match self.inner.get_mut(key.key().get() as usize) {
             Some(Some(t)) => Some(t),
             _ => None,
         }
@@ -127,17 +181,25 @@ impl<T, K> Arena<T, K> {
 
     pub fn set(&mut self, key: Key<K>, value: T) {
         let idx = key.get() as usize;
-        if idx > self.len() - 1 {
+        if idx >= self.inner.len() {
             self.inner.resize_with(idx + 1, Default::default);
+            self.generations.resize(idx + 1, 0);
+        }
+        if self.inner[idx].replace(value).is_none() {
+            self.len += 1;
         }
-        self.inner[idx] = Some(value);
     }
 
-    pub fn remove(&mut self, key: Key<K>) -> Option<T> {
-        let idx = key.get();
-        if let Some(value) = self.inner.get_mut(key.get() as usize) {
+    pub fn remove(&mut self, key: impl ArenaKey<K>) -> Option<T> {
+        if self.is_stale(key) {
+            return None;
+        }
+        let idx = key.key().get();
+        if let Some(value) = self.inner.get_mut(idx as usize) {
             if let Some(value) = value.take() {
                 self.free.push(idx);
+                self.generations[idx as usize] = self.generations[idx as usize].wrapping_add(1);
+                self.len -= 1;
                 return Some(value);
             } else {
                 return None;
@@ -147,26 +209,44 @@ impl<T, K> Arena<T, K> {
     }
 
     pub fn insert(&mut self, value: T) -> Key<K> {
-        if let Some(idx) = self.free.pop() {
-            self.inner[idx as usize] = Some(value);
-            let key = unsafe { Key::new_unchecked(idx) };
-            key
-        } else {
-            let key = self.new_key();
-            self.inner.push(Some(value));
-            key
+        let key = self.reserve();
+        self.set(key, value);
+        key
+    }
+
+    /// insert a value that needs its own key, like a record pointing at itself
+    pub fn insert_with(&mut self, f: impl FnOnce(Key<K>) -> T) -> Key<K> {
+        let key = self.reserve();
+        self.set(key, f(key));
+        key
+    }
+
+    /// insert values that need each other's keys, like the two halves of an edge
+    pub fn insert_many_with<const N: usize>(
+        &mut self,
+        f: impl FnOnce([Key<K>; N]) -> [T; N],
+    ) -> [Key<K>; N] {
+        let keys = std::array::from_fn(|_| self.reserve());
+        for (key, value) in keys.into_iter().zip(f(keys)) {
+            self.set(key, value);
+        }
+        keys
+    }
+
+    pub fn insert_gen(&mut self, value: T) -> GenKey<K> {
+        let key = self.insert(value);
+        GenKey {
+            key,
+            generation: self.generations[key.get() as usize],
         }
     }
 
+    /// a key for an empty slot, to be filled with [`set`](Self::set). the slot freed last is
+    /// taken first, so what is removed last to first comes back under the same keys
     pub fn reserve(&mut self) -> Key<K> {
-        if let Some(idx) = self.free.pop() {
-            self.inner[idx as usize] = None;
-            let key = unsafe { Key::new_unchecked(idx) };
-            key
-        } else {
-            let key = self.new_key();
-            self.inner.push(None);
-            key
+        match self.free.pop() {
+            Some(idx) => unsafe { Key::new_unchecked(idx) },
+            None => self.new_key(),
         }
     }
 
@@ -181,44 +261,59 @@ impl<T, K> Arena<T, K> {
         }
     }
 
-    pub fn iter<'a>(&'a self) -> impl 'a + Iterator<Item = (&'a T, Key<K>)> {
-        self.inner
-            .iter()
-            .enumerate()
-            .skip(1)
-            .filter_map(|(i, v)| match v {
-                Some(v) => Some((v, self.key(i as u32)?)),
-                None => None,
-            })
+    /// keep only the values `f` returns true for, the others are removed like with
+    /// [`remove`](Self::remove)
+    pub fn retain(&mut self, mut f: impl FnMut(Key<K>, &mut T) -> bool) {
+        for idx in 1..self.inner.len() {
+            let key = unsafe { Key::new_unchecked(idx as u32) };
+            if let Some(value) = &mut self.inner[idx]
+                && !f(key, value)
+            {
+                self.remove(key);
+            }
+        }
     }
 
-    pub fn iter_mut<'a>(&'a mut self) -> impl 'a + Iterator<Item = (&'a mut T, Key<K>)> {
-        self.inner
-            .iter_mut()
-            .enumerate()
-            .skip(1)
-            .filter_map(|(i, v)| match v {
-                Some(v) => Some((v, unsafe { Key::new_unchecked(i as u32) })),
-                None => None,
-            })
+    pub fn clear(&mut self) {
+        self.drain().for_each(drop);
     }
 }
 
-impl<T, K> Index<Key<K>> for Arena<T, K> {
-    type Output = T;
-    fn index(&self, index: Key<K>) -> &Self::Output {
-        match self.get(index) {
-            Some(v) => v,
-            None => panic!("{} is out of bounds", index),
+impl<T, K> Arena<T, K> {
+    /// why a key has nothing behind it
+    fn missing(&self, key: impl ArenaKey<K>) -> String {
+        match self.generation(key.key()) {
+            Some(generation) if self.is_stale(key) => {
+                format!("{key} is stale, its slot is at generation {generation}")
+            }
+            Some(_) => format!("{key} points at an empty slot"),
+            None => format!("{key} is out of bounds"),
         }
     }
 }
 
-impl<T, K> IndexMut<Key<K>> for Arena<T, K> {
-    fn index_mut(&mut self, index: Key<K>) -> &mut Self::Output {
-        match self.get_mut(index) {
-            Some(v) => v,
-            None => panic!(""),
+macro_rules! index {
+    ($key:ident) => {
+        impl<T, K> Index<$key<K>> for Arena<T, K> {
+            type Output = T;
+            fn index(&self, index: $key<K>) -> &Self::Output {
+                match self.get(index) {
+                    Some(v) => v,
+                    None => panic!("{}", self.missing(index)),
+                }
+            }
         }
-    }
+
+        impl<T, K> IndexMut<$key<K>> for Arena<T, K> {
+            fn index_mut(&mut self, index: $key<K>) -> &mut Self::Output {
+                if self.get(index).is_none() {
+                    panic!("{}", self.missing(index));
+                }
+                self.get_mut(index).unwrap()
+            }
+        }
+    };
 }
+
+index!(Key);
+index!(GenKey);
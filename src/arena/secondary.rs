@@ -0,0 +1,267 @@
+//! Data kept beside an arena rather than in it. Every entry remembers the generation of the slot it
+//! was inserted for, so once the value in the primary arena is removed the entry is ignored, even
+//! if the slot is filled again
+
+use std::marker::PhantomData;
+
+use crate::arena::{Arena, ArenaKey, Key};
+
+/// whether the slot still holds the value an entry was made for
+fn is_live<T, K>(arena: &Arena<T, K>, key: impl ArenaKey<K>, generation: u32) -> bool {
+    arena.get(key).is_some() && arena.generation(key.key()) == Some(generation)
+}
+
+/// the generation of the slot, if there is a live value behind the key
+fn live_generation<T, K>(arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<u32> {
+    arena.get(key)?;
+    arena.generation(key.key())
+}
+
+/// Sparse per slot storage, as large as the primary arena
+pub struct SecondaryMap<K, V> {
+    slots: Vec<Option<(u32, V)>>,
+    phantom: PhantomData<K>,
+}
+
+impl<K, V> Default for SecondaryMap<K, V> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<K, V> SecondaryMap<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// insert a value for a key of `arena`, returning the live value it replaces. nothing is
+    /// inserted when the key has nothing behind it in `arena`
+    pub fn insert<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>, value: V) -> Option<V> {
+        let generation = live_generation(arena, key)?;
+        let idx = key.key().get() as usize;
+        if idx >= self.slots.len() {
+            self.slots.resize_with(idx + 1, || None);
+        }
+        match self.slots[idx].replace((generation, value)) {
+            Some((old, value)) if old == generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get<T>(&self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<&V> {
+        match self.slots.get(key.key().get() as usize) {
+            Some(Some((generation, value))) if is_live(arena, key, *generation) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<&mut V> {
+        match self.slots.get_mut(key.key().get() as usize) {
+            Some(Some((generation, value))) if is_live(arena, key, *generation) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn contains<T>(&self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> bool {
+        self.get(arena, key).is_some()
+    }
+
+    pub fn remove<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<V> {
+        let slot = self.slots.get_mut(key.key().get() as usize)?;
+        match slot {
+            Some((generation, _)) if is_live(arena, key, *generation) => {
+                slot.take().map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    /// every live entry, in key order
+    pub fn iter<'a, T>(
+        &'a self,
+        arena: &'a Arena<T, K>,
+    ) -> impl 'a + Iterator<Item = (Key<K>, &'a V)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(idx, slot)| {
+                let (generation, value) = slot.as_ref()?;
+                let key = Key::new(idx as u32)?;
+                is_live(arena, key, *generation).then_some((key, value))
+            })
+    }
+
+    pub fn iter_mut<'a, T>(
+        &'a mut self,
+        arena: &'a Arena<T, K>,
+    ) -> impl 'a + Iterator<Item = (Key<K>, &'a mut V)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(idx, slot)| {
+                let (generation, value) = slot.as_mut()?;
+                let key = Key::new(idx as u32)?;
+                is_live(arena, key, *generation).then_some((key, value))
+            })
+    }
+
+    /// keep the live entries `f` returns true for, every other entry is dropped
+    pub fn retain<T>(&mut self, arena: &Arena<T, K>, mut f: impl FnMut(Key<K>, &mut V) -> bool) {
+        for (idx, slot) in self.slots.iter_mut().enumerate() {
+            let keep = match (slot.as_mut(), Key::new(idx as u32)) {
+                (Some((generation, value)), Some(key)) => {
+                    is_live(arena, key, *generation) && f(key, value)
+                }
+                _ => false,
+            };
+            if !keep {
+                *slot = None;
+            }
+        }
+    }
+
+    pub fn len<T>(&self, arena: &Arena<T, K>) -> usize {
+        self.iter(arena).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+}
+
+/// Dense storage, its values are packed together however sparse the keys are
+pub struct ComponentStore<K, V> {
+    values: Vec<V>,
+    /// the key and generation each value was inserted for
+    owners: Vec<(Key<K>, u32)>,
+    /// where the value of every slot is in `values`
+    index: Vec<Option<u32>>,
+}
+
+impl<K, V> Default for ComponentStore<K, V> {
+    fn default() -> Self {
+        Self {
+            values: vec![],
+            owners: vec![],
+            index: vec![],
+        }
+    }
+}
+
+impl<K, V> ComponentStore<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position(&self, key: Key<K>) -> Option<usize> {
+        self.index
+            .get(key.get() as usize)
+            .copied()
+            .flatten()
+            .map(|pos| pos as usize)
+    }
+
+    /// the position of a live entry
+    fn live_position<T>(&self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<usize> {
+        let pos = self.position(key.key())?;
+        is_live(arena, key, self.owners[pos].1).then_some(pos)
+    }
+
+    /// insert a value for a key of `arena`, returning the live value it replaces. nothing is
+    /// inserted when the key has nothing behind it in `arena`
+    pub fn insert<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>, value: V) -> Option<V> {
+        let generation = live_generation(arena, key)?;
+        let key = key.key();
+        if let Some(pos) = self.position(key) {
+            let old = std::mem::replace(&mut self.values[pos], value);
+            let (_, old_generation) = std::mem::replace(&mut self.owners[pos], (key, generation));
+            return (old_generation == generation).then_some(old);
+        }
+
+        let idx = key.get() as usize;
+        if idx >= self.index.len() {
+            self.index.resize(idx + 1, None);
+        }
+        self.index[idx] = Some(self.values.len() as u32);
+        self.values.push(value);
+        self.owners.push((key, generation));
+        None
+    }
+
+    pub fn get<T>(&self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<&V> {
+        self.live_position(arena, key).map(|pos| &self.values[pos])
+    }
+
+    pub fn get_mut<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<&mut V> {
+        self.live_position(arena, key)
+            .map(|pos| &mut self.values[pos])
+    }
+
+    pub fn contains<T>(&self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> bool {
+        self.live_position(arena, key).is_some()
+    }
+
+    /// drop the value at `pos`, moving the last value into its place
+    fn swap_remove(&mut self, pos: usize) -> V {
+        let (key, _) = self.owners.swap_remove(pos);
+        self.index[key.get() as usize] = None;
+        if let Some((moved, _)) = self.owners.get(pos) {
+            self.index[moved.get() as usize] = Some(pos as u32);
+        }
+        self.values.swap_remove(pos)
+    }
+
+    pub fn remove<T>(&mut self, arena: &Arena<T, K>, key: impl ArenaKey<K>) -> Option<V> {
+        let pos = self.live_position(arena, key)?;
+        Some(self.swap_remove(pos))
+    }
+
+    /// every live entry, in the order they are stored
+    pub fn iter<'a, T>(
+        &'a self,
+        arena: &'a Arena<T, K>,
+    ) -> impl 'a + Iterator<Item = (Key<K>, &'a V)> {
+        self.owners
+            .iter()
+            .zip(&self.values)
+            .filter(|((key, generation), _)| is_live(arena, *key, *generation))
+            .map(|((key, _), value)| (*key, value))
+    }
+
+    pub fn iter_mut<'a, T>(
+        &'a mut self,
+        arena: &'a Arena<T, K>,
+    ) -> impl 'a + Iterator<Item = (Key<K>, &'a mut V)> {
+        self.owners
+            .iter()
+            .zip(&mut self.values)
+            .filter(|((key, generation), _)| is_live(arena, *key, *generation))
+            .map(|((key, _), value)| (*key, value))
+    }
+
+    /// keep the live entries `f` returns true for, every other entry is dropped
+    pub fn retain<T>(&mut self, arena: &Arena<T, K>, mut f: impl FnMut(Key<K>, &mut V) -> bool) {
+        let mut pos = 0;
+        while pos < self.values.len() {
+            let (key, generation) = self.owners[pos];
+            if is_live(arena, key, generation) && f(key, &mut self.values[pos]) {
+                pos += 1;
+            } else {
+                self.swap_remove(pos);
+            }
+        }
+    }
+
+    pub fn len<T>(&self, arena: &Arena<T, K>) -> usize {
+        self.iter(arena).count()
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.owners.clear();
+        self.index.clear();
+    }
+}
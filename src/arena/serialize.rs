@@ -0,0 +1,81 @@
+//! Arenas are serialized slot by slot, empty ones included, so every key stays valid, stale keys
+//! stay stale and freed slots are reused in the same order after a round trip
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use crate::arena::Arena;
+
+#[derive(Serialize)]
+struct RawRef<'a, T> {
+    slots: &'a [Option<T>],
+    generations: &'a [u32],
+    free: &'a [u32],
+}
+
+#[derive(Deserialize)]
+struct Raw<T> {
+    slots: Vec<Option<T>>,
+    /// every slot is at generation 0 when left out
+    #[serde(default)]
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl<T: Serialize, K> Serialize for Arena<T, K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        RawRef {
+            slots: &self.inner,
+            generations: &self.generations,
+            free: &self.free,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, K> Deserialize<'de> for Arena<T, K> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Raw {
+            slots,
+            mut generations,
+            free,
+        } = Raw::deserialize(deserializer)?;
+        if slots.is_empty() || u32::try_from(slots.len()).is_err() {
+            return Err(D::Error::custom(
+                "an arena has between 1 and u32::MAX slots",
+            ));
+        }
+
+        if generations.is_empty() {
+            generations = vec![0; slots.len()];
+        } else if generations.len() != slots.len() {
+            return Err(D::Error::custom("an arena has one generation per slot"));
+        }
+
+        let mut freed = vec![false; slots.len()];
+        for &idx in &free {
+            let idx = idx as usize;
+            match slots.get(idx) {
+                Some(None) if idx != 0 && !freed[idx] => freed[idx] = true,
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "free slot {idx} is not an empty slot, or is freed twice"
+                    )));
+                }
+            }
+        }
+
+        Ok(Self {
+            len: slots[1..].iter().flatten().count(),
+            inner: slots,
+            generations,
+            free,
+            phantom: std::marker::PhantomData,
+        })
+    }
+}
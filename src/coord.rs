@@ -1,61 +1,165 @@
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display},
+    ops::{Add, Mul, Neg, Sub},
+};
 
+mod predicates;
+
+pub(crate) use predicates::{Expansion, orient2d_exact};
+pub use predicates::{incircle, orient2d};
+
+/// the scalar coordinates used to have before they were generic
+#[deprecated(note = "coordinates are generic over `Scalar`, name the scalar of the vertex instead")]
 pub type Precision = f32;
 
+/// The number type of a coordinate. Floats work as they are, integers make a grid that every
+/// coordinate snaps to, and a fixed point type only needs to say how it converts to and from `f64`.
+/// Predicates are exact for every scalar that converts to `f64` without rounding
+pub trait Scalar:
+    Copy
+    + Debug
+    + PartialEq
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+    + Display
+    + 'static
+{
+    const ZERO: Self;
+    fn to_f64(self) -> f64;
+    /// the closest value, integer grids round to the nearest step
+    fn from_f64(value: f64) -> Self;
+    fn is_finite(self) -> bool {
+        true
+    }
+    /// the ends of the range of `f64` that [`Scalar::from_f64`] rounds to this value, the extent
+    /// of the hot pixel around a coordinate when crossings are snap rounded. an end rounds to this
+    /// value or to its neighbour. without it only the value itself is taken, which leaves exact
+    /// incidences alone to snap to
+    fn pixel(self) -> [f64; 2] {
+        [self.to_f64(); 2]
+    }
+}
+
+macro_rules! float_scalar {
+    ($($ty:ty),*) => {$(
+        impl Scalar for $ty {
+            const ZERO: Self = 0.;
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self {
+                value as $ty
+            }
+            fn is_finite(self) -> bool {
+                <$ty>::is_finite(self)
+            }
+            fn pixel(self) -> [f64; 2] {
+                let value = self as f64;
+                // from_f64 keeps an f64 as it is
+                if <$ty>::MANTISSA_DIGITS >= f64::MANTISSA_DIGITS {
+                    return [value; 2];
+                }
+                [self.next_down(), self.next_up()].map(|next| (next as f64 + value) / 2.)
+            }
+        }
+    )*};
+}
+
+macro_rules! int_scalar {
+    ($($ty:ty),*) => {$(
+        impl Scalar for $ty {
+            const ZERO: Self = 0;
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+            fn from_f64(value: f64) -> Self {
+                value.round() as $ty
+            }
+            fn pixel(self) -> [f64; 2] {
+                [-0.5, 0.5].map(|half| self as f64 + half)
+            }
+        }
+    )*};
+}
+
+float_scalar!(f32, f64);
+int_scalar!(i32, i64);
+
+/// convert between scalars, going through `f64`
+pub fn cast<A: Scalar, B: Scalar>(value: A) -> B {
+    B::from_f64(value.to_f64())
+}
+
 pub trait Coordinate {
-    fn xyz(&self) -> [Precision; 3];
-    fn xy(&self) -> [Precision; 2] {
+    type Scalar: Scalar;
+    fn xyz(&self) -> [Self::Scalar; 3];
+    fn xy(&self) -> [Self::Scalar; 2] {
         [self.x(), self.y()]
     }
-    fn x(&self) -> Precision {
+    fn x(&self) -> Self::Scalar {
         self.xyz()[0]
     }
-    fn y(&self) -> Precision {
+    fn y(&self) -> Self::Scalar {
         self.xyz()[1]
     }
-    fn z(&self) -> Precision {
+    fn z(&self) -> Self::Scalar {
         self.xyz()[2]
     }
 }
 
-impl Coordinate for [Precision; 3] {
-    fn xyz(&self) -> [Precision; 3] {
+impl<S: Scalar> Coordinate for [S; 3] {
+    type Scalar = S;
+    fn xyz(&self) -> [S; 3] {
         *self
     }
 }
 
-impl Coordinate for (Precision, Precision, Precision) {
-    fn xyz(&self) -> [Precision; 3] {
+impl<S: Scalar> Coordinate for (S, S, S) {
+    type Scalar = S;
+    fn xyz(&self) -> [S; 3] {
         [self.0, self.1, self.2]
     }
 }
 
-impl Coordinate for (Precision, Precision) {
-    fn xyz(&self) -> [Precision; 3] {
-        [self.0, self.1, 0.]
+impl<S: Scalar> Coordinate for (S, S) {
+    type Scalar = S;
+    fn xyz(&self) -> [S; 3] {
+        [self.0, self.1, S::ZERO]
     }
 }
 
-impl Coordinate for [Precision; 2] {
-    fn xyz(&self) -> [Precision; 3] {
-        [self[0], self[1], 0.]
+impl<S: Scalar> Coordinate for [S; 2] {
+    type Scalar = S;
+    fn xyz(&self) -> [S; 3] {
+        [self[0], self[1], S::ZERO]
     }
 }
 
-pub trait FromCoordinate {
-    fn from_xyz(xyz: [Precision; 3]) -> Self
+pub trait FromCoordinate: Coordinate {
+    fn from_xyz(xyz: [Self::Scalar; 3]) -> Self
     where
         Self: Sized;
-    fn from_xy([x, y]: [Precision; 2]) -> Self
+    fn from_xy([x, y]: [Self::Scalar; 2]) -> Self
     where
         Self: Sized,
     {
-        Self::from_xyz([x, y, 0.])
+        Self::from_xyz([x, y, Self::Scalar::ZERO])
+    }
+    /// take the position of any coordinate, whatever its scalar
+    fn from_coordinate(coord: &impl Coordinate) -> Self
+    where
+        Self: Sized,
+    {
+        Self::from_xyz(coord.xyz().map(cast))
     }
 }
 
-impl FromCoordinate for [f32; 2] {
-    fn from_xyz([x, y, z]: [Precision; 3]) -> Self
+impl<S: Scalar> FromCoordinate for [S; 2] {
+    fn from_xyz([x, y, z]: [S; 3]) -> Self
     where
         Self: Sized,
     {
@@ -63,8 +167,8 @@ impl FromCoordinate for [f32; 2] {
     }
 }
 
-impl FromCoordinate for [f32; 3] {
-    fn from_xyz(xyz: [Precision; 3]) -> Self
+impl<S: Scalar> FromCoordinate for [S; 3] {
+    fn from_xyz(xyz: [S; 3]) -> Self
     where
         Self: Sized,
     {
@@ -72,10 +176,22 @@ impl FromCoordinate for [f32; 3] {
     }
 }
 
-pub trait UpdateCoordinate {
-    fn set_xyz(&mut self, xyz: [Precision; 3]);
-    fn set_xy(&mut self, [x, y]: [Precision; 2]) {
-        Self::set_xyz(self, [x, y, 0.]);
+pub trait UpdateCoordinate: Coordinate {
+    fn set_xyz(&mut self, xyz: [Self::Scalar; 3]);
+    fn set_xy(&mut self, [x, y]: [Self::Scalar; 2]) {
+        Self::set_xyz(self, [x, y, Self::Scalar::ZERO]);
+    }
+}
+
+impl<S: Scalar> UpdateCoordinate for [S; 3] {
+    fn set_xyz(&mut self, xyz: [S; 3]) {
+        *self = xyz;
+    }
+}
+
+impl<S: Scalar> UpdateCoordinate for [S; 2] {
+    fn set_xyz(&mut self, [x, y, _]: [S; 3]) {
+        *self = [x, y];
     }
 }
 
@@ -95,13 +211,13 @@ impl Winding {
 
 #[derive(Debug, PartialEq)]
 pub enum Orientation {
-    Clockwise(Precision),
-    Counterclockwise(Precision),
+    Clockwise(f64),
+    Counterclockwise(f64),
     Neutral,
 }
 
-fn orientation([x1, y1]: [Precision; 2], [x2, y2]: [Precision; 2]) -> Orientation {
-    let [x, y] = [x2 - x1, y2 - y1];
+fn orientation<S: Scalar>([x1, y1]: [S; 2], [x2, y2]: [S; 2]) -> Orientation {
+    let [x, y] = [(x2 - x1).to_f64(), (y2 - y1).to_f64()];
     let theta = y.atan2(x);
     match theta {
         theta if theta > 0. => Orientation::Counterclockwise(theta),
@@ -110,12 +226,12 @@ fn orientation([x1, y1]: [Precision; 2], [x2, y2]: [Precision; 2]) -> Orientatio
     }
 }
 
-pub fn polar_angle([x, y]: [Precision; 2], [cx, cy]: [Precision; 2]) -> Precision {
-    (y - cy).atan2(x - cx)
+pub fn polar_angle<S: Scalar>([x, y]: [S; 2], [cx, cy]: [S; 2]) -> f64 {
+    (y - cy).to_f64().atan2((x - cx).to_f64())
 }
 
 impl Orientation {
-    pub fn from_points(from: [Precision; 2], to: [Precision; 2]) -> Self {
+    pub fn from_points<S: Scalar>(from: [S; 2], to: [S; 2]) -> Self {
         orientation(from, to)
     }
 
@@ -141,21 +257,20 @@ impl Orientation {
     }
 }
 
-pub fn sort_clockwise(
-    [cx, cy]: [Precision; 2],
-    [ax, ay]: [Precision; 2],
-    [bx, by]: [Precision; 2],
-) -> Ordering {
-    if ax - cx >= 0. && bx - cx < 0. {
+/// order points clockwise around a center, starting at twelve o clock. points in the same
+/// direction are ordered from far to near. every decision is exact
+pub fn sort_clockwise<S: Scalar>(c: [S; 2], a: [S; 2], b: [S; 2]) -> Ordering {
+    let [[cx, cy], [ax, ay], [bx, by]] = [c, a, b];
+    if ax >= cx && bx < cx {
         return Ordering::Less;
     }
 
-    if ax - cx < 0. && bx - cx >= 0. {
+    if ax < cx && bx >= cx {
         return Ordering::Greater;
     }
 
-    if ax - cx == 0. && bx - cx == 0. {
-        if ay - cy >= 0. || by - cy >= 0. {
+    if ax == cx && bx == cx {
+        if ay >= cy || by >= cy {
             return if ay > by {
                 Ordering::Less
             } else {
@@ -169,7 +284,7 @@ pub fn sort_clockwise(
         };
     }
 
-    let det = (ax - cx) * (by - cy) - (bx - cx) * (ay - cy);
+    let det = orient2d(c, a, b);
     if det < 0. {
         return Ordering::Less;
     }
@@ -178,16 +293,114 @@ pub fn sort_clockwise(
         return Ordering::Greater;
     }
 
-    let d1 = (ax - cx) * (ax - cx) + (ay - cy) * (ay - cy);
-    let d2 = (bx - cx) * (bx - cx) + (by - cy) * (by - cy);
-
-    if d1 > d2 {
+    // a and b lie on the same ray from c, the one further along it is further away
+    let axis = if ax != cx || bx != cx { 0 } else { 1 };
+    let further = if a[axis] > c[axis] || b[axis] > c[axis] {
+        a[axis] > b[axis]
+    } else {
+        a[axis] < b[axis]
+    };
+    if further {
         Ordering::Less
     } else {
         Ordering::Greater
     }
 }
 
+/// twice the signed area of the triangle a b c, positive when it turns counterclockwise. computed
+/// in `f64`, so integer grids can't overflow, but rounded: [`orient2d`] has the exact sign
+pub fn cross<S: Scalar>(a: [S; 2], b: [S; 2], c: [S; 2]) -> f64 {
+    let [[ax, ay], [bx, by], [cx, cy]] = [a, b, c].map(|p| p.map(S::to_f64));
+    (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+}
+
+/// whether p lies on the closed segment a b
+pub fn on_segment<S: Scalar>(p: [S; 2], a: [S; 2], b: [S; 2]) -> bool {
+    let between = |p: S, a: S, b: S| (a <= p && p <= b) || (b <= p && p <= a);
+    orient2d(a, b, p) == 0. && between(p[0], a[0], b[0]) && between(p[1], a[1], b[1])
+}
+
+/// whether the closed segments a b and c d share any point
+pub fn segments_intersect<S: Scalar>(a: [S; 2], b: [S; 2], c: [S; 2], d: [S; 2]) -> bool {
+    let [d1, d2] = [orient2d(c, d, a), orient2d(c, d, b)];
+    let [d3, d4] = [orient2d(a, b, c), orient2d(a, b, d)];
+    if ((d1 > 0. && d2 < 0.) || (d1 < 0. && d2 > 0.))
+        && ((d3 > 0. && d4 < 0.) || (d3 < 0. && d4 > 0.))
+    {
+        return true;
+    }
+    on_segment(a, c, d) || on_segment(b, c, d) || on_segment(c, a, b) || on_segment(d, a, b)
+}
+
+/// even-odd test of a point against a closed polygon, given as its segments
+pub fn polygon_encloses<S: Scalar>(
+    segments: impl IntoIterator<Item = [[S; 2]; 2]>,
+    [px, py]: [S; 2],
+) -> bool {
+    let mut inside = false;
+    for [from, to] in segments {
+        // the ray to the right crosses the segment when p is left of it, seen going upwards
+        let side = orient2d(from, to, [px, py]);
+        let left = if to[1] > from[1] {
+            side > 0.
+        } else {
+            side < 0.
+        };
+        if (from[1] > py) != (to[1] > py) && left {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// whether p lies inside or on the triangle a b c, which may be degenerate
+pub fn in_triangle<S: Scalar>(p: [S; 2], a: [S; 2], b: [S; 2], c: [S; 2]) -> bool {
+    let [d1, d2, d3] = [orient2d(a, b, p), orient2d(b, c, p), orient2d(c, a, p)];
+    if d1 == 0. && d2 == 0. && d3 == 0. {
+        return on_segment(p, a, b) || on_segment(p, b, c) || on_segment(p, c, a);
+    }
+    let has_neg = d1 < 0. || d2 < 0. || d3 < 0.;
+    let has_pos = d1 > 0. || d2 > 0. || d3 > 0.;
+    !(has_neg && has_pos)
+}
+
+/// the position of a point along a hilbert curve through `bounds`, split into a 2^16 by 2^16 grid.
+/// points close on the curve are close in the plane
+pub fn hilbert_index<S: Scalar>(
+    [px, py]: [S; 2],
+    [[min_x, min_y], [max_x, max_y]]: [[S; 2]; 2],
+) -> u64 {
+    const SIDE: u32 = 1 << 16;
+    let cell = |p: S, min: S, max: S| {
+        let [p, min, max] = [p, min, max].map(S::to_f64);
+        let t = if max > min {
+            (p - min) / (max - min)
+        } else {
+            0.
+        };
+        (t.clamp(0., 1.) * (SIDE - 1) as f64).round() as u32
+    };
+    let [mut x, mut y] = [cell(px, min_x, max_x), cell(py, min_y, max_y)];
+
+    let mut index = 0;
+    let mut s = SIDE / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        index += (s as u64) * (s as u64) * ((3 * rx) ^ ry) as u64;
+        // rotate the quadrant so the curve inside it starts and ends where it should
+        if ry == 0 {
+            if rx == 1 {
+                x = SIDE - 1 - x;
+                y = SIDE - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
 #[test]
 fn test_orientation() {
     assert!(
@@ -242,3 +455,105 @@ fn test_sort_clockwise() {
 
     print!("{:?}", sorted_points);
 }
+
+#[test]
+fn test_segments_intersect() {
+    assert!(segments_intersect([0., 0.], [2., 2.], [0., 2.], [2., 0.]));
+    assert!(segments_intersect([0., 0.], [2., 0.], [1., 0.], [3., 0.]));
+    assert!(segments_intersect([0., 0.], [2., 0.], [2., 0.], [2., 2.]));
+    assert!(!segments_intersect([0., 0.], [2., 0.], [0., 1.], [2., 1.]));
+    assert!(!segments_intersect([0., 0.], [1., 0.], [2., 0.], [3., 0.]));
+
+    assert!(in_triangle([1., 1.], [0., 0.], [4., 0.], [0., 4.]));
+    assert!(in_triangle([2., 0.], [0., 0.], [4., 0.], [0., 4.]));
+    assert!(!in_triangle([3., 3.], [0., 0.], [4., 0.], [0., 4.]));
+    assert!(in_triangle([1., 0.], [0., 0.], [2., 0.], [2., 0.]));
+}
+
+#[test]
+fn test_cross_on_a_wide_grid() {
+    // the differences and products overflow i32 and i64
+    let [min, max] = [i32::MIN, i32::MAX];
+    let side = max as f64 - min as f64;
+    assert_eq!(cross([min, min], [max, min], [min, max]), side * side);
+    assert_eq!(cross([min, min], [min, max], [max, min]), -side * side);
+    assert_eq!(cross([max, max], [max - 1, max - 1], [min, min]), 0.);
+    assert!(orient2d([min, min], [max, min], [max - 1, max]) > 0.);
+}
+
+#[test]
+fn test_pixel() {
+    assert_eq!(3i64.pixel(), [2.5, 3.5]);
+    // everything strictly inside rounds to the value, the edges are halfway to the neighbours
+    let [low, high] = 1f32.pixel();
+    assert!(low < 1. && high > 1.);
+    assert_eq!(f32::from_f64(low.next_up()), 1.);
+    assert_eq!(f32::from_f64(high.next_down()), 1.);
+    assert_eq!(f32::from_f64(high.next_up()), 1f32.next_up());
+    assert_eq!(0.1f64.pixel(), [0.1; 2]);
+}
+
+#[test]
+fn test_hilbert_index() {
+    let bounds = [[0., 0.], [1., 1.]];
+    let quadrants = [[0.25, 0.25], [0.25, 0.75], [0.75, 0.75], [0.75, 0.25]]
+        .map(|point| hilbert_index(point, bounds));
+    assert!(quadrants.is_sorted());
+    assert_eq!(hilbert_index([0., 0.], bounds), 0);
+    assert_eq!(hilbert_index([1., 0.], bounds), (1 << 32) - 1);
+    // neighbouring cells follow each other along the curve
+    let step = 1. / ((1 << 16) - 1) as f32;
+    assert_eq!(hilbert_index([0., step], bounds), 3);
+    assert_eq!(hilbert_index([step, step], bounds), 2);
+}
+
+#[test]
+fn test_orient2d_near_collinear() {
+    // points a few ulps off the line y = x, far from its anchors
+    let [q, r] = [[12., 12.], [24., 24.]];
+    let ulp = f32::EPSILON / 4.;
+    let mut naive_wrong = 0;
+    for i in 0..32 {
+        for j in 0..32 {
+            let p = [0.5 + i as f32 * ulp, 0.5 + j as f32 * ulp];
+            let expected = p[1].partial_cmp(&p[0]).unwrap();
+            assert_eq!(
+                orient2d(q, r, p).partial_cmp(&0.).unwrap(),
+                expected,
+                "{p:?}"
+            );
+            let naive = (r[0] - q[0]) * (p[1] - q[1]) - (r[1] - q[1]) * (p[0] - q[0]);
+            naive_wrong += (naive.partial_cmp(&0.).unwrap() != expected) as u32;
+        }
+    }
+    assert!(naive_wrong > 0);
+
+    // the same ray, ordered far to near, and both sides of it
+    let c = [0.5, 0.5];
+    let [near, far] = [[12., 12.], [24., 24.]];
+    assert_eq!(sort_clockwise(c, far, near), Ordering::Less);
+    assert_eq!(sort_clockwise(c, near, far), Ordering::Greater);
+    // one ulp above the segment, and on it
+    let above = [12., 12. + 8. * f32::EPSILON];
+    assert!(orient2d(c, far, above) > 0.);
+    assert!(!on_segment(above, c, far));
+    assert!(on_segment(near, c, far));
+    assert!(segments_intersect(c, far, near, above));
+}
+
+#[test]
+fn test_incircle() {
+    let [a, b, c] = [[1., 0.], [0., 1.], [-1., 0.]];
+    assert_eq!(incircle(a, b, c, [0., -1.]), 0.);
+    assert!(incircle(a, b, c, [0., -0.99]) > 0.);
+    assert!(incircle(a, b, c, [0., -1.01]) < 0.);
+    assert!(incircle(c, b, a, [0., -0.99]) < 0.);
+
+    // cocircular points far from the origin, and a point one ulp inside
+    let offset = 1024.;
+    let shift = |[x, y]: [f32; 2]| [x + offset, y + offset];
+    let [a, b, c] = [shift(a), shift(b), shift(c)];
+    assert_eq!(incircle(a, b, c, shift([0., -1.])), 0.);
+    let inside = [offset, offset - 1. + offset * f32::EPSILON];
+    assert!(incircle(a, b, c, inside) > 0.);
+}
@@ -0,0 +1,175 @@
+//! Geometric predicates with an exact sign, after Shewchuk's "Adaptive Precision Floating-Point
+//! Arithmetic and Fast Robust Geometric Predicates". The determinant is evaluated in `f64` first,
+//! and only when its error bound can't rule out a wrong sign it is evaluated again as an exact
+//! expansion, a sum of non overlapping `f64`s. Scalars are taken through `f64`, so the sign is
+//! exact for `f32`, `f64` and integers up to 2^53
+
+use crate::coord::Scalar;
+
+/// half an ulp of 1, the relative error of a single rounding
+const EPSILON: f64 = f64::EPSILON / 2.;
+const ORIENT_BOUND: f64 = (3. + 16. * EPSILON) * EPSILON;
+const INCIRCLE_BOUND: f64 = (10. + 96. * EPSILON) * EPSILON;
+
+/// twice the signed area of the triangle a b c. positive when a b c turn counterclockwise, negative
+/// when they turn clockwise and zero when they are collinear. the sign is exact, the magnitude is
+/// only as good as plain floating point
+pub fn orient2d<S: Scalar>(a: [S; 2], b: [S; 2], c: [S; 2]) -> f64 {
+    let [a, b, c] = [a, b, c].map(|p| p.map(S::to_f64));
+    let left = (a[0] - c[0]) * (b[1] - c[1]);
+    let right = (a[1] - c[1]) * (b[0] - c[0]);
+    let det = left - right;
+
+    let sum = left.abs() + right.abs();
+    if det.abs() >= ORIENT_BOUND * sum {
+        return det;
+    }
+
+    orient2d_exact(a, b, c).estimate()
+}
+
+/// twice the signed area of the triangle a b c, as an exact expansion
+pub(crate) fn orient2d_exact(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> Expansion {
+    let [acx, acy, bcx, bcy] = [
+        Expansion::diff(a[0], c[0]),
+        Expansion::diff(a[1], c[1]),
+        Expansion::diff(b[0], c[0]),
+        Expansion::diff(b[1], c[1]),
+    ];
+    acx.mul(&bcy).sub(&acy.mul(&bcx))
+}
+
+/// positive when d lies inside the circle through a b c, given counterclockwise, negative when it
+/// lies outside and zero when the four are cocircular. the sign flips when a b c turn clockwise, and
+/// is exact
+pub fn incircle<S: Scalar>(a: [S; 2], b: [S; 2], c: [S; 2], d: [S; 2]) -> f64 {
+    let [a, b, c, d] = [a, b, c, d].map(|p| p.map(S::to_f64));
+    let [adx, ady] = [a[0] - d[0], a[1] - d[1]];
+    let [bdx, bdy] = [b[0] - d[0], b[1] - d[1]];
+    let [cdx, cdy] = [c[0] - d[0], c[1] - d[1]];
+
+    let [bc, ca, ab] = [
+        [bdx * cdy, cdx * bdy],
+        [cdx * ady, adx * cdy],
+        [adx * bdy, bdx * ady],
+    ];
+    let lifts = [
+        adx * adx + ady * ady,
+        bdx * bdx + bdy * bdy,
+        cdx * cdx + cdy * cdy,
+    ];
+    let det = lifts[0] * (bc[0] - bc[1]) + lifts[1] * (ca[0] - ca[1]) + lifts[2] * (ab[0] - ab[1]);
+
+    let permanent = lifts[0] * (bc[0].abs() + bc[1].abs())
+        + lifts[1] * (ca[0].abs() + ca[1].abs())
+        + lifts[2] * (ab[0].abs() + ab[1].abs());
+    if det.abs() >= INCIRCLE_BOUND * permanent {
+        return det;
+    }
+
+    let [a, b, c] = [a, b, c].map(|p| [Expansion::diff(p[0], d[0]), Expansion::diff(p[1], d[1])]);
+    let lift = |[x, y]: &[Expansion; 2]| x.mul(x).add(&y.mul(y));
+    let cross = |[px, py]: &[Expansion; 2], [qx, qy]: &[Expansion; 2]| px.mul(qy).sub(&py.mul(qx));
+    lift(&a)
+        .mul(&cross(&b, &c))
+        .add(&lift(&b).mul(&cross(&c, &a)))
+        .add(&lift(&c).mul(&cross(&a, &b)))
+        .estimate()
+}
+
+/// an exact sum of `f64`s, each smaller than the bits the next one covers and none of them zero.
+/// there is room for only so many of those in the exponent range of `f64`, so expansions stay short
+#[derive(Clone, Default)]
+pub(crate) struct Expansion(Vec<f64>);
+
+impl Expansion {
+    pub(crate) fn new(value: f64) -> Self {
+        Self::diff(value, 0.)
+    }
+
+    /// the exact difference of two floats
+    pub(crate) fn diff(a: f64, b: f64) -> Self {
+        let (sum, err) = two_sum(a, -b);
+        Self([err, sum].into_iter().filter(|v| *v != 0.).collect())
+    }
+
+    /// the exact product of two floats
+    pub(crate) fn product(a: f64, b: f64) -> Self {
+        let (product, err) = two_product(a, b);
+        Self([err, product].into_iter().filter(|v| *v != 0.).collect())
+    }
+
+    /// add a single float, Shewchuk's GROW-EXPANSION with the zeros left out
+    pub(crate) fn grow(mut self, value: f64) -> Self {
+        let mut carry = value;
+        let mut out = Vec::with_capacity(self.0.len() + 1);
+        for component in self.0.drain(..) {
+            let (sum, err) = two_sum(carry, component);
+            if err != 0. {
+                out.push(err);
+            }
+            carry = sum;
+        }
+        if carry != 0. {
+            out.push(carry);
+        }
+        Self(out)
+    }
+
+    pub(crate) fn add(&self, other: &Self) -> Self {
+        other
+            .0
+            .iter()
+            .fold(self.clone(), |sum, value| sum.grow(*value))
+    }
+
+    pub(crate) fn neg(&self) -> Self {
+        Self(self.0.iter().map(|value| -value).collect())
+    }
+
+    pub(crate) fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub(crate) fn mul(&self, other: &Self) -> Self {
+        let mut product = Self::default();
+        for a in &self.0 {
+            for b in &other.0 {
+                product = product.add(&Self::product(*a, *b));
+            }
+        }
+        product
+    }
+
+    /// the largest component carries the sign of the whole sum
+    pub(crate) fn signum(&self) -> f64 {
+        match self.0.last() {
+            Some(value) => value.signum(),
+            None => 0.,
+        }
+    }
+
+    /// the sum rounded to a float, with the sign of the exact sum
+    pub(crate) fn estimate(&self) -> f64 {
+        let estimate: f64 = self.0.iter().sum();
+        if estimate == 0. || estimate.signum() != self.signum() {
+            self.signum() * f64::MIN_POSITIVE
+        } else {
+            estimate
+        }
+    }
+}
+
+/// the rounded sum and its rounding error
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let sum = a + b;
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    (sum, (a - a_virtual) + (b - b_virtual))
+}
+
+/// the rounded product and its rounding error
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let product = a * b;
+    (product, a.mul_add(b, -product))
+}
@@ -0,0 +1,410 @@
+//! Inserting line segments that may cross, touch or overlap what is already there, snap rounded
+//! to the scalar of the dcel
+
+use std::{cmp::Ordering, fmt::Debug};
+
+use crate::{
+    arena::Key,
+    coord::{Coordinate, FromCoordinate, Scalar, UpdateCoordinate},
+    dcel::{
+        Dcel, EdgeKey, Entity, Flavor, OpGroup, Operator, OperatorErr, RollbackErr, SpacialIndex,
+        Transaction, Traverser, VertexKey,
+        ops::{self, AnyKey},
+        snap::{self, SelectHotAlong, SelectThroughPixel},
+    },
+    index::{Aabb, SpatialIndex},
+};
+
+type Point<F> = [<<F as Flavor>::Vertex as Coordinate>::Scalar; 2];
+
+/// an edge that is bent, its ends and the crossings it has to go through
+type Bent<F> = (Key<EdgeKey>, [Point<F>; 2], Vec<Point<F>>);
+
+/// what an insertion made, and the group that takes it out again
+type Inserted<F, T> = Result<(T, OpGroup<F>), SegmentError<F>>;
+
+#[derive(thiserror::Error)]
+pub enum SegmentError<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    #[error("segment coordinate is not finite")]
+    NonFiniteCoordinate,
+    #[error("there is no bounding face to connect the vertices already there in, nor room for one")]
+    NoBoundingFace,
+    #[error("rounding the segments left a face without a boundary")]
+    Degenerate,
+    #[error("an operator failed")]
+    Op(ops::Error<F>),
+}
+
+impl<F: Flavor> Debug for SegmentError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl<F: Flavor> From<RollbackErr<F>> for SegmentError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn from(err: RollbackErr<F>) -> Self {
+        Self::Op(err.into())
+    }
+}
+
+impl<F: Flavor, O: Operator<F>> From<OperatorErr<O, O::Error>> for SegmentError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    ops::Error<F>: From<OperatorErr<O, O::Error>>,
+{
+    fn from(err: OperatorErr<O, O::Error>) -> Self {
+        Self::Op(err.into())
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: Coordinate,
+{
+    /// the half edge running from one vertex to another, if they are joined
+    pub fn edge_between(&self, from: Key<VertexKey>, to: Key<VertexKey>) -> Option<Key<EdgeKey>> {
+        Traverser::around(self, from)
+            .ok()?
+            .find(|edge| edge.twin(self).origin(self) == to)
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    /// insert the segment from a to b, snap rounded to the scalar of the dcel: where it crosses
+    /// an edge is rounded to a vertex, and the segment and every edge passing through the hot
+    /// pixel of a vertex, the points that round to it, are bent to go through the vertex. the
+    /// edges that are bent are taken out and made again. returns the vertices along it from a to
+    /// b, and the group that takes it out again
+    pub fn insert_segment(&mut self, a: Point<F>, b: Point<F>) -> Inserted<F, Vec<Key<VertexKey>>> {
+        let mut index = SpacialIndex::new(self);
+        self.transaction(|tx| insert_segment(tx, &mut index, [a, b]))
+    }
+
+    /// insert segments one after the other, as a single edit. a segment can bend the edges of
+    /// the ones before it, so their vertices are kept but not always joined directly
+    pub fn insert_segments(
+        &mut self,
+        segments: impl IntoIterator<Item = [Point<F>; 2]>,
+    ) -> Inserted<F, Vec<Vec<Key<VertexKey>>>> {
+        let mut index = SpacialIndex::new(self);
+        self.transaction(|tx| {
+            segments
+                .into_iter()
+                .map(|segment| insert_segment(tx, &mut index, segment))
+                .collect()
+        })
+    }
+}
+
+fn insert_segment<F: Flavor>(
+    tx: &mut Transaction<'_, F>,
+    index: &mut SpacialIndex<F>,
+    [a, b]: [Point<F>; 2],
+) -> Result<Vec<Key<VertexKey>>, SegmentError<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    if !a.iter().chain(&b).all(|c| c.is_finite()) {
+        return Err(SegmentError::NonFiniteCoordinate);
+    }
+    // the first edge makes the bounding face
+    if tx.dcel().vertices.is_empty() && a != b {
+        let kvvef = tx.apply(ops::Mvvef {
+            data: (
+                F::Vertex::from_xy(a),
+                F::Vertex::from_xy(b),
+                Default::default(),
+                Default::default(),
+                Some(Default::default()),
+            ),
+        })?;
+        let vertices = kvvef.vertices;
+        index.refresh(tx.dcel(), vertices.map(AnyKey::from));
+        return Ok(vertices.to_vec());
+    }
+    // isolated vertices can't be joined before there is a bounding face, a scaffold makes one
+    let mut scaffold = None;
+    if tx.dcel().bounding_face.is_none() && a != b {
+        scaffold = Some(make_scaffold(tx, index, [a, b])?);
+    }
+
+    let segment = [a, b];
+    let envelope = Aabb::from_points(segment).unwrap();
+    let mut crossings = vec![];
+    for entity in index.locate_in_envelope_intersecting(envelope) {
+        if let Entity::Edge([edge, _], ends) = entity
+            && let Some(point) = snap::crossing(segment, *ends)
+        {
+            crossings.push((*edge, *ends, point.map(Scalar::from_f64)));
+        }
+    }
+
+    // the new hot pixels, at the ends and the crossings where there is no vertex yet
+    let mut centers: Vec<Point<F>> = [a, b]
+        .into_iter()
+        .chain(crossings.iter().map(|(.., point)| *point))
+        .filter(|point| vertex_at(index, *point).is_none())
+        .collect();
+    centers.sort_by(|p, q| p.partial_cmp(q).unwrap_or(Ordering::Equal));
+    centers.dedup();
+
+    // the edges that pass through them are bent, and through the crossings they take part in
+    let mut bent: Vec<Bent<F>> = vec![];
+    let mut add = |edge, ends, forced: Option<Point<F>>| {
+        let at = match bent.iter().position(|(e, ..)| *e == edge) {
+            Some(at) => at,
+            None => {
+                bent.push((edge, ends, vec![]));
+                bent.len() - 1
+            }
+        };
+        bent[at].2.extend(forced);
+    };
+    for center in &centers {
+        for entity in index.select(SelectThroughPixel(*center)) {
+            if let Entity::Edge([edge, _], ends) = entity {
+                add(*edge, *ends, None);
+            }
+        }
+    }
+    for (edge, ends, point) in &crossings {
+        add(*edge, *ends, Some(*point));
+    }
+
+    let mut hot = |piece: [Point<F>; 2]| {
+        let mut points: Vec<_> = index
+            .select(SelectHotAlong(piece))
+            .filter_map(|entity| match entity {
+                Entity::Vertex(_, point) => Some(*point),
+                _ => None,
+            })
+            .collect();
+        points.extend(centers.iter().filter(|center| snap::meets(piece, **center)));
+        points
+    };
+    let forced: Vec<_> = crossings.iter().map(|(.., point)| *point).collect();
+    let path = snap::route(segment, &forced, &mut hot);
+    let routes: Vec<_> = bent
+        .iter()
+        .map(|(_, ends, forced)| snap::route(*ends, forced, &mut hot))
+        .collect();
+
+    // out with the edges that are bent, in with the vertices and the edges of every route
+    for (edge, ..) in &bent {
+        kill(tx, index, *edge, &mut scaffold, segment)?;
+    }
+    for center in &centers {
+        let vertex = tx
+            .apply(ops::Mvh {
+                vertex: F::Vertex::from_xy(*center),
+            })?
+            .vertex;
+        index.refresh(tx.dcel(), [vertex.into()]);
+    }
+    let path: Vec<_> = path
+        .into_iter()
+        .map(|point| vertex_at(index, point).unwrap())
+        .collect();
+    for route in routes {
+        let route: Vec<_> = route
+            .into_iter()
+            .map(|point| vertex_at(index, point).unwrap())
+            .collect();
+        connect(tx, index, &route)?;
+    }
+    connect(tx, index, &path)?;
+
+    if let Some(scaffold) = scaffold {
+        let mut keys = scaffold.vertices.map(AnyKey::from).to_vec();
+        keys.extend(scaffold.edges.map(AnyKey::from));
+        tx.apply(scaffold)?;
+        index.refresh(tx.dcel(), keys);
+    }
+    Ok(path)
+}
+
+/// the vertex at point
+fn vertex_at<F: Flavor>(index: &SpacialIndex<F>, point: Point<F>) -> Option<Key<VertexKey>>
+where
+    F::Vertex: Coordinate,
+{
+    index
+        .locate_all_at_point(point)
+        .find_map(|entity| match entity {
+            Entity::Vertex(vertex, _) => Some(*vertex),
+            _ => None,
+        })
+}
+
+/// join every vertex of a path to the next, where they are not joined yet
+fn connect<F: Flavor>(
+    tx: &mut Transaction<'_, F>,
+    index: &mut SpacialIndex<F>,
+    path: &[Key<VertexKey>],
+) -> Result<(), SegmentError<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    for pair in path.windows(2) {
+        let [from, to] = [pair[0], pair[1]];
+        if tx.dcel().edge_between(from, to).is_none() {
+            join::<F, SegmentError<F>>(tx, [from, to])?;
+            index.refresh(tx.dcel(), [from.into(), to.into()]);
+        }
+    }
+    Ok(())
+}
+
+/// take out an edge that is bent, merging the faces on either side of it, or splitting the
+/// boundary it is on
+fn kill<F: Flavor>(
+    tx: &mut Transaction<'_, F>,
+    index: &mut SpacialIndex<F>,
+    edge: Key<EdgeKey>,
+    scaffold: &mut Option<ops::Kvvef>,
+    segment: [Point<F>; 2],
+) -> Result<(), SegmentError<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    let dcel = tx.dcel();
+    let edges = [edge, edge.twin(dcel)];
+    let ends = edges.map(|edge| AnyKey::from(edge.origin(dcel)));
+    let mut killed = edges.map(AnyKey::from).to_vec();
+    let [face, other] = edges.map(|edge| edge.face(dcel));
+    if face != other {
+        // the one the inverse makes again, so that undoing it brings back the same keys
+        let face = if dcel.bounding_face == Some(face) {
+            other
+        } else if dcel.bounding_face == Some(other) {
+            face
+        } else {
+            let [area, other_area] = edges.map(|edge| Traverser::shoestring(dcel, edge).unwrap());
+            match area.cmp_area(&other_area) {
+                Ordering::Less => face,
+                _ => other,
+            }
+        };
+        killed.push(face.into());
+        tx.apply(ops::Kef { face, edges })?;
+    } else {
+        let kemh = ops::Kemh { edges };
+        // the last edge of the bounding face can only go once there is another
+        if let Err(ops::KemhError::WouldKillFace) = kemh.check(dcel)
+            && scaffold.is_none()
+        {
+            *scaffold = Some(make_scaffold(tx, index, segment)?);
+        }
+        tx.apply(kemh)?;
+    }
+    index.refresh(tx.dcel(), killed.into_iter().chain(ends));
+    Ok(())
+}
+
+/// a lone edge above or below everything there is and the segment, which makes the bounding face
+/// while the segment goes in
+fn make_scaffold<F: Flavor>(
+    tx: &mut Transaction<'_, F>,
+    index: &mut SpacialIndex<F>,
+    segment: [Point<F>; 2],
+) -> Result<ops::Kvvef, SegmentError<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    let ys = tx
+        .dcel()
+        .vertices
+        .iter()
+        .map(|(vertex, _)| vertex.weight.y())
+        .chain(segment.map(|[_, y]| y));
+    let (bottom, top) = ys.fold((segment[0][1], segment[0][1]), |(bottom, top), y| {
+        (
+            if y < bottom { y } else { bottom },
+            if y > top { y } else { top },
+        )
+    });
+    let clear = |extreme: <F::Vertex as Coordinate>::Scalar, up: bool| {
+        let extreme64 = extreme.to_f64();
+        let step = extreme64.abs().max(1.) * 2.;
+        let y = Scalar::from_f64(if up {
+            extreme64 + step
+        } else {
+            extreme64 - step
+        });
+        let apart = match up {
+            true => Scalar::pixel(y)[0] > extreme.pixel()[1],
+            false => Scalar::pixel(y)[1] < extreme.pixel()[0],
+        };
+        apart.then_some(y)
+    };
+    let y = clear(top, true)
+        .or_else(|| clear(bottom, false))
+        .ok_or(SegmentError::NoBoundingFace)?;
+    let [left, right] = [0., 1.].map(|x| F::Vertex::from_xy([Scalar::from_f64(x), y]));
+    let kvvef = tx
+        .apply(ops::Mvvef {
+            data: (
+                left,
+                right,
+                Default::default(),
+                Default::default(),
+                Some(Default::default()),
+            ),
+        })?
+        .clone();
+    index.refresh(tx.dcel(), kvvef.vertices.map(AnyKey::from));
+    Ok(kvvef)
+}
+
+/// join two vertices of a face with an edge. it makes a new face when they are on the same boundary
+/// of it, and joins the boundaries into one otherwise
+pub(crate) fn join<F: Flavor, E>(
+    tx: &mut Transaction<'_, F>,
+    vertices: [Key<VertexKey>; 2],
+) -> Result<(), E>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    F::Edge: Default,
+    F::Face: Default,
+    E: From<OperatorErr<ops::Mef<F>, ops::MefError>>
+        + From<OperatorErr<ops::Mekh<F>, ops::MekhError>>,
+{
+    let mef = ops::Mef {
+        vertices,
+        data: Default::default(),
+    };
+    match mef.check(tx.dcel()) {
+        Err(ops::MefError::IsolatedVertex | ops::MefError::BoundaryMismatch) => {
+            tx.apply(ops::Mekh {
+                vertices,
+                data: Default::default(),
+            })?;
+        }
+        _ => {
+            tx.apply(mef)?;
+        }
+    }
+    Ok(())
+}
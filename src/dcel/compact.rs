@@ -0,0 +1,110 @@
+//! Dropping the empty slots from the arenas of a dcel, and fixing up every pointer into them
+
+use crate::{
+    arena::{Key, KeyRemap},
+    coord::{Coordinate, hilbert_index},
+    dcel::{Dcel, EdgeKey, FaceKey, Flavor, Generation, HolRef, VertexKey, ops::KeyVisitor},
+};
+
+/// Where every vertex, edge and face of a compacted dcel went. Keys held outside the dcel, like
+/// the ones in an undo stack, have to go through it, ops can be rewritten with
+/// [`VisitKeys`](crate::dcel::ops::VisitKeys)
+pub struct DcelRemap {
+    pub vertices: KeyRemap<VertexKey>,
+    pub edges: KeyRemap<EdgeKey>,
+    pub faces: KeyRemap<FaceKey>,
+}
+
+/// keys that had nothing behind them are left alone
+impl KeyVisitor for DcelRemap {
+    fn vertex(&mut self, key: &mut Key<VertexKey>) {
+        self.vertices.apply(key);
+    }
+    fn edge(&mut self, key: &mut Key<EdgeKey>) {
+        self.edges.apply(key);
+    }
+    fn face(&mut self, key: &mut Key<FaceKey>) {
+        self.faces.apply(key);
+    }
+}
+
+impl<F: Flavor> Dcel<F> {
+    /// move every vertex, edge and face to the front of its arena, keeping their order
+    pub fn compact(&mut self) -> DcelRemap {
+        let remap = DcelRemap {
+            vertices: self.vertices.compact(),
+            edges: self.edges.compact(),
+            faces: self.faces.compact(),
+        };
+        self.remap(&remap);
+        remap
+    }
+
+    fn remap(&mut self, remap: &DcelRemap) {
+        for (vertex, _) in self.vertices.iter_mut() {
+            if let Some(edge) = &mut vertex.edge {
+                remap.edges.apply(edge);
+            }
+        }
+        for (edge, _) in self.edges.iter_mut() {
+            remap.vertices.apply(&mut edge.origin);
+            remap.edges.apply(&mut edge.twin);
+            remap.edges.apply(&mut edge.prev);
+            remap.edges.apply(&mut edge.next);
+            remap.faces.apply(&mut edge.face);
+        }
+        for (face, _) in self.faces.iter_mut() {
+            remap.edges.apply(&mut face.edge);
+            for hole in &mut face.holes {
+                match hole {
+                    HolRef::Edge(edge) => remap.edges.apply(edge),
+                    HolRef::Vertex(vertex) => remap.vertices.apply(vertex),
+                };
+            }
+        }
+        if let Some(face) = &mut self.bounding_face {
+            remap.faces.apply(face);
+        }
+        self.generation = Generation::next();
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: Coordinate,
+{
+    /// compact, placing vertices and edges along a hilbert curve so the ones close together in
+    /// the plane are close together in memory, and walking a face touches fewer cache lines
+    pub fn compact_spatial(&mut self) -> DcelRemap {
+        let mut bounds = None;
+        for vertex in self.vertices.values() {
+            let p = vertex.weight.xy();
+            let [min, max] = bounds.get_or_insert([p, p]);
+            for axis in 0..2 {
+                if p[axis] < min[axis] {
+                    min[axis] = p[axis];
+                }
+                if p[axis] > max[axis] {
+                    max[axis] = p[axis];
+                }
+            }
+        }
+        let Some(bounds) = bounds else {
+            return self.compact();
+        };
+
+        let vertices = &self.vertices;
+        let place = |vertex: Key<VertexKey>| hilbert_index(vertices[vertex].weight.xy(), bounds);
+        let edges = self.edges.compact_by_key(|_, edge| place(edge.origin));
+        let vertices = self
+            .vertices
+            .compact_by_key(|_, vertex| hilbert_index(vertex.weight.xy(), bounds));
+        let remap = DcelRemap {
+            vertices,
+            edges,
+            faces: self.faces.compact(),
+        };
+        self.remap(&remap);
+        remap
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{
     arena::Key,
-    coord::{Coordinate, FromCoordinate},
+    coord::{Coordinate, FromCoordinate, Scalar},
     dcel::{Dcel, FaceKey, Flavor, VertexKey},
 };
 
 pub struct Draw<F: Flavor> {
-    coord: [f32; 3],
+    coord: [f64; 3],
     last_key: Key<VertexKey>,
     dcel: Dcel<F>,
 }
@@ -22,11 +22,14 @@ where
         v2: impl Coordinate,
     ) -> (Self, [Key<VertexKey>; 2]) {
         let kvvef = dcel
-            .mvvef(F::Vertex::from_xyz(v1.xyz()), F::Vertex::from_xyz(v2.xyz()))
+            .mvvef(
+                F::Vertex::from_coordinate(&v1),
+                F::Vertex::from_coordinate(&v2),
+            )
             .unwrap();
         (
             Self {
-                coord: v2.xyz(),
+                coord: v2.xyz().map(Scalar::to_f64),
                 last_key: kvvef.vertices[1],
                 dcel,
             },
@@ -34,6 +37,23 @@ where
         )
     }
 
+    /// keep drawing into an existing dcel, starting from a new isolated vertex
+    pub fn start(mut dcel: Dcel<F>, v: impl Coordinate) -> (Self, Key<VertexKey>) {
+        let vertex = dcel.mvh(F::Vertex::from_coordinate(&v)).unwrap().vertex;
+        (
+            Self {
+                coord: v.xyz().map(Scalar::to_f64),
+                last_key: vertex,
+                dcel,
+            },
+            vertex,
+        )
+    }
+
+    pub fn dcel(&self) -> &Dcel<F> {
+        &self.dcel
+    }
+
     /// will panic if you have not called start
     pub fn key(&self) -> Key<VertexKey> {
         self.last_key
@@ -44,19 +64,19 @@ where
     pub fn move_to(&mut self, coord: impl Coordinate) -> Key<VertexKey> {
         self.last_key = self
             .dcel
-            .mvh(F::Vertex::from_xyz(coord.xyz()))
+            .mvh(F::Vertex::from_coordinate(&coord))
             .unwrap()
             .vertex;
-        self.coord = coord.xyz();
+        self.coord = coord.xyz().map(Scalar::to_f64);
         self.last_key
     }
     pub fn line_to(&mut self, coord: impl Coordinate) -> Key<VertexKey> {
         self.last_key = self
             .dcel
-            .mve(self.last_key, F::Vertex::from_xyz(coord.xyz()))
+            .mve(self.last_key, F::Vertex::from_coordinate(&coord))
             .unwrap()
             .vertex;
-        self.coord = coord.xyz();
+        self.coord = coord.xyz().map(Scalar::to_f64);
         self.last_key
     }
     pub fn close_path(&mut self, to: Key<VertexKey>) -> Key<FaceKey> {
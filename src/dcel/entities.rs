@@ -40,6 +40,7 @@ where
     }
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VertexPtrs {
     pub edge: Option<Key<EdgeKey>>,
 }
@@ -49,6 +50,7 @@ impl HasKey for VertexPtrs {
 
 pub type Vertex<W> = Weighted<VertexPtrs, W>;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EdgePtrs {
     pub origin: Key<VertexKey>,
     pub twin: Key<EdgeKey>,
@@ -64,6 +66,7 @@ pub type Edge<W> = Weighted<EdgePtrs, W>;
 
 bitflags::bitflags! {
     #[derive(Clone, Copy, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct FaceMask: u8 {
         const IS_OUTER          = 0b0000_0001;
         const IS_BOUNDARY       = 0b0000_0010;
@@ -77,11 +80,17 @@ bitflags::bitflags! {
     }
 }
 
+/// An inner boundary of a face
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum HolRef {
-    Face(Key<FaceKey>),
+    /// any half edge on the boundary cycle of the hole
+    Edge(Key<EdgeKey>),
+    /// an isolated vertex
     Vertex(Key<VertexKey>),
 }
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FacePtrs {
     pub edge: Key<EdgeKey>,
     pub holes: Vec<HolRef>,
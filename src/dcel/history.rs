@@ -0,0 +1,199 @@
+//! Undo and redo on top of the inverse every operator returns
+
+use std::collections::VecDeque;
+
+use crate::{
+    coord::{Coordinate, UpdateCoordinate},
+    dcel::{
+        Dcel, Flavor, Op, OperatorErr, ops,
+        transaction::{GroupErr, replay},
+    },
+};
+
+/// Undo and redo stacks of groups of operators. Each group is undone and redone as a whole, and
+/// a group that fails half way is rolled back so the dcel is left as it was.
+pub struct History<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    /// the inverses of each group, in the order they are applied to undo it
+    undo: VecDeque<Vec<Op<F>>>,
+    /// the ops of each undone group, in the order they are applied to redo it
+    redo: Vec<Vec<Op<F>>>,
+    /// inverses of the group that is being recorded
+    open: Option<Vec<Op<F>>>,
+    /// how many groups can be undone, the oldest are forgotten first
+    limit: Option<usize>,
+}
+
+impl<F: Flavor> Default for History<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn default() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: vec![],
+            open: None,
+            limit: None,
+        }
+    }
+}
+
+impl<F: Flavor> History<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        self.enforce_limit();
+    }
+
+    fn enforce_limit(&mut self) {
+        if let Some(limit) = self.limit {
+            while self.undo.len() > limit {
+                self.undo.pop_front();
+            }
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.open = None;
+    }
+
+    fn record(&mut self, mut inverses: Vec<Op<F>>) {
+        if inverses.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        match &mut self.open {
+            Some(open) => open.append(&mut inverses),
+            None => {
+                inverses.reverse();
+                self.undo.push_back(inverses);
+                self.enforce_limit();
+            }
+        }
+    }
+
+    /// apply an op as a group of its own, or as part of the open group
+    #[allow(clippy::result_large_err)]
+    pub fn apply(
+        &mut self,
+        dcel: &mut Dcel<F>,
+        op: impl Into<Op<F>>,
+    ) -> Result<(), OperatorErr<Op<F>, ops::Error<F>>> {
+        let inverse = dcel.check_apply(op.into())?;
+        self.record(vec![inverse]);
+        Ok(())
+    }
+
+    /// apply ops as a single group. if any of them fails the ones before it are undone
+    pub fn apply_group(
+        &mut self,
+        dcel: &mut Dcel<F>,
+        ops: impl IntoIterator<Item = Op<F>>,
+    ) -> Result<(), GroupErr<F>> {
+        let inverses = replay(dcel, ops.into_iter().collect())?;
+        self.record(inverses);
+        Ok(())
+    }
+
+    /// start recording every applied op into one group, until [`History::end_group`]
+    pub fn begin_group(&mut self) {
+        self.open.get_or_insert_with(Vec::new);
+    }
+
+    pub fn end_group(&mut self) {
+        if let Some(mut inverses) = self.open.take()
+            && !inverses.is_empty()
+        {
+            inverses.reverse();
+            self.undo.push_back(inverses);
+            self.enforce_limit();
+        }
+    }
+
+    /// undo everything applied since [`History::begin_group`], and stop recording. if one of the
+    /// inverses fails the ones before it are redone, and the group stays open so it can be
+    /// aborted again or ended
+    pub fn abort_group(&mut self, dcel: &mut Dcel<F>) -> Result<(), ops::Error<F>> {
+        let Some(mut inverses) = self.open.take() else {
+            return Ok(());
+        };
+        inverses.reverse();
+        match replay(dcel, inverses) {
+            Ok(_) => Ok(()),
+            Err(OperatorErr {
+                op: mut inverses,
+                err,
+            }) => {
+                inverses.reverse();
+                self.open = Some(inverses);
+                Err(err)
+            }
+        }
+    }
+
+    /// undo the last group, returns false if there was nothing to undo. a group that fails stays
+    /// on the stack
+    pub fn undo(&mut self, dcel: &mut Dcel<F>) -> Result<bool, ops::Error<F>> {
+        self.end_group();
+        let Some(inverses) = self.undo.pop_back() else {
+            return Ok(false);
+        };
+        match replay(dcel, inverses) {
+            Ok(mut ops) => {
+                ops.reverse();
+                self.redo.push(ops);
+                Ok(true)
+            }
+            Err(OperatorErr { op, err }) => {
+                self.undo.push_back(op);
+                Err(err)
+            }
+        }
+    }
+
+    /// redo the last undone group, returns false if there was nothing to redo. a group that fails
+    /// stays on the stack
+    pub fn redo(&mut self, dcel: &mut Dcel<F>) -> Result<bool, ops::Error<F>> {
+        self.end_group();
+        let Some(ops) = self.redo.pop() else {
+            return Ok(false);
+        };
+        match replay(dcel, ops) {
+            Ok(mut inverses) => {
+                inverses.reverse();
+                self.undo.push_back(inverses);
+                self.enforce_limit();
+                Ok(true)
+            }
+            Err(OperatorErr { op, err }) => {
+                self.redo.push(op);
+                Err(err)
+            }
+        }
+    }
+}
@@ -1,9 +1,255 @@
-use std::marker::PhantomData;
+//! A spatial index of the vertices, edges and faces of a dcel
 
-use crate::dcel::Flavor;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+};
 
-pub struct SpacialIndex<F: Flavor> {
-    phantom: PhantomData<F>,
+use crate::{
+    arena::Key,
+    coord::{Coordinate, Scalar, on_segment},
+    dcel::{
+        Dcel, EdgeKey, FaceKey, Flavor, HolRef, Traverser, VertexKey,
+        ops::{AnyKey, Operator, OperatorErr, VisitKeys},
+    },
+    index::{Aabb, RTree, SpatialIndex, SpatialObject},
+};
+
+/// A vertex, an edge or a bounded face, with the shape it is indexed by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Entity<S> {
+    Vertex(Key<VertexKey>, [S; 2]),
+    /// both halves of an edge, the first running left to right, or bottom to top when the edge is
+    /// vertical
+    Edge([Key<EdgeKey>; 2], [[S; 2]; 2]),
+    /// the box around the outer boundary of the face
+    Face(Key<FaceKey>, Aabb<S>),
+}
+
+impl<S> Entity<S> {
+    pub fn key(&self) -> AnyKey {
+        match self {
+            Self::Vertex(key, _) => (*key).into(),
+            Self::Edge([key, _], _) => (*key).into(),
+            Self::Face(key, _) => (*key).into(),
+        }
+    }
+}
+
+impl<S: Scalar> SpatialObject for Entity<S> {
+    type Scalar = S;
+
+    fn envelope(&self) -> Aabb<S> {
+        match self {
+            Self::Vertex(_, point) => Aabb::from_point(*point),
+            Self::Edge(_, [from, to]) => Aabb::from_point(*from).merged(&Aabb::from_point(*to)),
+            Self::Face(_, envelope) => *envelope,
+        }
+    }
+
+    fn distance_2(&self, point: [S; 2]) -> f64 {
+        match self {
+            Self::Vertex(_, vertex) => vertex.distance_2(point),
+            Self::Edge(_, [from, to]) => {
+                let [p, a, b] = [point, *from, *to].map(|p| p.map(S::to_f64));
+                let [dx, dy] = [b[0] - a[0], b[1] - a[1]];
+                let t = ((p[0] - a[0]) * dx + (p[1] - a[1]) * dy) / (dx * dx + dy * dy);
+                let t = if t.is_finite() { t.clamp(0., 1.) } else { 0. };
+                let [cx, cy] = [a[0] + t * dx - p[0], a[1] + t * dy - p[1]];
+                cx * cx + cy * cy
+            }
+            Self::Face(_, envelope) => envelope.distance_2(point),
+        }
+    }
+
+    fn contains_point(&self, point: [S; 2]) -> bool {
+        match self {
+            Self::Vertex(_, vertex) => *vertex == point,
+            Self::Edge(_, [from, to]) => on_segment(point, *from, *to),
+            Self::Face(_, envelope) => envelope.contains_point(point),
+        }
+    }
+}
+
+/// An [`RTree`] of the vertices, edges and bounded faces of a dcel. It dereferences to the tree for
+/// queries, and is kept in sync by applying operators through it, or by refreshing the keys that
+/// changed otherwise
+pub struct SpacialIndex<F: Flavor>
+where
+    F::Vertex: Coordinate,
+{
+    tree: RTree<Entity<<F::Vertex as Coordinate>::Scalar>>,
+    /// what every key is indexed as, with both halves of an edge pointing to the same entity
+    entities: HashMap<AnyKey, Entity<<F::Vertex as Coordinate>::Scalar>>,
+}
+
+impl<F: Flavor> Deref for SpacialIndex<F>
+where
+    F::Vertex: Coordinate,
+{
+    type Target = RTree<Entity<<F::Vertex as Coordinate>::Scalar>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
 }
 
-impl<F: Flavor> SpacialIndex<F> {}
+impl<F: Flavor> SpacialIndex<F>
+where
+    F::Vertex: Coordinate,
+{
+    pub fn new(dcel: &Dcel<F>) -> Self {
+        let keys = dcel
+            .vertices
+            .keys()
+            .map(AnyKey::from)
+            .chain(dcel.edges.keys().map(AnyKey::from))
+            .chain(dcel.faces.keys().map(AnyKey::from));
+        let mut entities = HashMap::new();
+        for key in keys {
+            if !entities.contains_key(&key)
+                && let Some(entity) = Self::entity(dcel, key)
+            {
+                for key in Self::keys_of(&entity) {
+                    entities.insert(key, entity);
+                }
+            }
+        }
+
+        // edges once, by their first half
+        let tree = RTree::bulk_load(
+            entities
+                .iter()
+                .filter(|(key, entity)| **key == entity.key())
+                .map(|(_, entity)| *entity)
+                .collect(),
+        );
+        Self { tree, entities }
+    }
+
+    /// the entity a key is indexed as
+    pub fn get(
+        &self,
+        key: impl Into<AnyKey>,
+    ) -> Option<&Entity<<F::Vertex as Coordinate>::Scalar>> {
+        self.entities.get(&key.into())
+    }
+
+    /// check and apply an operator to dcel, and refresh everything it changed
+    pub fn apply<Op>(
+        &mut self,
+        dcel: &mut Dcel<F>,
+        mut op: Op,
+    ) -> Result<Op::Inverse, OperatorErr<Op, Op::Error>>
+    where
+        Op: Operator<F> + VisitKeys,
+        Op::Inverse: VisitKeys,
+    {
+        let mut keys = op.keys();
+        let mut inverse = dcel.check_apply(op)?;
+        keys.extend(inverse.keys());
+        self.refresh(dcel, keys);
+        Ok(inverse)
+    }
+
+    /// bring the index up to date after keys were made, killed or moved. the vertices, edges and
+    /// faces around them are refreshed as well, and every vertex on the boundaries of a face
+    pub fn refresh(&mut self, dcel: &Dcel<F>, keys: impl IntoIterator<Item = AnyKey>) {
+        let mut touched = HashSet::new();
+        let mut vertices = vec![];
+        for key in keys {
+            if !key.exists(dcel) {
+                touched.insert(key);
+                continue;
+            }
+            match key {
+                AnyKey::Vertex(vertex) => vertices.push(vertex),
+                AnyKey::Edge(edge) => {
+                    vertices.extend([edge, edge.twin(dcel)].map(|edge| edge.origin(dcel)))
+                }
+                AnyKey::Face(face) => {
+                    touched.insert(key);
+                    let boundaries = dcel.face(face).holes.iter().map(|hole| match hole {
+                        HolRef::Edge(edge) => Some(*edge),
+                        HolRef::Vertex(vertex) => {
+                            vertices.push(*vertex);
+                            None
+                        }
+                    });
+                    let boundaries: Vec<_> = std::iter::once(Some(face.edge(dcel)))
+                        .chain(boundaries)
+                        .flatten()
+                        .collect();
+                    for edge in boundaries {
+                        vertices.extend(
+                            Traverser::through(dcel, edge)
+                                .unwrap()
+                                .map(|edge| edge.origin(dcel)),
+                        );
+                    }
+                }
+            }
+        }
+
+        for vertex in vertices {
+            touched.insert(vertex.into());
+            for edge in Traverser::around(dcel, vertex).into_iter().flatten() {
+                touched.insert(edge.into());
+                touched.extend([edge, edge.twin(dcel)].map(|edge| AnyKey::from(edge.face(dcel))));
+            }
+        }
+
+        for key in &touched {
+            if let Some(old) = self.entities.remove(key) {
+                self.tree.remove(&old);
+                for key in Self::keys_of(&old) {
+                    self.entities.remove(&key);
+                }
+            }
+        }
+        for key in touched {
+            if !key.exists(dcel) || self.entities.contains_key(&key) {
+                continue;
+            }
+            if let Some(entity) = Self::entity(dcel, key) {
+                for key in Self::keys_of(&entity) {
+                    self.entities.insert(key, entity);
+                }
+                self.tree.insert(entity);
+            }
+        }
+    }
+
+    /// every key an entity is found by
+    fn keys_of(entity: &Entity<<F::Vertex as Coordinate>::Scalar>) -> Vec<AnyKey> {
+        match entity {
+            Entity::Edge(halves, _) => halves.map(AnyKey::from).to_vec(),
+            entity => vec![entity.key()],
+        }
+    }
+
+    /// what a live key is indexed as, none for the unbounded face
+    fn entity(dcel: &Dcel<F>, key: AnyKey) -> Option<Entity<<F::Vertex as Coordinate>::Scalar>> {
+        let point = |vertex: Key<VertexKey>| dcel.vertex(vertex).weight.xy();
+        Some(match key {
+            AnyKey::Vertex(vertex) => Entity::Vertex(vertex, point(vertex)),
+            AnyKey::Edge(edge) => {
+                let mut halves = [edge, edge.twin(dcel)];
+                let mut points = halves.map(|edge| point(edge.origin(dcel)));
+                if points[1] < points[0] {
+                    halves.reverse();
+                    points.reverse();
+                }
+                Entity::Edge(halves, points)
+            }
+            AnyKey::Face(face) => {
+                if dcel.bounding_face == Some(face) {
+                    return None;
+                }
+                let boundary = Traverser::through(dcel, face.edge(dcel)).ok()?;
+                let envelope = Aabb::from_points(boundary.map(|edge| point(edge.origin(dcel))))?;
+                Entity::Face(face, envelope)
+            }
+        })
+    }
+}
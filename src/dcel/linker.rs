@@ -3,10 +3,18 @@ use std::{borrow::Cow, cmp::Ordering, collections::BTreeSet, marker::PhantomData
 
 use crate::{
     arena::Key,
-    coord::{Coordinate, Precision, sort_clockwise},
-    dcel::{Dcel, EdgeKey, Traverser, VertexKey, error::Error, flavor::Flavor},
+    coord::{Coordinate, sort_clockwise},
+    dcel::{Dcel, EdgeKey, FaceKey, Traverser, VertexKey, error::Error, flavor::Flavor},
 };
 
+/// The gap between two consecutive edges around a vertex
+pub(crate) struct Wedge {
+    /// the face the gap opens into
+    pub face: Key<FaceKey>,
+    /// the local prev and next edges, none if the vertex is isolated
+    pub edges: Option<[Key<EdgeKey>; 2]>,
+}
+
 pub struct Linker<F: Flavor> {
     sort_buffer: Vec<Key<EdgeKey>>,
     phantom: PhantomData<F>,
@@ -98,8 +106,11 @@ impl<F: Flavor> Linker<F> {
         Self::follow(dcel, c, d);
     }
 
-    pub fn sort_around(dcel: &mut Dcel<F>, [cx, cy]: [Precision; 2], buffer: &mut [Key<EdgeKey>])
-    where
+    pub fn sort_around(
+        dcel: &mut Dcel<F>,
+        [cx, cy]: [<F::Vertex as Coordinate>::Scalar; 2],
+        buffer: &mut [Key<EdgeKey>],
+    ) where
         F::Vertex: Coordinate,
     {
         buffer.sort_unstable_by(|a, b| {
@@ -115,6 +126,8 @@ impl<F: Flavor> Linker<F> {
         });
     }
 
+    /// find the two edges outgoing from center that an edge from center towards reference would sit between,
+    /// ordered so that `local_next` of the first would be the new edge, and `local_next` of the new edge the second
     pub fn find_prev_next(
         dcel: &Dcel<F>,
         center: Key<VertexKey>,
@@ -123,38 +136,78 @@ impl<F: Flavor> Linker<F> {
     where
         F::Vertex: Coordinate,
     {
-        let [cx, cy] = center.weight(dcel).xy();
-        let [rx, ry] = reference.weight(dcel).xy();
+        let c = center.weight(dcel).xy();
+        let r = reference.weight(dcel).xy();
+        let towards = |edge: Key<EdgeKey>| edge.twin(dcel).origin(dcel).weight(dcel).xy();
 
-        // Create the traverser and grab the very first edge
         let mut trav = Traverser::at(dcel, center).unwrap();
-        let first = trav.edge();
-
-        let [x1, y1] = first.twin(dcel).origin(dcel).weight(dcel).xy();
-        let ordering = sort_clockwise([cx, cy], [x1, y1], [rx, ry]);
-
-        let mut last = first;
+        let mut wrap = None;
 
+        // local_next turns counter clockwise, so walking around the vertex visits the edges in descending
+        // clockwise order with a single wrap around. the reference either sits where the order crosses it,
+        // or in the wrap around if every edge is on the same side of it.
         loop {
+            let prev = trav.edge();
             trav.local_next(dcel);
+            let next = trav.edge();
+
+            let [p, n] = [towards(prev), towards(next)];
+            if sort_clockwise(c, p, r) == Ordering::Greater
+                && sort_clockwise(c, n, r) == Ordering::Less
+            {
+                return [prev, next];
+            }
+            if sort_clockwise(c, p, n) == Ordering::Less {
+                wrap.get_or_insert([prev, next]);
+            }
+
             if trav.is_at_start() {
-                return [last, trav.edge()];
+                break;
             }
+        }
 
-            let [x1, y1] = trav.edge().twin(dcel).origin(dcel).weight(dcel).xy();
-            let new_ordering = sort_clockwise([cx, cy], [x1, y1], [rx, ry]);
+        wrap.unwrap_or([trav.edge(), trav.edge()])
+    }
 
-            if new_ordering != Ordering::Equal && new_ordering != ordering {
-                return [last, trav.edge()];
+    /// find where an edge from center towards reference would be inserted
+    pub(crate) fn find_wedge(
+        dcel: &Dcel<F>,
+        center: Key<VertexKey>,
+        reference: Key<VertexKey>,
+    ) -> Option<Wedge>
+    where
+        F::Vertex: Coordinate,
+    {
+        match center.edge(dcel) {
+            Some(_) => {
+                let edges = Self::find_prev_next(dcel, center, reference);
+                Some(Wedge {
+                    face: edges[0].twin(dcel).face(dcel),
+                    edges: Some(edges),
+                })
             }
+            None => Some(Wedge {
+                face: dcel.isolated_vertex_face(center)?,
+                edges: None,
+            }),
+        }
+    }
 
-            last = trav.edge();
+    /// splice a new edge into a wedge found with [`Linker::find_wedge`], edges from isolated vertices will turn around at it
+    pub(crate) fn splice_wedge(dcel: &mut Dcel<F>, edge: Key<EdgeKey>, wedge: &Wedge) {
+        match wedge.edges {
+            Some([local_prev, local_next]) => Self::splice_edge(dcel, edge, local_prev, local_next),
+            None => {
+                Self::splice_edge(dcel, edge, edge, edge);
+                let origin = edge.origin(dcel);
+                dcel.vertex_mut(origin).edge = Some(edge);
+            }
         }
     }
 
     fn find_prev_next_sort(
         dcel: &mut Dcel<F>,
-        c: [Precision; 2],
+        c: [<F::Vertex as Coordinate>::Scalar; 2],
         origin: Key<VertexKey>,
         edge: Key<EdgeKey>,
         sort_buffer: &mut Vec<Key<EdgeKey>>,
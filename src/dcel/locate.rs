@@ -0,0 +1,244 @@
+//! Finding what of a dcel lies at a point
+
+use std::cmp::Ordering;
+
+use crate::{
+    arena::Key,
+    coord::{Coordinate, Scalar, on_segment, orient2d},
+    dcel::{Dcel, EdgeKey, FaceKey, Flavor, VertexKey},
+};
+
+/// What a point hits. Edges are reported by the half edge running left to right, or bottom to top
+/// when the edge is vertical
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Vertex(Key<VertexKey>),
+    Edge(Key<EdgeKey>),
+    Face(Key<FaceKey>),
+}
+
+fn lex<S: Scalar>(a: [S; 2], b: [S; 2]) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: Coordinate,
+{
+    /// what lies at point, by walking every vertex, edge and face. none when the dcel has no
+    /// bounding face yet and nothing is hit. for many queries build a [`PointLocator`]
+    pub fn locate(&self, point: [<F::Vertex as Coordinate>::Scalar; 2]) -> Option<Location> {
+        if let Some((_, key)) = self.vertices.iter().find(|(v, _)| v.weight.xy() == point) {
+            return Some(Location::Vertex(key));
+        }
+        for (edge, key) in self.edges.iter() {
+            let [from, to] =
+                [edge.origin, key.twin(self).origin(self)].map(|v| v.weight(self).xy());
+            if lex(from, to) == Ordering::Less && on_segment(point, from, to) {
+                return Some(Location::Edge(key));
+            }
+        }
+        self.face_containing(point).map(Location::Face)
+    }
+}
+
+/// an edge that is not vertical, running left to right
+struct Segment<S> {
+    points: [[S; 2]; 2],
+    edge: Key<EdgeKey>,
+    below: Key<FaceKey>,
+    above: Key<FaceKey>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum LocateError {
+    #[error("the dcel was edited since the locator was built, or is not the one it was built from")]
+    Stale { built: u64, generation: u64 },
+}
+
+/// Answers [`Dcel::locate`] in O(log n), by splitting the plane into vertical slabs at every vertex,
+/// with the edges crossing each slab kept in order from bottom to top. It takes up to O(n²)
+/// memory for edges that span many slabs, and can't be updated in place: it remembers
+/// [`Dcel::generation`] when it was built, refuses to answer for any other dcel or one edited
+/// since, and [`PointLocator::update`] builds it again
+pub struct PointLocator<S> {
+    /// every vertex, by x and then y
+    vertices: Vec<([S; 2], Key<VertexKey>)>,
+    /// vertical edges by their lower end, with the y of their upper end
+    verticals: Vec<([S; 2], S, Key<EdgeKey>)>,
+    segments: Vec<Segment<S>>,
+    /// the x of every slab boundary
+    xs: Vec<S>,
+    /// the segments crossing every slab, from bottom to top
+    slabs: Vec<Vec<u32>>,
+    outside: Option<Key<FaceKey>>,
+    /// the generation of the dcel it was built from
+    built: u64,
+}
+
+impl<S: Scalar> PointLocator<S> {
+    pub fn new<F: Flavor>(dcel: &Dcel<F>) -> Self
+    where
+        F::Vertex: Coordinate<Scalar = S>,
+    {
+        let mut vertices: Vec<_> = dcel
+            .vertices
+            .iter()
+            .map(|(vertex, key)| (vertex.weight.xy(), key))
+            .collect();
+        vertices.sort_by(|a, b| lex(a.0, b.0));
+
+        let mut verticals = vec![];
+        let mut segments = vec![];
+        for (edge, key) in dcel.edges.iter() {
+            let twin = dcel.edge(edge.twin);
+            let [from, to] = [edge.origin, twin.origin].map(|v| dcel.vertex(v).weight.xy());
+            // every pair once, from its left or lower end
+            if lex(from, to) != Ordering::Less {
+                continue;
+            }
+            if from[0] == to[0] {
+                verticals.push((from, to[1], key));
+            } else {
+                segments.push(Segment {
+                    points: [from, to],
+                    edge: key,
+                    below: edge.face,
+                    above: twin.face,
+                });
+            }
+        }
+        verticals.sort_by(|a, b| lex(a.0, b.0));
+
+        let mut xs: Vec<S> = segments
+            .iter()
+            .flat_map(|segment| segment.points.map(|[x, _]| x))
+            .collect();
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        xs.dedup();
+
+        let slab_of = |x: S| xs.partition_point(|boundary| *boundary < x);
+        let mut slabs = vec![vec![]; xs.len().saturating_sub(1)];
+        for (i, segment) in segments.iter().enumerate() {
+            let [[left, _], [right, _]] = segment.points;
+            for slab in &mut slabs[slab_of(left)..slab_of(right)] {
+                slab.push(i as u32);
+            }
+        }
+        for slab in &mut slabs {
+            slab.sort_by(|a, b| {
+                segment_order(segments[*a as usize].points, segments[*b as usize].points)
+            });
+        }
+
+        Self {
+            vertices,
+            verticals,
+            segments,
+            xs,
+            slabs,
+            outside: dcel.bounding_face,
+            built: dcel.generation(),
+        }
+    }
+
+    /// whether this was built from the dcel as it is now
+    pub fn is_current<F: Flavor>(&self, dcel: &Dcel<F>) -> bool {
+        self.built == dcel.generation()
+    }
+
+    /// build again from the dcel, unless it is current
+    pub fn update<F: Flavor>(&mut self, dcel: &Dcel<F>)
+    where
+        F::Vertex: Coordinate<Scalar = S>,
+    {
+        if !self.is_current(dcel) {
+            *self = Self::new(dcel);
+        }
+    }
+
+    /// what lies at point in dcel, which has to be the dcel this was built from
+    pub fn locate<F: Flavor>(
+        &self,
+        dcel: &Dcel<F>,
+        point: [S; 2],
+    ) -> Result<Option<Location>, LocateError> {
+        match self.is_current(dcel) {
+            true => Ok(self.find(point)),
+            false => Err(LocateError::Stale {
+                built: self.built,
+                generation: dcel.generation(),
+            }),
+        }
+    }
+
+    fn find(&self, point: [S; 2]) -> Option<Location> {
+        let [px, py] = point;
+        let vertex = self
+            .vertices
+            .partition_point(|(p, _)| lex(*p, point) == Ordering::Less);
+        if let Some((p, key)) = self.vertices.get(vertex)
+            && *p == point
+        {
+            return Some(Location::Vertex(*key));
+        }
+
+        let start = self.verticals.partition_point(|(low, ..)| low[0] < px);
+        for (low, high, edge) in self.verticals[start..].iter() {
+            if low[0] != px {
+                break;
+            }
+            if low[1] < py && py < *high {
+                return Some(Location::Edge(*edge));
+            }
+        }
+
+        // the slab to the right of a boundary, or to its left at the last one
+        let slab = match self.xs.partition_point(|x| *x <= px) {
+            0 => return self.outside.map(Location::Face),
+            i if i < self.xs.len() => i - 1,
+            i if self.xs[i - 1] == px => i - 2,
+            _ => return self.outside.map(Location::Face),
+        };
+
+        let crossing = &self.slabs[slab];
+        let segment = |i: usize| &self.segments[crossing[i] as usize];
+        let side = |i: usize| {
+            let [from, to] = segment(i).points;
+            orient2d(from, to, point)
+        };
+        let above = crossing.partition_point(|i| {
+            let [from, to] = self.segments[*i as usize].points;
+            orient2d(from, to, point) > 0.
+        });
+        if above < crossing.len() && side(above) == 0. {
+            return Some(Location::Edge(segment(above).edge));
+        }
+        let face = match above {
+            above if above < crossing.len() => Some(segment(above).below),
+            0 => self.outside,
+            above => Some(segment(above - 1).above),
+        };
+        face.map(Location::Face)
+    }
+}
+
+/// the order of two left to right segments that overlap in x and only meet at their ends, from
+/// bottom to top. each one is tested against the other where both exist
+fn segment_order<S: Scalar>(a: [[S; 2]; 2], b: [[S; 2]; 2]) -> Ordering {
+    let above = |side: f64| side.partial_cmp(&0.).unwrap_or(Ordering::Equal);
+    let order = if a[0][0] >= b[0][0] {
+        above(orient2d(b[0], b[1], a[0]))
+    } else {
+        above(orient2d(a[0], a[1], b[0])).reverse()
+    };
+    if order != Ordering::Equal {
+        return order;
+    }
+    // they start at the same point
+    if a[1][0] <= b[1][0] {
+        above(orient2d(b[0], b[1], a[1]))
+    } else {
+        above(orient2d(a[0], a[1], b[1])).reverse()
+    }
+}
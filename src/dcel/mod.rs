@@ -1,32 +1,64 @@
+mod arrangement;
+mod compact;
 pub mod draw;
 mod entities;
 pub mod error;
 mod flavor;
+mod history;
 mod index;
 mod linker;
+mod locate;
 pub mod ops;
+mod overlay;
+mod polygons;
+mod snap;
+mod sweep;
 #[cfg(test)]
 mod tests;
+mod transaction;
 mod traverser;
+mod triangulate;
 mod util;
+mod validate;
 pub mod vis;
 
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{self, AtomicU64},
+};
 
 use crate::{arena::ArenaBitMask, dcel::linker::Linker, util::ShoeString};
 
 use error::Error::{self, EdgeDoesNotExist, FaceDoesNotExist, VertexDoesNotExist};
 
+pub use arrangement::SegmentError;
+pub use compact::DcelRemap;
 pub use entities::*;
 pub use flavor::Flavor;
+pub use history::History;
+pub use index::{Entity, SpacialIndex};
+pub use locate::{LocateError, Location, PointLocator};
 pub use ops::{Op, Operator, OperatorErr};
+pub use overlay::{BooleanOp, Overlay};
+pub use polygons::PolygonError;
+pub use transaction::{GroupErr, OpGroup, RollbackErr, Transaction};
 pub use traverser::*;
+pub use triangulate::TriangulationError;
+pub use validate::{ValidationReport, Violation};
 
 use crate::{
     arena::{Arena, Key},
-    coord::{Coordinate, FromCoordinate, Precision, UpdateCoordinate, sort_clockwise},
+    coord::{Coordinate, FromCoordinate, Scalar, UpdateCoordinate, sort_clockwise},
 };
 
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Dcel<F: Flavor> {
     /// all vertices
     pub vertices: Arena<Vertex<F::Vertex>, VertexKey>,
@@ -36,6 +68,27 @@ pub struct Dcel<F: Flavor> {
     pub faces: Arena<Face<F::Face>, FaceKey>,
     /// the top level bounding face
     bounding_face: Option<Key<FaceKey>>,
+    /// changes with every operator applied and compaction made, so what is derived from the dcel
+    /// can tell it is out of date. a deserialized dcel gets a fresh one
+    #[cfg_attr(feature = "serde", serde(skip))]
+    generation: Generation,
+}
+
+/// A number no two dcels, or the same dcel before and after an edit, ever share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Generation(u64);
+
+impl Generation {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self::next()
+    }
 }
 
 impl<F: Flavor> Dcel<F> {
@@ -91,6 +144,7 @@ impl<F: Flavor> Default for Dcel<F> {
             edges: Default::default(),
             faces: Default::default(),
             bounding_face: None,
+            generation: Generation::next(),
         }
     }
 }
@@ -107,9 +161,17 @@ impl<F: Flavor> Dcel<F> {
             edges,
             faces,
             bounding_face,
+            generation: Generation::next(),
         }
     }
 
+    /// identifies this dcel as it is now. it changes with every operator applied through
+    /// [`Dcel::check_apply`] and every compaction, edits made through the arenas directly don't
+    /// change it
+    pub fn generation(&self) -> u64 {
+        self.generation.0
+    }
+
     pub fn through<Cb>(&mut self, edge: Key<EdgeKey>, mut cb: Cb) -> Result<(), Error>
     where
         Cb: FnMut(&mut Self, Key<EdgeKey>),
@@ -143,7 +205,7 @@ impl<F: Flavor> Dcel<F> {
         Ok(())
     }
 
-    pub fn face_signed_area(&self, key: Key<FaceKey>) -> Result<f32, Error>
+    pub fn face_signed_area(&self, key: Key<FaceKey>) -> Result<f64, Error>
     where
         F::Vertex: Coordinate,
     {
@@ -151,7 +213,10 @@ impl<F: Flavor> Dcel<F> {
         Traverser::signed_area(&self, face.edge)
     }
 
-    pub fn face_path(&self, key: Key<FaceKey>) -> Result<Vec<f32>, Error>
+    pub fn face_path(
+        &self,
+        key: Key<FaceKey>,
+    ) -> Result<Vec<<F::Vertex as Coordinate>::Scalar>, Error>
     where
         F::Vertex: Coordinate,
     {
@@ -173,6 +238,108 @@ impl<F: Flavor> Dcel<F> {
         Ok(path)
     }
 
+    /// find the innermost bounded face whose outer boundary encloses point, falling back to the bounding face
+    pub(crate) fn face_containing(
+        &self,
+        point: [<F::Vertex as Coordinate>::Scalar; 2],
+    ) -> Option<Key<FaceKey>>
+    where
+        F::Vertex: Coordinate,
+    {
+        let mut innermost: Option<(f64, Key<FaceKey>)> = None;
+        for (face, key) in self.faces.iter() {
+            if Some(key) == self.bounding_face {
+                continue;
+            }
+            // bounded faces wind clockwise, anything else isn't enclosing an area
+            let Ok(area) = Traverser::signed_area(self, face.edge) else {
+                continue;
+            };
+            if area >= 0. || !Traverser::encloses(self, face.edge, point).unwrap_or(false) {
+                continue;
+            }
+            if innermost.is_none_or(|(smallest, _)| -area < smallest) {
+                innermost = Some((-area, key));
+            }
+        }
+        innermost.map(|(_, key)| key).or(self.bounding_face)
+    }
+
+    /// the face an isolated vertex is a hole of
+    pub(crate) fn isolated_vertex_face(&self, vertex: Key<VertexKey>) -> Option<Key<FaceKey>>
+    where
+        F::Vertex: Coordinate,
+    {
+        let hole = HolRef::Vertex(vertex);
+        self.faces
+            .iter()
+            .find(|(face, _)| face.holes.contains(&hole))
+            .map(|(_, key)| key)
+            .or_else(|| self.face_containing(self.vertices.get(vertex)?.weight.xy()))
+    }
+
+    /// the index of the hole of face whose boundary cycle contains edge
+    pub(crate) fn hole_of_cycle(&self, face: Key<FaceKey>, edge: Key<EdgeKey>) -> Option<usize> {
+        let cycle = Traverser::through(self, edge).ok()?.collect::<Vec<_>>();
+        self.faces[face]
+            .holes
+            .iter()
+            .position(|hole| matches!(hole, HolRef::Edge(e) if cycle.contains(e)))
+    }
+
+    /// point the boundaries of faces that run through edges about to be removed at what will remain of
+    /// their cycle, or at the vertex left behind if nothing remains
+    pub(crate) fn retarget_boundaries(
+        &mut self,
+        removed: &[Key<EdgeKey>],
+        isolated: Option<Key<VertexKey>>,
+    ) {
+        for &edge in removed {
+            let face = edge.face(self);
+            let replacement = Traverser::through(self, edge)
+                .unwrap()
+                .find(|e| !removed.contains(e))
+                .map(HolRef::Edge)
+                .or(isolated.map(HolRef::Vertex));
+
+            let face = &mut self.faces[face];
+            if let Some(hole) = face.holes.iter().position(|h| *h == HolRef::Edge(edge)) {
+                match replacement {
+                    Some(replacement) => face.holes[hole] = replacement,
+                    None => {
+                        face.holes.swap_remove(hole);
+                    }
+                }
+            }
+
+            if face.edge == edge {
+                match replacement {
+                    Some(HolRef::Edge(replacement)) => face.edge = replacement,
+                    replacement => {
+                        // the cycle is gone, so another hole has to become the boundary
+                        if let Some(hole) =
+                            face.holes.iter().position(|h| matches!(h, HolRef::Edge(_)))
+                            && let HolRef::Edge(promoted) = face.holes.swap_remove(hole)
+                        {
+                            face.edge = promoted;
+                        }
+                        face.holes.extend(replacement);
+                    }
+                }
+            }
+        }
+    }
+
+    /// whether the cycle through edge is a lone edge and the only boundary its face has left
+    pub(crate) fn is_last_boundary(&self, edge: Key<EdgeKey>) -> bool {
+        let twin = edge.twin(self);
+        let face = &self.faces[edge.face(self)];
+        edge.next(self) == twin
+            && twin.next(self) == edge
+            && (face.edge == edge || face.edge == twin)
+            && !face.holes.iter().any(|h| matches!(h, HolRef::Edge(_)))
+    }
+
     pub(crate) fn propagate_face(
         &mut self,
         edge: Key<EdgeKey>,
@@ -196,10 +363,25 @@ impl<F: Flavor> Dcel<F> {
         &mut self,
         op: Op,
     ) -> Result<Op::Inverse, OperatorErr<Op, Op::Error>> {
-        match op.check(self) {
-            Ok(input) => op.apply(self),
+        let res = match op.check(self) {
+            Ok(()) => op.apply(self),
             Err(err) => Err(OperatorErr { op, err }),
+        };
+        if res.is_ok() {
+            self.generation = Generation::next();
+        }
+
+        #[cfg(all(debug_assertions, feature = "validate"))]
+        if res.is_ok() {
+            let report = self.validate();
+            assert!(
+                report.is_valid(),
+                "{} broke the dcel:\n{report}",
+                std::any::type_name::<Op>()
+            );
         }
+
+        res
     }
 }
 
@@ -240,12 +422,21 @@ where
         self.check_apply(ops::Kef { face, edges }.into())
     }
 
-    pub fn mekh(&mut self) -> op_res!(F => ops::Mekh) {
-        todo!()
+    pub fn mekh(&mut self, from: Key<VertexKey>, to: Key<VertexKey>) -> op_res!(F => ops::Mekh<F>)
+    where
+        F::Vertex: Coordinate,
+    {
+        self.check_apply(ops::Mekh {
+            vertices: [from, to],
+            data: (Default::default(), Default::default()),
+        })
     }
 
-    pub fn kemh(&mut self) -> op_res!(F => ops::Kemh) {
-        todo!()
+    pub fn kemh(&mut self, edges: [Key<EdgeKey>; 2]) -> op_res!(F => ops::Kemh)
+    where
+        F::Vertex: Coordinate,
+    {
+        self.check_apply(ops::Kemh { edges })
     }
 
     pub fn mve(&mut self, origin: Key<VertexKey>, vw: F::Vertex) -> op_res!(F => ops::Mve<F>)
@@ -276,23 +467,48 @@ where
         })
     }
 
-    pub fn mvvef(&mut self, v1: F::Vertex, v2: F::Vertex) -> op_res!(F => ops::Mvvef<F>) {
+    pub fn mev(&mut self, edge: Key<EdgeKey>, vertex: F::Vertex) -> op_res!(F => ops::Mev<F>) {
+        self.check_apply(ops::Mev::new(
+            edge,
+            vertex,
+            [Default::default(), Default::default()],
+        ))
+    }
+
+    pub fn kev(
+        &mut self,
+        vertex: Key<VertexKey>,
+        edges: [Key<EdgeKey>; 2],
+    ) -> op_res!(F => ops::Kev) {
+        self.check_apply(ops::Kev { vertex, edges })
+    }
+
+    pub fn mvvef(&mut self, v1: F::Vertex, v2: F::Vertex) -> op_res!(F => ops::Mvvef<F>)
+    where
+        F::Vertex: Coordinate,
+    {
         self.check_apply(ops::Mvvef {
             data: (
                 v1,
                 v2,
                 Default::default(),
                 Default::default(),
-                Default::default(),
+                Some(Default::default()),
             ),
         })
     }
 
-    pub fn mvh(&mut self, vertex: F::Vertex) -> op_res!(F => ops::Mvh<F>) {
+    pub fn mvh(&mut self, vertex: F::Vertex) -> op_res!(F => ops::Mvh<F>)
+    where
+        F::Vertex: Coordinate,
+    {
         self.check_apply(ops::Mvh { vertex })
     }
 
-    pub fn kvh(&mut self, vertex: Key<VertexKey>) -> op_res!(F => ops::Kvh) {
+    pub fn kvh(&mut self, vertex: Key<VertexKey>) -> op_res!(F => ops::Kvh)
+    where
+        F::Vertex: Coordinate,
+    {
         self.check_apply(ops::Kvh { vertex })
     }
 }
@@ -308,7 +524,7 @@ impl<F: Flavor> Dcel<F> {
     {
         self.check_apply(ops::TranslateVertex {
             vertex: key,
-            delta: delta.xyz(),
+            delta: delta.xyz().map(Scalar::to_f64),
         })
     }
 
@@ -322,4 +538,50 @@ impl<F: Flavor> Dcel<F> {
     {
         self.check_apply(ops::TranslateVertex::from_absolute(self, key, coord))
     }
+
+    pub fn translate_face(
+        &mut self,
+        faces: Vec<Key<FaceKey>>,
+        delta: impl Coordinate,
+    ) -> op_res!(F => ops::TranslateFace)
+    where
+        F::Vertex: Coordinate + UpdateCoordinate,
+    {
+        self.check_apply(ops::TranslateFace {
+            faces,
+            delta: delta.xy().map(Scalar::to_f64),
+        })
+    }
+
+    pub fn rotate_face(
+        &mut self,
+        faces: Vec<Key<FaceKey>>,
+        center: impl Coordinate,
+        angle: f64,
+    ) -> op_res!(F => ops::RotateFace)
+    where
+        F::Vertex: Coordinate + UpdateCoordinate,
+    {
+        self.check_apply(ops::RotateFace {
+            faces,
+            center: center.xy().map(Scalar::to_f64),
+            angle,
+        })
+    }
+
+    pub fn scale_face(
+        &mut self,
+        faces: Vec<Key<FaceKey>>,
+        center: impl Coordinate,
+        factor: impl Coordinate,
+    ) -> op_res!(F => ops::ScaleFace)
+    where
+        F::Vertex: Coordinate + UpdateCoordinate,
+    {
+        self.check_apply(ops::ScaleFace {
+            faces,
+            center: center.xy().map(Scalar::to_f64),
+            factor: factor.xy().map(Scalar::to_f64),
+        })
+    }
 }
@@ -2,10 +2,10 @@
 
 use crate::{
     arena::Key,
-    coord::Coordinate,
+    coord::{Coordinate, UpdateCoordinate},
     dcel::{
-        Dcel, EdgeKey, Flavor, Op, Operator, OperatorErr, Traverser, VertexKey,
-        ops::{Kef, Kve, Mef, Mekh, Mve},
+        Dcel, EdgeKey, Flavor, Op, OpGroup, Operator, OperatorErr, Traverser, VertexKey,
+        ops::{self, Kef, Kemh, Kve, Mef, Mekh, Mve},
     },
 };
 
@@ -54,6 +54,14 @@ impl CollapseEdge {
             }
         });
 
+        // kve wants the half edge leaving the origin first
+        let [e1, e2] = edges;
+        let edges = if e1.origin(dcel) == origin {
+            [e1, e2]
+        } else {
+            [e2, e1]
+        };
+
         Self {
             kill_adjacent_faces,
             kill_vertex_edge: Kve {
@@ -63,39 +71,69 @@ impl CollapseEdge {
             },
         }
     }
-    pub fn apply<F: Flavor>(self, dcel: &mut Dcel<F>) -> UncollapseEdge<F>
+    /// the euler operators that collapse the edge, to apply them as one group of a
+    /// [`History`](crate::dcel::History)
+    pub fn into_ops<F: Flavor>(self) -> Vec<Op<F>>
     where
-        F::Vertex: Coordinate,
+        F::Vertex: UpdateCoordinate + Coordinate,
     {
-        let make_adjacent_faces = self
-            .kill_adjacent_faces
-            .map(|op| op.and_then(|op| dcel.check_apply(op).ok()));
-        let make_vertex_edge = dcel.check_apply(self.kill_vertex_edge).unwrap();
-        UncollapseEdge {
-            make_adjacent_faces,
-            make_vertex_edge,
+        self.kill_adjacent_faces
+            .into_iter()
+            .flatten()
+            .map(Op::from)
+            .chain([self.kill_vertex_edge.into()])
+            .collect()
+    }
+
+    /// collapse as one edit, nothing is left collapsed half way if it fails
+    pub fn apply<F: Flavor>(self, dcel: &mut Dcel<F>) -> Result<UncollapseEdge<F>, ops::Error<F>>
+    where
+        F::Vertex: UpdateCoordinate + Coordinate,
+    {
+        let ((), group) = dcel.transaction(|tx| {
+            for kill in self.kill_adjacent_faces.into_iter().flatten() {
+                // a face that can't be killed is left to the kve
+                let _ = tx.apply(kill);
+            }
+            tx.apply(self.kill_vertex_edge)?;
+            Ok::<_, ops::Error<F>>(())
+        })?;
+        Ok(UncollapseEdge { group })
+    }
+
+    fn from_group<F: Flavor>(group: OpGroup<F>) -> Self {
+        let mut kill_adjacent_faces = [None, None];
+        let mut kill_vertex_edge = None;
+        for op in group.ops {
+            match op {
+                Op::Kef(kef) => {
+                    let slot = kill_adjacent_faces.iter_mut().find(|kef| kef.is_none());
+                    *slot.unwrap() = Some(kef);
+                }
+                Op::Kve(kve) => kill_vertex_edge = Some(kve),
+                _ => unreachable!("a collapse is only made of kef and kve"),
+            }
+        }
+        Self {
+            kill_adjacent_faces,
+            kill_vertex_edge: kill_vertex_edge.unwrap(),
         }
     }
 }
 
 pub struct UncollapseEdge<F: Flavor> {
-    make_adjacent_faces: [Option<Mef<F>>; 2],
-    make_vertex_edge: Mve<F>,
+    /// the mve and mefs that undo the collapse
+    group: OpGroup<F>,
 }
 
 impl<F: Flavor> UncollapseEdge<F>
 where
-    F::Vertex: Coordinate,
+    F::Vertex: UpdateCoordinate + Coordinate,
 {
-    pub fn apply(self, dcel: &mut Dcel<F>) -> CollapseEdge {
-        let kill_vertex_edge = dcel.check_apply(self.make_vertex_edge).unwrap();
-        let kill_adjacent_faces = self
-            .make_adjacent_faces
-            .map(|op| op.and_then(|op| dcel.check_apply(op).ok()));
-        CollapseEdge {
-            kill_adjacent_faces,
-            kill_vertex_edge,
-        }
+    /// uncollapse as one edit, nothing is left uncollapsed half way if it fails
+    pub fn apply(self, dcel: &mut Dcel<F>) -> Result<CollapseEdge, ops::Error<F>> {
+        let group = dcel.check_apply(self.group).map_err(|err| err.err)?;
+        Ok(CollapseEdge::from_group(group))
     }
 }
 
@@ -111,7 +149,7 @@ pub struct UnweldVertex<F: Flavor> {
 
 pub enum LinkVertices<F: Flavor> {
     Mef(Mef<F>),
-    Mekh(Mekh),
+    Mekh(Mekh<F>),
 }
 
 /// You probably want Op::Euler::Mve
@@ -130,18 +168,22 @@ where
 
         match mef.check(dcel) {
             Ok(_) => LinkVertices::Mef(mef),
-            Err(_) => LinkVertices::Mekh(Mekh {}),
+            Err(_) => LinkVertices::Mekh(Mekh {
+                vertices: mef.vertices,
+                data: (mef.data.1, mef.data.2),
+            }),
         }
     }
 
     pub fn apply(self, dcel: &mut Dcel<F>) -> UnlinkVertices {
         match self {
             LinkVertices::Mef(mef) => UnlinkVertices::Kef(dcel.check_apply(mef).unwrap()),
-            LinkVertices::Mekh(mekh) => todo!(),
+            LinkVertices::Mekh(mekh) => UnlinkVertices::Kemh(dcel.check_apply(mekh).unwrap()),
         }
     }
 }
 
 pub enum UnlinkVertices {
     Kef(Kef),
+    Kemh(Kemh),
 }
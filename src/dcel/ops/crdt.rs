@@ -0,0 +1,444 @@
+//! A dcel edited by several replicas at once, kept in sync by exchanging commands
+
+use std::{collections::HashMap, fmt::Debug};
+
+use crate::{
+    arena::Key,
+    coord::{Coordinate, UpdateCoordinate},
+    dcel::{
+        Dcel, EdgeKey, FaceKey, Flavor, Location, VertexKey,
+        ops::{AnyKey, Error, KeyVisitor, Mev, Op, Operator, OperatorErr, VisitKeys},
+    },
+};
+
+/// A lamport timestamp, made unique by the replica that issued the command. Commands are applied
+/// in this order on every replica
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommandId {
+    pub version: u64,
+    pub replica: u32,
+}
+
+impl CommandId {
+    /// the command every replica starts from
+    pub const ROOT: Self = Self {
+        version: 0,
+        replica: 0,
+    };
+}
+
+/// The same vertex, edge or face on every replica: the command that made it, and which of the
+/// elements made by that command it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ElementId {
+    pub command: CommandId,
+    pub index: u32,
+}
+
+/// An op as sent between replicas. Its keys are not keys to any dcel, the key `i` stands for the
+/// element `ids[i - 1]`
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Command<F: Flavor> {
+    pub id: CommandId,
+    op: Op<F>,
+    ids: Vec<ElementId>,
+}
+
+impl<F: Flavor> Clone for Command<F>
+where
+    F::Vertex: Clone,
+    F::Edge: Clone,
+    F::Face: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            op: self.op.clone(),
+            ids: self.ids.clone(),
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum CrdtError<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    #[error("an operator failed")]
+    Op(Error<F>),
+    #[error("the op refers to an element that is not shared between replicas")]
+    NotShared(AnyKey),
+    #[error("a command ordered after the one received could not be rolled back")]
+    Rollback(CommandId),
+}
+
+impl<F: Flavor> Debug for CrdtError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+struct Entry<F: Flavor> {
+    command: Command<F>,
+    /// how to take the command back on this replica, none if it could not be applied here
+    undo: Option<Op<F>>,
+}
+
+/// A dcel shared between replicas. Every replica applies the same commands in the same order,
+/// whatever order they arrive in, so all of them end up with the same dcel. A command that no
+/// longer applies once the commands ordered before it are in, because another replica edited the
+/// same elements concurrently, is skipped on every replica. An isolated vertex that lands on an
+/// edge made concurrently splits that edge instead.
+pub struct CrdtDcel<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    replica: u32,
+    /// the highest version seen so far
+    clock: u64,
+    dcel: Dcel<F>,
+    ids: HashMap<ElementId, AnyKey>,
+    keys: HashMap<AnyKey, ElementId>,
+    /// every command since the last compaction, ordered by id
+    log: Vec<Entry<F>>,
+    /// the last command every replica has acknowledged, nothing up to it is kept in the log
+    acknowledged: CommandId,
+}
+
+impl<F: Flavor> CrdtDcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + Clone,
+    F::Edge: Clone,
+    F::Face: Clone,
+{
+    /// every replica has to start from the same dcel. the bounding face is never shared, no op
+    /// can edit it directly
+    pub fn new(dcel: Dcel<F>, replica: u32) -> Self {
+        let mut crdt = Self {
+            replica,
+            clock: 0,
+            dcel,
+            ids: HashMap::new(),
+            keys: HashMap::new(),
+            log: vec![],
+            acknowledged: CommandId::ROOT,
+        };
+
+        let vertices = crdt.dcel.vertices.iter().map(|(_, key)| key.into());
+        let edges = crdt.dcel.edges.iter().map(|(_, key)| key.into());
+        let faces = crdt
+            .dcel
+            .faces
+            .iter()
+            .map(|(_, key)| key)
+            .filter(|key| Some(*key) != crdt.dcel.bounding_face)
+            .map(Into::into);
+        let keys: Vec<AnyKey> = vertices.chain(edges).chain(faces).collect();
+        for (index, key) in keys.into_iter().enumerate() {
+            crdt.bind(
+                key,
+                ElementId {
+                    command: CommandId::ROOT,
+                    index: index as u32,
+                },
+            );
+        }
+        crdt
+    }
+
+    pub fn dcel(&self) -> &Dcel<F> {
+        &self.dcel
+    }
+
+    pub fn replica(&self) -> u32 {
+        self.replica
+    }
+
+    pub fn id(&self, key: impl Into<AnyKey>) -> Option<ElementId> {
+        self.keys.get(&key.into()).copied()
+    }
+
+    pub fn key(&self, id: ElementId) -> Option<AnyKey> {
+        self.ids.get(&id).copied()
+    }
+
+    /// every command since the last compaction, in the order they are applied
+    pub fn commands(&self) -> impl Iterator<Item = &Command<F>> {
+        self.log.iter().map(|entry| &entry.command)
+    }
+
+    /// apply an op made against this replica, returning the command to send to every other one.
+    /// an op referring to an element that has no id, such as the bounding face, is refused
+    #[allow(clippy::result_large_err)]
+    pub fn edit(
+        &mut self,
+        op: impl Into<Op<F>>,
+    ) -> Result<Command<F>, OperatorErr<Op<F>, CrdtError<F>>> {
+        let op = op.into();
+        if let Err(err) = self.snap(op.clone()).check(&self.dcel) {
+            return Err(OperatorErr {
+                op,
+                err: CrdtError::Op(err),
+            });
+        }
+
+        let mut wire = op.clone();
+        let mut encode = Encode {
+            keys: &self.keys,
+            ids: vec![],
+            missing: None,
+        };
+        wire.visit_keys(&mut encode);
+        if let Some(key) = encode.missing {
+            return Err(OperatorErr {
+                op,
+                err: CrdtError::NotShared(key),
+            });
+        }
+
+        self.clock += 1;
+        let command = Command {
+            id: CommandId {
+                version: self.clock,
+                replica: self.replica,
+            },
+            ids: encode.ids,
+            op: wire,
+        };
+        match self.receive(command.clone()) {
+            Ok(()) => Ok(command),
+            Err(err) => Err(OperatorErr { op, err }),
+        }
+    }
+
+    /// apply a command from any replica, this one included. commands seen before are ignored. if
+    /// a command ordered after it can't be rolled back to make room for it, the dcel is left as
+    /// it was and the command is not taken in
+    pub fn receive(&mut self, command: Command<F>) -> Result<(), CrdtError<F>> {
+        self.clock = self.clock.max(command.id.version);
+        if command.id <= self.acknowledged {
+            return Ok(());
+        }
+        let Err(at) = self
+            .log
+            .binary_search_by_key(&command.id, |entry| entry.command.id)
+        else {
+            return Ok(());
+        };
+
+        self.rollback(at).map_err(CrdtError::Rollback)?;
+        self.log.insert(
+            at,
+            Entry {
+                command,
+                undo: None,
+            },
+        );
+
+        let mut log = std::mem::take(&mut self.log);
+        for entry in &mut log[at..] {
+            entry.undo = self.execute(&entry.command);
+        }
+        self.log = log;
+        Ok(())
+    }
+
+    /// forget the commands up to and including `acknowledged`, once every replica has received
+    /// all of them. they are never rolled back again, and a command ordered before them that
+    /// arrives later is taken as one seen before
+    pub fn compact(&mut self, acknowledged: CommandId) {
+        let at = self
+            .log
+            .partition_point(|entry| entry.command.id <= acknowledged);
+        self.log.drain(..at);
+        self.acknowledged = self.acknowledged.max(acknowledged);
+    }
+
+    fn bind(&mut self, key: AnyKey, id: ElementId) {
+        self.ids.insert(id, key);
+        self.keys.insert(key, id);
+    }
+
+    /// drop the ids of keys that an op removed
+    fn forget(&mut self, keys: &[AnyKey]) {
+        for key in keys {
+            if !key.exists(&self.dcel)
+                && let Some(id) = self.keys.remove(key)
+            {
+                self.ids.remove(&id);
+            }
+        }
+    }
+
+    /// apply a command to the dcel, returning the op that undoes it. the elements it makes are
+    /// given ids in the order its inverse visits them
+    fn execute(&mut self, command: &Command<F>) -> Option<Op<F>> {
+        let mut op = command.op.clone();
+        let mut decode = Decode {
+            ids: &self.ids,
+            wire: &command.ids,
+            missing: false,
+        };
+        op.visit_keys(&mut decode);
+        if decode.missing {
+            return None;
+        }
+
+        let mut op = self.snap(op);
+        let referenced = op.keys();
+        let mut undo = self.dcel.check_apply(op).ok()?;
+        self.forget(&referenced);
+
+        let mut index = 0;
+        for key in undo.keys() {
+            if !self.keys.contains_key(&key) {
+                let id = ElementId {
+                    command: command.id,
+                    index,
+                };
+                self.bind(key, id);
+                index += 1;
+            }
+        }
+        Some(undo)
+    }
+
+    /// an isolated vertex on an edge splits the edge, the new half edges carry the weights of the
+    /// ones they continue
+    fn snap(&self, op: Op<F>) -> Op<F> {
+        let Op::Mvh(mvh) = op else {
+            return op;
+        };
+        match self.dcel.locate(mvh.vertex.xy()) {
+            Some(Location::Edge(edge)) => {
+                let data =
+                    [edge, edge.twin(&self.dcel)].map(|edge| self.dcel.edge(edge).weight.clone());
+                Op::Mev(Mev::new(edge, mvh.vertex, data))
+            }
+            _ => Op::Mvh(mvh),
+        }
+    }
+
+    /// undo every command from `at` on, newest first. elements those commands removed come back
+    /// under new keys, which are matched to their ids by their place in the command. if one can't
+    /// be undone, the ones undone after it are applied again and its id is returned
+    fn rollback(&mut self, at: usize) -> Result<(), CommandId> {
+        let mut log = std::mem::take(&mut self.log);
+        let mut failed = None;
+        for (i, entry) in log.iter_mut().enumerate().skip(at).rev() {
+            let Some(mut undo) = entry.undo.take() else {
+                continue;
+            };
+            let referenced = undo.keys();
+            let mut redo = match self.dcel.check_apply(undo) {
+                Ok(redo) => redo,
+                Err(err) => {
+                    entry.undo = Some(err.op);
+                    failed = Some(i);
+                    break;
+                }
+            };
+            self.forget(&referenced);
+
+            for (key, id) in redo.keys().into_iter().zip(&entry.command.ids) {
+                if !self.keys.contains_key(&key) {
+                    self.bind(key, *id);
+                }
+            }
+        }
+
+        if let Some(i) = failed {
+            for entry in &mut log[i + 1..] {
+                entry.undo = self.execute(&entry.command);
+            }
+        }
+        self.log = log;
+        match failed {
+            Some(i) => Err(self.log[i].command.id),
+            None => Ok(()),
+        }
+    }
+}
+
+/// turn keys into places in a list of ids
+struct Encode<'a> {
+    keys: &'a HashMap<AnyKey, ElementId>,
+    ids: Vec<ElementId>,
+    /// the first key without an id
+    missing: Option<AnyKey>,
+}
+
+impl Encode<'_> {
+    fn encode<K>(&mut self, key: &mut Key<K>)
+    where
+        Key<K>: Into<AnyKey>,
+    {
+        let local = (*key).into();
+        let Some(id) = self.keys.get(&local) else {
+            self.missing.get_or_insert(local);
+            return;
+        };
+        self.ids.push(*id);
+        *key = Key::new(self.ids.len() as u32).unwrap();
+    }
+}
+
+impl KeyVisitor for Encode<'_> {
+    fn vertex(&mut self, key: &mut Key<VertexKey>) {
+        self.encode(key);
+    }
+    fn edge(&mut self, key: &mut Key<EdgeKey>) {
+        self.encode(key);
+    }
+    fn face(&mut self, key: &mut Key<FaceKey>) {
+        self.encode(key);
+    }
+}
+
+/// turn places in a list of ids back into keys, if every id is known here
+struct Decode<'a> {
+    ids: &'a HashMap<ElementId, AnyKey>,
+    wire: &'a [ElementId],
+    missing: bool,
+}
+
+impl Decode<'_> {
+    fn decode<K>(&mut self, key: &mut Key<K>)
+    where
+        Key<K>: TryFrom<AnyKey>,
+    {
+        let local = self
+            .wire
+            .get(key.get() as usize - 1)
+            .and_then(|id| self.ids.get(id))
+            .and_then(|local| Key::try_from(*local).ok());
+        match local {
+            Some(local) => *key = local,
+            None => self.missing = true,
+        }
+    }
+}
+
+impl KeyVisitor for Decode<'_> {
+    fn vertex(&mut self, key: &mut Key<VertexKey>) {
+        self.decode(key);
+    }
+    fn edge(&mut self, key: &mut Key<EdgeKey>) {
+        self.decode(key);
+    }
+    fn face(&mut self, key: &mut Key<FaceKey>) {
+        self.decode(key);
+    }
+}
@@ -1,11 +1,11 @@
-use std::convert::Infallible;
+use std::{cmp::Ordering, convert::Infallible};
 
 use crate::{
     arena::Key,
     coord::{Coordinate, Orientation},
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, Keyed, Op, Traverser,
-        Vertex, VertexKey, VertexPtrs,
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, HolRef, Keyed, Op,
+        Traverser, Vertex, VertexKey, VertexPtrs,
         flavor::Flavor,
         linker::Linker,
         ops::{Operator, OperatorErr},
@@ -13,6 +13,14 @@ use crate::{
 };
 
 /// Make a Vertex + Edge connected to an existing Vertex
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Mef<F: Flavor> {
     pub vertices: [Key<VertexKey>; 2],
     pub data: (F::Face, F::Edge, F::Edge),
@@ -20,42 +28,62 @@ pub struct Mef<F: Flavor> {
 
 #[derive(thiserror::Error, Debug)]
 pub enum MefError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
     #[error("face does not match")]
     FaceMismatch,
     #[error("isolated vertex")]
     IsolatedVertex,
+    #[error("vertices are on different boundaries of the face")]
+    BoundaryMismatch,
 }
 
-impl<F: Flavor> Operator<F> for Mef<F>
+impl<F: Flavor> Mef<F>
 where
     F::Vertex: Coordinate,
 {
-    type Inverse = Kef;
-    type Error = MefError;
-
-    fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+    fn wedges(&self, dcel: &Dcel<F>) -> Result<[[Key<EdgeKey>; 2]; 2], MefError> {
         let [v1, v2] = self.vertices;
-        let Some(_) = dcel.vertices.get(v1) else {
-            return Err(MefError::IsolatedVertex);
-        };
-        let Some(_) = dcel.vertices.get(v2) else {
-            return Err(MefError::IsolatedVertex);
-        };
+        for v in self.vertices {
+            let vertex = dcel.vertices.get(v).ok_or(MefError::VertexDoesNotExist)?;
+            if vertex.edge.is_none() {
+                return Err(MefError::IsolatedVertex);
+            }
+        }
 
-        let [outgoing_local_prev, outgoing_local_next] = Linker::find_prev_next(dcel, v1, v2);
-        let [incoming_local_prev, incoming_local_next] = Linker::find_prev_next(dcel, v2, v1);
+        let outgoing = Linker::find_prev_next(dcel, v1, v2);
+        let incoming = Linker::find_prev_next(dcel, v2, v1);
 
-        // this will become outgoing.next
-        let outgoing_face = incoming_local_prev.face(dcel);
-        // this will become incoming.next
-        let incoming_face = outgoing_local_prev.face(dcel);
+        // the edges that will become outgoing.prev and incoming.prev
+        let outgoing_prev = outgoing[0].twin(dcel);
+        let incoming_prev = incoming[0].twin(dcel);
 
         // if they aren't equal we can't split this face
-        if outgoing_face != incoming_face {
+        if outgoing_prev.face(dcel) != incoming_prev.face(dcel) {
             return Err(MefError::FaceMismatch);
         }
 
-        Ok(())
+        // if they aren't on the same cycle this would join a hole rather than split the face
+        if !Traverser::through(dcel, outgoing_prev)
+            .unwrap()
+            .any(|edge| edge == incoming_prev)
+        {
+            return Err(MefError::BoundaryMismatch);
+        }
+
+        Ok([outgoing, incoming])
+    }
+}
+
+impl<F: Flavor> Operator<F> for Mef<F>
+where
+    F::Vertex: Coordinate,
+{
+    type Inverse = Kef;
+    type Error = MefError;
+
+    fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        self.wedges(dcel).map(|_| ())
     }
 
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
@@ -76,74 +104,52 @@ where
 
         let [v1, v2] = self.vertices;
 
-        let [outgoing_local_prev, outgoing_local_next] = Linker::find_prev_next(dcel, v1, v2);
-        let [incoming_local_prev, incoming_local_next] = Linker::find_prev_next(dcel, v2, v1);
-
-        // this will become outgoing.next
-        let outgoing_face = incoming_local_prev.face(dcel);
-        // this will become incoming.next
-        let incoming_face = outgoing_local_prev.face(dcel);
+        let [
+            [outgoing_local_prev, outgoing_local_next],
+            [incoming_local_prev, incoming_local_next],
+        ] = match self.wedges(dcel) {
+            Ok(wedges) => wedges,
+            Err(err) => return Err(OperatorErr { op: self, err }),
+        };
 
-        // if they aren't equal we can't split this face
-        if outgoing_face != incoming_face {
-            return Err(OperatorErr {
-                op: self,
-                err: MefError::FaceMismatch,
-            });
-        }
-        let input = &outgoing_face;
-
-        let outgoing = dcel.edges.reserve();
-        let incoming = dcel.edges.reserve();
-
-        dcel.edges.set(
-            outgoing,
-            Edge {
-                inner: EdgePtrs {
-                    origin: v1,
-                    twin: incoming,
-                    prev: incoming,
-                    next: incoming,
-                    face: *input,
+        let input = outgoing_local_prev.twin(dcel).face(dcel);
+
+        let [outgoing, incoming] = dcel.edges.insert_many_with(|[outgoing, incoming]| {
+            [
+                Edge {
+                    inner: EdgePtrs {
+                        origin: v1,
+                        twin: incoming,
+                        prev: incoming,
+                        next: incoming,
+                        face: input,
+                    },
+                    weight: self.data.1,
                 },
-                weight: self.data.1,
-            },
-        );
-
-        dcel.edges.set(
-            incoming,
-            Edge {
-                inner: EdgePtrs {
-                    origin: v2,
-                    twin: outgoing,
-                    prev: outgoing,
-                    next: outgoing,
-                    face: *input,
+                Edge {
+                    inner: EdgePtrs {
+                        origin: v2,
+                        twin: outgoing,
+                        prev: outgoing,
+                        next: outgoing,
+                        face: input,
+                    },
+                    weight: self.data.2,
                 },
-                weight: self.data.2,
-            },
-        );
-
-        // Linker::splice_edge(dcel, outgoing, outgoing_local_prev, outgoing_local_next);
-        // Linker::splice_edge(dcel, incoming, incoming_local_prev, incoming_local_next);
-
-        Linker::follow(dcel, outgoing_local_next.twin(dcel), outgoing);
-        Linker::follow(dcel, outgoing, incoming_local_prev);
-
-        Linker::follow(dcel, incoming_local_next.twin(dcel), incoming);
-        Linker::follow(dcel, incoming, outgoing_local_prev);
+            ]
+        });
 
-        let face_orientation = Traverser::shoestring(dcel, input.edge(dcel))
-            .unwrap()
-            .orientation();
+        Linker::splice_edge(dcel, outgoing, outgoing_local_prev, outgoing_local_next);
+        Linker::splice_edge(dcel, incoming, incoming_local_prev, incoming_local_next);
 
-        let outgoing_orientation = Traverser::shoestring(dcel, outgoing).unwrap().orientation();
+        let outgoing_area = Traverser::shoestring(dcel, outgoing).unwrap();
+        let incoming_area = Traverser::shoestring(dcel, incoming).unwrap();
 
-        // use the edge that causes the opposite orientation of the face we are splitting
-        let propagate = if face_orientation == outgoing_orientation {
-            incoming
+        // the new face takes the clockwise side, which leaves the split face with its outer boundary
+        let (propagate, remain) = if outgoing_area.cmp_area(&incoming_area) == Ordering::Less {
+            (outgoing, incoming)
         } else {
-            outgoing
+            (incoming, outgoing)
         };
         let face = dcel.faces.insert(Face {
             inner: FacePtrs {
@@ -156,6 +162,20 @@ where
 
         dcel.propagate_face(propagate, face).unwrap();
 
+        // whichever boundary of the split face was cut now continues through the remaining edge
+        if input.edge(dcel).face(dcel) != input {
+            dcel.face_mut(input).edge = remain;
+        }
+        for hole in dcel.faces[input].holes.iter_mut() {
+            if let HolRef::Edge(edge) = hole
+                && dcel.edges[*edge].face != input
+            {
+                *edge = remain;
+            }
+        }
+
+        adopt_enclosed_holes(dcel, input, face, remain);
+
         Ok(Kef {
             face,
             edges: [outgoing, incoming],
@@ -163,6 +183,67 @@ where
     }
 }
 
+/// move the holes of a face that are enclosed by the boundary of a face split off from it
+fn adopt_enclosed_holes<F: Flavor>(
+    dcel: &mut Dcel<F>,
+    from: Key<FaceKey>,
+    into: Key<FaceKey>,
+    remain: Key<EdgeKey>,
+) where
+    F::Vertex: Coordinate,
+{
+    let boundary = into.edge(dcel);
+    if !Traverser::shoestring(dcel, boundary)
+        .unwrap()
+        .orientation()
+        .is_cw()
+    {
+        return;
+    }
+
+    // the outer boundary of the bounding face is just another component that could be enclosed
+    let is_bounding = dcel.bounding_face == Some(from);
+    let mut candidates = std::mem::take(&mut dcel.face_mut(from).holes);
+    if is_bounding {
+        candidates.push(HolRef::Edge(from.edge(dcel)));
+    }
+
+    // what is left of the cycle that was split touches the new boundary, so it can't be tested
+    let split = Traverser::through(dcel, remain)
+        .unwrap()
+        .collect::<Vec<_>>();
+    let (enclosed, mut kept): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|hole| {
+        let point = match *hole {
+            HolRef::Edge(edge) if split.contains(&edge) => return false,
+            HolRef::Edge(edge) => edge.origin(dcel).weight(dcel).xy(),
+            HolRef::Vertex(vertex) => vertex.weight(dcel).xy(),
+        };
+        Traverser::encloses(dcel, boundary, point).unwrap()
+    });
+
+    for hole in &enclosed {
+        if let HolRef::Edge(edge) = hole {
+            dcel.propagate_face(*edge, into).unwrap();
+        }
+    }
+
+    if is_bounding {
+        let outer = kept
+            .iter()
+            .rposition(|hole| matches!(hole, HolRef::Edge(_)))
+            .unwrap();
+        let HolRef::Edge(edge) = kept.remove(outer) else {
+            unreachable!()
+        };
+        dcel.face_mut(from).edge = edge;
+    }
+
+    dcel.face_mut(from).holes = kept;
+    dcel.face_mut(into).holes.extend(enclosed);
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kef {
     pub face: Key<FaceKey>,
     pub edges: [Key<EdgeKey>; 2],
@@ -170,8 +251,44 @@ pub struct Kef {
 
 #[derive(thiserror::Error, Debug)]
 pub enum KefError {
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
+    #[error("face does not exist")]
+    FaceDoesNotExist,
+    #[error("edges are not twins")]
+    NotTwins,
     #[error("splitting face and face do not share an edge")]
     FaceMismatch,
+    #[error("edge has the same face on both sides")]
+    SameFace,
+    #[error("the bounding face can not be killed")]
+    BoundingFace,
+}
+
+impl Kef {
+    /// the face that remains once the edge is gone
+    fn remaining_face<F: Flavor>(&self, dcel: &Dcel<F>) -> Result<Key<FaceKey>, KefError> {
+        let [e1, e2] = self.edges;
+        let edge = dcel.edges.get(e1).ok_or(KefError::EdgeDoesNotExist)?;
+        let twin = dcel.edges.get(e2).ok_or(KefError::EdgeDoesNotExist)?;
+        dcel.faces
+            .get(self.face)
+            .ok_or(KefError::FaceDoesNotExist)?;
+        if edge.twin != e2 {
+            return Err(KefError::NotTwins);
+        }
+        if edge.face == twin.face {
+            return Err(KefError::SameFace);
+        }
+        if dcel.bounding_face == Some(self.face) {
+            return Err(KefError::BoundingFace);
+        }
+        match self.face {
+            face if face == edge.face => Ok(twin.face),
+            face if face == twin.face => Ok(edge.face),
+            _ => Err(KefError::FaceMismatch),
+        }
+    }
 }
 
 impl<F: Flavor> Operator<F> for Kef
@@ -179,25 +296,35 @@ where
     F::Vertex: Coordinate,
 {
     type Inverse = Mef<F>;
-    type Error = Infallible;
+    type Error = KefError;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
-        Ok(())
+        self.remaining_face(dcel).map(|_| ())
     }
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        let rface = match self.remaining_face(dcel) {
+            Ok(face) => face,
+            Err(err) => return Err(OperatorErr { op: self, err }),
+        };
         let [e1, e2] = self.edges;
-        println!("kef {e1} {e2}");
-        let e1f = e1.face(dcel);
-        let e2f = e2.face(dcel);
-        let rface = if self.face == e1f { e2f } else { e1f };
 
         dcel.propagate_face(e1, rface).unwrap();
         dcel.propagate_face(e2, rface).unwrap();
 
+        // the holes of the killed face are now holes of the face it merged into
+        let holes = std::mem::take(&mut dcel.face_mut(self.face).holes);
+        for hole in &holes {
+            if let HolRef::Edge(edge) = hole {
+                dcel.propagate_face(*edge, rface).unwrap();
+            }
+        }
+        dcel.face_mut(rface).holes.extend(holes);
+
+        dcel.retarget_boundaries(&self.edges, None);
         Linker::unsplice_edge(dcel, self.edges);
 
-        let outgoing = dcel.edges.remove(e1).unwrap();
         let incoming = dcel.edges.remove(e2).unwrap();
+        let outgoing = dcel.edges.remove(e1).unwrap();
         let face = dcel.faces.remove(self.face).unwrap();
 
         Ok(Mef {
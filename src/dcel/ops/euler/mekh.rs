@@ -1,48 +1,305 @@
+use std::cmp::Ordering;
+
 use crate::{
     arena::Key,
+    coord::Coordinate,
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, FaceKey, FacePtrs, Keyed, Traverser, Vertex, VertexKey,
-        VertexPtrs,
+        Dcel, Edge, EdgeKey, EdgePtrs, FaceKey, HolRef, Traverser, VertexKey,
         flavor::Flavor,
-        linker::Linker,
+        linker::{Linker, Wedge},
         ops::{Operator, OperatorErr},
     },
 };
 
-pub struct Mekh {}
+/// Make an Edge that joins two boundaries of the same face, killing one of them as a hole
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Mekh<F: Flavor> {
+    pub vertices: [Key<VertexKey>; 2],
+    pub data: (F::Edge, F::Edge),
+}
 
 #[derive(thiserror::Error, Debug)]
-pub enum MekhError {}
+pub enum MekhError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("vertex is not inside a face")]
+    NoFace,
+    #[error("face does not match")]
+    FaceMismatch,
+    #[error("vertices are on the same boundary of the face")]
+    SameBoundary,
+}
+
+impl<F: Flavor> Mekh<F>
+where
+    F::Vertex: Coordinate,
+{
+    fn wedges(&self, dcel: &Dcel<F>) -> Result<[Wedge; 2], MekhError> {
+        let [v1, v2] = self.vertices;
+        for v in self.vertices {
+            dcel.vertices.get(v).ok_or(MekhError::VertexDoesNotExist)?;
+        }
+        if v1 == v2 {
+            return Err(MekhError::SameBoundary);
+        }
+
+        let outgoing = Linker::find_wedge(dcel, v1, v2).ok_or(MekhError::NoFace)?;
+        let incoming = Linker::find_wedge(dcel, v2, v1).ok_or(MekhError::NoFace)?;
+
+        if outgoing.face != incoming.face {
+            return Err(MekhError::FaceMismatch);
+        }
+
+        if let (Some([outgoing_prev, _]), Some([incoming_prev, _])) =
+            (outgoing.edges, incoming.edges)
+        {
+            let incoming_prev = incoming_prev.twin(dcel);
+            if Traverser::through(dcel, outgoing_prev.twin(dcel))
+                .unwrap()
+                .any(|edge| edge == incoming_prev)
+            {
+                return Err(MekhError::SameBoundary);
+            }
+        }
+
+        Ok([outgoing, incoming])
+    }
+}
 
-impl<F: Flavor> Operator<F> for Mekh {
+impl<F: Flavor> Operator<F> for Mekh<F>
+where
+    F::Vertex: Coordinate,
+{
     type Error = MekhError;
 
     type Inverse = Kemh;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
-        Ok(())
+        self.wedges(dcel).map(|_| ())
     }
 
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        todo!()
+        // before:
+        //    ______________
+        //   |     ____     |
+        //   |    |    |    |
+        //   v1   v2   |    |
+        //   |    |____|    |
+        //   |______________|
+        //
+        // after:
+        //    ______________
+        //   |     ____     |
+        //   |    |    |    |
+        //   v1---v2   |    |
+        //   |    |____|    |
+        //   |______________|
+        //
+        let [v1, v2] = self.vertices;
+        let [outgoing_wedge, incoming_wedge] = match self.wedges(dcel) {
+            Ok(wedges) => wedges,
+            Err(err) => return Err(OperatorErr { op: self, err }),
+        };
+        let face = outgoing_wedge.face;
+
+        // the hole that disappears once the two boundaries are one
+        let killed = match (outgoing_wedge.edges, incoming_wedge.edges) {
+            (_, None) => dcel.faces[face]
+                .holes
+                .iter()
+                .position(|hole| *hole == HolRef::Vertex(v2)),
+            (None, Some(_)) => dcel.faces[face]
+                .holes
+                .iter()
+                .position(|hole| *hole == HolRef::Vertex(v1)),
+            (Some([outgoing_prev, _]), Some([incoming_prev, _])) => dcel
+                .hole_of_cycle(face, incoming_prev.twin(dcel))
+                .or_else(|| dcel.hole_of_cycle(face, outgoing_prev.twin(dcel))),
+        };
+
+        let [outgoing, incoming] = dcel.edges.insert_many_with(|[outgoing, incoming]| {
+            [
+                Edge {
+                    inner: EdgePtrs {
+                        origin: v1,
+                        twin: incoming,
+                        prev: incoming,
+                        next: incoming,
+                        face,
+                    },
+                    weight: self.data.0,
+                },
+                Edge {
+                    inner: EdgePtrs {
+                        origin: v2,
+                        twin: outgoing,
+                        prev: outgoing,
+                        next: outgoing,
+                        face,
+                    },
+                    weight: self.data.1,
+                },
+            ]
+        });
+
+        Linker::splice_wedge(dcel, outgoing, &outgoing_wedge);
+        Linker::splice_wedge(dcel, incoming, &incoming_wedge);
+
+        let holes = &mut dcel.faces[face].holes;
+        if let Some(killed) = killed {
+            holes.swap_remove(killed);
+        }
+        // two isolated vertices merge into a single hole that is now an edge
+        if outgoing_wedge.edges.is_none() && incoming_wedge.edges.is_none() {
+            holes.retain(|hole| *hole != HolRef::Vertex(v1));
+            holes.push(HolRef::Edge(outgoing));
+        }
+
+        Ok(Kemh {
+            edges: [outgoing, incoming],
+        })
     }
 }
 
-pub struct Kemh {}
+/// Kill an Edge that has the same face on both sides, making a hole from one of the boundaries it joined
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Kemh {
+    pub edges: [Key<EdgeKey>; 2],
+}
 
 #[derive(thiserror::Error, Debug)]
-pub enum KhmeError {}
+pub enum KemhError {
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
+    #[error("edges are not twins")]
+    NotTwins,
+    #[error("edge separates two different faces")]
+    FaceMismatch,
+    #[error("this operation would leave the face without a boundary")]
+    WouldKillFace,
+}
+
+impl Kemh {
+    fn check_face<F: Flavor>(&self, dcel: &Dcel<F>) -> Result<Key<FaceKey>, KemhError> {
+        let [e1, e2] = self.edges;
+        let edge = dcel.edges.get(e1).ok_or(KemhError::EdgeDoesNotExist)?;
+        dcel.edges.get(e2).ok_or(KemhError::EdgeDoesNotExist)?;
+        if edge.twin != e2 {
+            return Err(KemhError::NotTwins);
+        }
+
+        let face = edge.face;
+        if e2.face(dcel) != face {
+            return Err(KemhError::FaceMismatch);
+        }
+
+        if dcel.is_last_boundary(e1) {
+            return Err(KemhError::WouldKillFace);
+        }
 
-impl<F: Flavor> Operator<F> for Kemh {
-    type Error = KhmeError;
+        Ok(face)
+    }
+}
 
-    type Inverse = Mekh;
+impl<F: Flavor> Operator<F> for Kemh
+where
+    F::Vertex: Coordinate,
+{
+    type Error = KemhError;
+
+    type Inverse = Mekh<F>;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
-        Ok(())
+        self.check_face(dcel).map(|_| ())
     }
 
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        todo!()
+        let face = match self.check_face(dcel) {
+            Ok(face) => face,
+            Err(err) => return Err(OperatorErr { op: self, err }),
+        };
+
+        let [e1, e2] = self.edges;
+        let [u, w] = [e1.origin(dcel), e2.origin(dcel)];
+
+        // what remains on either side of the edge once it is gone
+        let parts = [(e1, w), (e2, u)].map(|(edge, end)| {
+            let next = edge.next(dcel);
+            if next == edge.twin(dcel) {
+                HolRef::Vertex(end)
+            } else {
+                HolRef::Edge(next)
+            }
+        });
+
+        let is_outer = Traverser::through(dcel, e1)
+            .unwrap()
+            .any(|edge| edge == face.edge(dcel));
+        let hole = if is_outer {
+            None
+        } else {
+            dcel.hole_of_cycle(face, e1)
+        };
+
+        Linker::unsplice_edge(dcel, self.edges);
+
+        let [first, second] = parts;
+        if is_outer {
+            let is_bounding = dcel.bounding_face == Some(face);
+            let areas = parts.map(|part| match part {
+                HolRef::Edge(edge) => Some(Traverser::shoestring(dcel, edge).unwrap()),
+                HolRef::Vertex(_) => None,
+            });
+            // the outer boundary of a bounded face is the clockwise side
+            let outer = match &areas {
+                [Some(a1), Some(a2)] if !is_bounding && a2.cmp_area(a1) == Ordering::Less => 1,
+                [None, Some(_)] => 1,
+                [None, None] => 2,
+                _ => 0,
+            };
+
+            let holes = &mut dcel.faces[face].holes;
+            if outer == 2 {
+                // promote another hole to the boundary of the bounding face
+                let promoted = holes
+                    .iter()
+                    .position(|hole| matches!(hole, HolRef::Edge(_)))
+                    .unwrap();
+                let HolRef::Edge(edge) = holes.swap_remove(promoted) else {
+                    unreachable!()
+                };
+                holes.extend(parts);
+                dcel.face_mut(face).edge = edge;
+            } else {
+                holes.push(parts[1 - outer]);
+                let HolRef::Edge(edge) = parts[outer] else {
+                    unreachable!()
+                };
+                dcel.face_mut(face).edge = edge;
+            }
+        } else {
+            let holes = &mut dcel.faces[face].holes;
+            match hole {
+                Some(hole) => holes[hole] = first,
+                None => holes.push(first),
+            }
+            holes.push(second);
+        }
+
+        let e2 = dcel.edges.remove(e2).unwrap();
+        let e1 = dcel.edges.remove(e1).unwrap();
+
+        Ok(Mekh {
+            vertices: [u, w],
+            data: (e1.weight, e2.weight),
+        })
     }
 }
@@ -1,45 +1,217 @@
-use std::convert::Infallible;
-
-use crate::dcel::{
-    Dcel, Edge, EdgePtrs,
-    flavor::Flavor,
-    ops::{Operator, OperatorErr},
+use crate::{
+    arena::Key,
+    dcel::{
+        Dcel, Edge, EdgeKey, EdgePtrs, Vertex, VertexKey, VertexPtrs,
+        flavor::Flavor,
+        linker::Linker,
+        ops::{Operator, OperatorErr},
+    },
 };
 
-/// ad a vertex on an edge
+/// Make a Vertex + Edge by splitting an existing half edge pair
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Mev<F: Flavor> {
-    pub edge: EdgePtrs,
-    pub data: F::Edge,
+    /// the half edge to split, it will end at the new vertex
+    pub edge: Key<EdgeKey>,
+    pub vertex: F::Vertex,
+    /// weights of the new half edge leaving the new vertex, and of its twin
+    pub data: [F::Edge; 2],
 }
 
 impl<F: Flavor> Mev<F> {
-    pub fn new(edge: EdgePtrs, data: F::Edge) -> Self {
-        Self { edge, data }
+    pub fn new(edge: Key<EdgeKey>, vertex: F::Vertex, data: [F::Edge; 2]) -> Self {
+        Self { edge, vertex, data }
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum MevError {
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
+}
+
 impl<F: Flavor> Operator<F> for Mev<F> {
     type Inverse = Kev;
-    type Error = Infallible;
+    type Error = MevError;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        dcel.edges
+            .get(self.edge)
+            .ok_or(MevError::EdgeDoesNotExist)?;
         Ok(())
     }
+
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        todo!()
+        // before:
+        //
+        //          e ->
+        //   a --------------- b
+        //          <- t
+        //
+        // after:
+        //
+        //      e ->     e2 ->
+        //   a ------ m ------ b
+        //      <- t     <- t2
+        //
+        if dcel.edges.get(self.edge).is_none() {
+            return Err(OperatorErr {
+                op: self,
+                err: MevError::EdgeDoesNotExist,
+            });
+        }
+
+        let e = self.edge;
+        let t = e.twin(dcel);
+        let b = t.origin(dcel);
+        let e_next = e.next(dcel);
+        let t_prev = t.prev(dcel);
+
+        let m = dcel.vertices.insert(Vertex {
+            inner: VertexPtrs { edge: None },
+            weight: self.vertex,
+        });
+
+        let [e2_weight, t2_weight] = self.data;
+        let [e_face, t_face] = [e.face(dcel), t.face(dcel)];
+
+        let [e2, t2] = dcel.edges.insert_many_with(|[e2, t2]| {
+            [
+                Edge {
+                    inner: EdgePtrs {
+                        origin: m,
+                        twin: t2,
+                        prev: e,
+                        next: e_next,
+                        face: e_face,
+                    },
+                    weight: e2_weight,
+                },
+                Edge {
+                    inner: EdgePtrs {
+                        origin: b,
+                        twin: e2,
+                        prev: t_prev,
+                        next: t,
+                        face: t_face,
+                    },
+                    weight: t2_weight,
+                },
+            ]
+        });
+
+        dcel.vertex_mut(m).edge = Some(e2);
+        dcel.edge_mut(e).next = e2;
+        dcel.edge_mut(t).prev = t2;
+        dcel.edge_mut(t).origin = m;
+
+        if e_next == t {
+            // b was only connected through this edge, so the new pair turns around at b
+            Linker::follow(dcel, e2, t2);
+        } else {
+            Linker::follow(dcel, e2, e_next);
+            Linker::follow(dcel, t_prev, t2);
+        }
+
+        if b.edge(dcel) == Some(t) {
+            dcel.vertex_mut(b).edge = Some(t2);
+        }
+
+        Ok(Kev {
+            vertex: m,
+            edges: [e2, t2],
+        })
     }
 }
 
-pub struct Kev {}
+/// Kill a Vertex + Edge by merging the two half edge pairs around a vertex of degree two
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Kev {
+    pub vertex: Key<VertexKey>,
+    /// the half edge leaving the vertex that will be removed, and its twin
+    pub edges: [Key<EdgeKey>; 2],
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum KevError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
+    #[error("edges are not twins leaving the vertex")]
+    EdgeVertexMismatch,
+    #[error("vertex does not have exactly two edges")]
+    NotDegreeTwo,
+}
 
 impl<F: Flavor> Operator<F> for Kev {
     type Inverse = Mev<F>;
-    type Error = Infallible;
+    type Error = KevError;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        let [e2, t2] = self.edges;
+        dcel.vertices
+            .get(self.vertex)
+            .ok_or(KevError::VertexDoesNotExist)?;
+        let edge = dcel.edges.get(e2).ok_or(KevError::EdgeDoesNotExist)?;
+        dcel.edges.get(t2).ok_or(KevError::EdgeDoesNotExist)?;
+
+        if edge.origin != self.vertex || edge.twin != t2 {
+            return Err(KevError::EdgeVertexMismatch);
+        }
+
+        // the other edge around the vertex, which must lead straight back to e2
+        let e = edge.prev;
+        let t = e.twin(dcel);
+        if e == t2 || t.prev(dcel) != t2 {
+            return Err(KevError::NotDegreeTwo);
+        }
+
         Ok(())
     }
+
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        todo!()
+        if let Err(err) = Operator::<F>::check(&self, dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
+        let [e2, t2] = self.edges;
+        let e = e2.prev(dcel);
+        let t = t2.next(dcel);
+        let b = t2.origin(dcel);
+        let e2_next = e2.next(dcel);
+        let t2_prev = t2.prev(dcel);
+
+        dcel.retarget_boundaries(&self.edges, None);
+
+        if e2_next == t2 {
+            Linker::follow(dcel, e, t);
+        } else {
+            Linker::follow(dcel, e, e2_next);
+            Linker::follow(dcel, t2_prev, t);
+        }
+        dcel.edge_mut(t).origin = b;
+
+        if b.edge(dcel) == Some(t2) {
+            dcel.vertex_mut(b).edge = Some(t);
+        }
+
+        let t2 = dcel.edges.remove(t2).unwrap();
+        let e2 = dcel.edges.remove(e2).unwrap();
+        let vertex = dcel.vertices.remove(self.vertex).unwrap();
+
+        Ok(Mev {
+            edge: e,
+            vertex: vertex.weight,
+            data: [e2.weight, t2.weight],
+        })
     }
 }
@@ -2,10 +2,10 @@ use std::convert::Infallible;
 
 use crate::{
     arena::Key,
-    coord::Coordinate,
+    coord::{Coordinate, Scalar},
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, FaceKey, FacePtrs, Keyed, Traverser, Vertex, VertexKey,
-        VertexPtrs,
+        Dcel, Edge, EdgeKey, EdgePtrs, FaceKey, FacePtrs, HolRef, Keyed, Traverser, Vertex,
+        VertexKey, VertexPtrs,
         flavor::Flavor,
         linker::Linker,
         ops::{Operator, OperatorErr},
@@ -13,6 +13,14 @@ use crate::{
 };
 
 /// Make a Vertex + Edge connected to an existing Vertex
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Mve<F: Flavor> {
     pub origin: Key<VertexKey>,
     pub edges: [F::Edge; 2],
@@ -33,9 +41,19 @@ impl<F: Flavor> Mve<F> {
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum MevError {
+pub enum MveError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
     #[error("local cycle does not have uniform faces")]
     NextPrevFaceMismatch,
+    #[error("isolated origin is not inside a face")]
+    NoFace,
+    #[error("vertex coordinate is not finite")]
+    NonFiniteCoordinate,
+    #[error("new vertex is at the same position as the origin")]
+    ZeroLengthEdge,
+    #[error("edge to reparent does not leave the origin")]
+    ReparentMismatch,
 }
 
 impl<F: Flavor> Operator<F> for Mve<F>
@@ -43,13 +61,38 @@ where
     F::Vertex: Coordinate,
 {
     type Inverse = Kve;
-    type Error = MevError;
+    type Error = MveError;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        let origin = dcel
+            .vertices
+            .get(self.origin)
+            .ok_or(MveError::VertexDoesNotExist)?;
+        let position = self.vertex.xy();
+        if !position.iter().all(|c| c.is_finite()) {
+            return Err(MveError::NonFiniteCoordinate);
+        }
+        if position == origin.weight.xy() {
+            return Err(MveError::ZeroLengthEdge);
+        }
+        if origin.edge.is_none() && dcel.isolated_vertex_face(self.origin).is_none() {
+            return Err(MveError::NoFace);
+        }
+        if self
+            .reparent
+            .iter()
+            .any(|edge| dcel.edges.get(*edge).map(|e| e.origin) != Some(self.origin))
+        {
+            return Err(MveError::ReparentMismatch);
+        }
         Ok(())
     }
 
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = self.check(dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
         //
         //    > o >
         //  a |   | b
@@ -77,50 +120,56 @@ where
         let [he1_weight, he2_weight] = self.edges;
         let vertex_weight = self.vertex;
 
-        let outgoing = dcel.edges.reserve();
-        let incoming = dcel.edges.reserve();
-
         let vertex = dcel.vertices.insert(Vertex {
-            inner: VertexPtrs {
-                edge: Some(incoming),
-            },
+            inner: VertexPtrs { edge: None },
             weight: vertex_weight,
         });
 
-        let [outgoing_prev, outgoing_next] = Linker::find_prev_next(dcel, self.origin, vertex);
-        let outgoing_face = outgoing_prev.twin(dcel).face(dcel);
-
-        dcel.edges.set(
-            outgoing,
-            Edge {
-                inner: EdgePtrs {
-                    origin: self.origin,
-                    twin: incoming,
-                    prev: outgoing_prev,
-                    next: incoming,
-                    face: outgoing_face,
+        let wedge = Linker::find_wedge(dcel, self.origin, vertex).unwrap();
+        let outgoing_face = wedge.face;
+        let origin = self.origin;
+
+        let [outgoing, incoming] = dcel.edges.insert_many_with(|[outgoing, incoming]| {
+            [
+                Edge {
+                    inner: EdgePtrs {
+                        origin,
+                        twin: incoming,
+                        prev: incoming,
+                        next: incoming,
+                        face: outgoing_face,
+                    },
+                    weight: he1_weight,
                 },
-                weight: he1_weight,
-            },
-        );
-
-        dcel.edges.set(
-            incoming,
-            Edge {
-                inner: EdgePtrs {
-                    origin: vertex,
-                    twin: outgoing,
-                    prev: outgoing,
-                    next: outgoing,
-                    face: outgoing_face,
+                Edge {
+                    inner: EdgePtrs {
+                        origin: vertex,
+                        twin: outgoing,
+                        prev: outgoing,
+                        next: outgoing,
+                        face: outgoing_face,
+                    },
+                    weight: he2_weight,
                 },
-                weight: he2_weight,
-            },
-        );
+            ]
+        });
+        dcel.vertex_mut(vertex).edge = Some(incoming);
 
-        Linker::splice_edge(dcel, outgoing, outgoing_prev, outgoing_next);
+        Linker::splice_wedge(dcel, outgoing, &wedge);
         Linker::splice_edge(dcel, incoming, incoming, incoming);
 
+        // an isolated origin stops being a hole of its own, the stub is the hole now
+        if wedge.edges.is_none() {
+            let holes = &mut dcel.faces[outgoing_face].holes;
+            match holes
+                .iter()
+                .position(|hole| *hole == HolRef::Vertex(self.origin))
+            {
+                Some(hole) => holes[hole] = HolRef::Edge(outgoing),
+                None => holes.push(HolRef::Edge(outgoing)),
+            }
+        }
+
         // carry over some edges from the origin when making the new vertex. they wil now go to
         if self.reparent.len() > 0 {
             let mut linker = Linker::new();
@@ -135,6 +184,8 @@ where
     }
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kve {
     pub origin: Key<VertexKey>,
     pub vertex: Key<VertexKey>,
@@ -153,9 +204,13 @@ impl Kve {
 
 #[derive(thiserror::Error, Debug)]
 pub enum KveError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
     #[error("this operationw would kill a face")]
     WouldKillFace,
-    #[error("neither edge has the target vertex as an origin")]
+    #[error("edges are not twins between the origin and the vertex")]
     EdgeVertexMismatch,
 }
 
@@ -168,32 +223,38 @@ where
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
         let [outgoing, incoming] = self.edges;
-        let outgoing_face = outgoing.face(dcel);
-        let incoming_face = incoming.face(dcel);
+        for v in [self.origin, self.vertex] {
+            dcel.vertices.get(v).ok_or(KveError::VertexDoesNotExist)?;
+        }
+        let edge = dcel.edges.get(outgoing).ok_or(KveError::EdgeDoesNotExist)?;
+        let twin = dcel.edges.get(incoming).ok_or(KveError::EdgeDoesNotExist)?;
+        if edge.twin != incoming || edge.origin != self.origin || twin.origin != self.vertex {
+            return Err(KveError::EdgeVertexMismatch);
+        }
 
-        let outgoing_count = Traverser::through(dcel, outgoing_face.edge(dcel))
-            .unwrap()
-            .count();
-        let incoming_count = Traverser::through(dcel, incoming_face.edge(dcel))
-            .unwrap()
-            .count();
+        let outgoing_count = Traverser::through(dcel, outgoing).unwrap().count();
+        let incoming_count = Traverser::through(dcel, incoming).unwrap().count();
 
-        if outgoing_count == 3 || incoming_count == 3 {
+        if outgoing_count == 3 || incoming_count == 3 || dcel.is_last_boundary(outgoing) {
             return Err(KveError::WouldKillFace);
         }
 
         Ok(())
     }
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = Operator::<F>::check(&self, dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
         let mut linker = Linker::new();
-        // dcel.vertex(self.vertex);
 
         let [outgoing, incoming] = self.edges;
 
         // unsplice edges from the graph
+        dcel.retarget_boundaries(&self.edges, Some(self.origin));
         Linker::unsplice_edge(dcel, self.edges);
-        let outgoing = dcel.edges.remove(outgoing).unwrap();
         let incoming = dcel.edges.remove(incoming).unwrap();
+        let outgoing = dcel.edges.remove(outgoing).unwrap();
 
         // reparent remaining edge around vertex
         let reparent = linker.reparent_vertex(dcel, self.origin, self.vertex, None);
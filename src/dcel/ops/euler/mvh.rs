@@ -1,65 +1,122 @@
-use std::convert::Infallible;
-
 use crate::{
     arena::Key,
+    coord::{Coordinate, Scalar},
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, Vertex, VertexKey,
-        VertexPtrs,
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, HolRef, Location, Vertex,
+        VertexKey, VertexPtrs,
         flavor::Flavor,
         ops::{Operator, OperatorErr},
     },
 };
 
+/// Make an isolated Vertex, which is a hole in the face that contains it
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Mvh<F: Flavor> {
     pub vertex: F::Vertex,
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum MvhError {}
+pub enum MvhError {
+    #[error("vertex coordinate is not finite")]
+    NonFiniteCoordinate,
+    #[error("there is a vertex at that point already")]
+    OnVertex(Key<VertexKey>),
+    #[error("the point lies on an edge")]
+    OnEdge(Key<EdgeKey>),
+}
 
-impl<F: Flavor> Operator<F> for Mvh<F> {
-    type Error = Infallible;
+impl<F: Flavor> Operator<F> for Mvh<F>
+where
+    F::Vertex: Coordinate,
+{
+    type Error = MvhError;
     type Inverse = Kvh;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
-        Ok(())
+        if !self.vertex.xy().iter().all(|c| c.is_finite()) {
+            return Err(MvhError::NonFiniteCoordinate);
+        }
+        match dcel.locate(self.vertex.xy()) {
+            Some(Location::Vertex(vertex)) => Err(MvhError::OnVertex(vertex)),
+            Some(Location::Edge(edge)) => Err(MvhError::OnEdge(edge)),
+            _ => Ok(()),
+        }
     }
 
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = self.check(dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
+        let face = match dcel.locate(self.vertex.xy()) {
+            Some(Location::Face(face)) => Some(face),
+            _ => None,
+        };
+
         let vertex = dcel.vertices.insert(Vertex {
             inner: VertexPtrs { edge: None },
             weight: self.vertex,
         });
 
+        // without a bounding face there is nothing to be a hole of yet, the vertex is adopted once there is
+        if let Some(face) = face {
+            dcel.face_mut(face).holes.push(HolRef::Vertex(vertex));
+        }
+
         Ok(Kvh { vertex })
     }
 }
 
+/// Kill an isolated Vertex, and the hole it made
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kvh {
     pub vertex: Key<VertexKey>,
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum KvError {
+pub enum KvhError {
     #[error("vertex does not exist")]
     VertexDoesNotExist,
+    #[error("vertex still has edges")]
+    NotIsolated,
 }
 
-impl<F: Flavor> Operator<F> for Kvh {
-    type Error = KvError;
+impl<F: Flavor> Operator<F> for Kvh
+where
+    F::Vertex: Coordinate,
+{
+    type Error = KvhError;
     type Inverse = Mvh<F>;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        let vertex = dcel
+            .vertices
+            .get(self.vertex)
+            .ok_or(KvhError::VertexDoesNotExist)?;
+        if vertex.edge.is_some() {
+            return Err(KvhError::NotIsolated);
+        }
         Ok(())
     }
+
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        let vertex = dcel.vertices.remove(self.vertex).unwrap().weight;
-        match dcel.vertices.remove(self.vertex) {
-            Some(Vertex { weight, .. }) => Ok(Mvh { vertex }),
-            None => Err(OperatorErr {
-                op: self,
-                err: KvError::VertexDoesNotExist,
-            }),
+        if let Err(err) = Operator::<F>::check(&self, dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
+        let Vertex { weight, .. } = dcel.vertices.remove(self.vertex).unwrap();
+        let hole = HolRef::Vertex(self.vertex);
+        for (face, _) in dcel.faces.iter_mut() {
+            face.holes.retain(|h| *h != hole);
         }
+        Ok(Mvh { vertex: weight })
     }
 }
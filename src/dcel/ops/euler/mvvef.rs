@@ -1,29 +1,76 @@
-use std::convert::Infallible;
-
 use crate::{
     arena::Key,
-    coord::Coordinate,
+    coord::{Coordinate, Scalar},
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, Keyed, Traverser, Vertex,
-        VertexKey, VertexPtrs,
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, HolRef, Keyed, Traverser,
+        Vertex, VertexKey, VertexPtrs,
         flavor::Flavor,
         linker::Linker,
         ops::{Operator, OperatorErr},
     },
 };
 
+/// the weights of both vertices, of both half edges and of the bounding face
+type MvvefData<F> = (
+    <F as Flavor>::Vertex,
+    <F as Flavor>::Vertex,
+    <F as Flavor>::Edge,
+    <F as Flavor>::Edge,
+    Option<<F as Flavor>::Face>,
+);
+
+/// Make a new component of two Vertices and an Edge. The first component also makes the bounding
+/// Face, every other one is a hole in the face that contains it.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Mvvef<F: Flavor> {
-    pub data: (F::Vertex, F::Vertex, F::Edge, F::Edge, F::Face),
+    /// the face weight is only used when this makes the bounding face
+    pub data: MvvefData<F>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MvvefError {
+    #[error("there is no bounding face, and no face data to make one")]
+    MissingFace,
+    #[error("vertex coordinate is not finite")]
+    NonFiniteCoordinate,
+    #[error("both vertices are at the same position")]
+    ZeroLengthEdge,
 }
 
-impl<F: Flavor> Operator<F> for Mvvef<F> {
-    type Error = Infallible;
+impl<F: Flavor> Operator<F> for Mvvef<F>
+where
+    F::Vertex: Coordinate,
+{
+    type Error = MvvefError;
     type Inverse = Kvvef;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        if dcel.bounding_face.is_none() && self.data.4.is_none() {
+            return Err(MvvefError::MissingFace);
+        }
+        let [p1, p2] = [self.data.0.xy(), self.data.1.xy()];
+        if !p1.iter().chain(&p2).all(|c| c.is_finite()) {
+            return Err(MvvefError::NonFiniteCoordinate);
+        }
+        if p1 == p2 {
+            return Err(MvvefError::ZeroLengthEdge);
+        }
         Ok(())
     }
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = self.check(dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
+        let enclosing = dcel.face_containing(self.data.0.xy());
+
         let outgoing = dcel.edges.reserve();
         let incoming = dcel.edges.reserve();
 
@@ -40,14 +87,31 @@ impl<F: Flavor> Operator<F> for Mvvef<F> {
             weight: self.data.1,
         });
 
-        let face = dcel.faces.insert(Face {
-            inner: FacePtrs {
-                edge: outgoing,
-                holes: vec![],
-                mask: FaceMask::default(),
-            },
-            weight: self.data.4,
-        });
+        let face = match enclosing {
+            Some(face) => {
+                dcel.face_mut(face).holes.push(HolRef::Edge(outgoing));
+                face
+            }
+            None => {
+                // every vertex left floating so far is a hole of the new bounding face
+                let holes = dcel
+                    .vertices
+                    .iter()
+                    .filter(|(vertex, _)| vertex.edge.is_none())
+                    .map(|(_, key)| HolRef::Vertex(key))
+                    .collect();
+                let face = dcel.faces.insert(Face {
+                    inner: FacePtrs {
+                        edge: outgoing,
+                        holes,
+                        mask: FaceMask::IS_OUTER,
+                    },
+                    weight: self.data.4.unwrap(),
+                });
+                dcel.bounding_face = Some(face);
+                face
+            }
+        };
 
         dcel.edges.set(
             outgoing,
@@ -77,40 +141,78 @@ impl<F: Flavor> Operator<F> for Mvvef<F> {
             },
         );
 
-        dcel.bounding_face.get_or_insert(face);
-
         Ok(Kvvef {
             edges: [outgoing, incoming],
             vertices: [v1, v2],
-            face,
         })
     }
 }
 
+/// Kill a component of two Vertices and an Edge, and the bounding Face if it was the last component
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kvvef {
     pub vertices: [Key<VertexKey>; 2],
     pub edges: [Key<EdgeKey>; 2],
-    pub face: Key<FaceKey>,
 }
 
-impl<F: Flavor> Operator<F> for Kvvef {
-    type Error = Infallible;
+#[derive(thiserror::Error, Debug)]
+pub enum KvvefError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("edge does not exist")]
+    EdgeDoesNotExist,
+    #[error("edges are not twins between the vertices")]
+    EdgeVertexMismatch,
+    #[error("vertices have other edges")]
+    NotIsolatedComponent,
+}
+
+impl<F: Flavor> Operator<F> for Kvvef
+where
+    F::Vertex: Coordinate,
+{
+    type Error = KvvefError;
     type Inverse = Mvvef<F>;
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        let [v1, v2] = self.vertices;
+        let [e1, e2] = self.edges;
+        for v in self.vertices {
+            dcel.vertices.get(v).ok_or(KvvefError::VertexDoesNotExist)?;
+        }
+        let edge = dcel.edges.get(e1).ok_or(KvvefError::EdgeDoesNotExist)?;
+        let twin = dcel.edges.get(e2).ok_or(KvvefError::EdgeDoesNotExist)?;
+        if edge.twin != e2 || edge.origin != v1 || twin.origin != v2 {
+            return Err(KvvefError::EdgeVertexMismatch);
+        }
+        // the pair must be a lone segment, anything else is still attached to the vertices
+        if edge.next != e2 || twin.next != e1 {
+            return Err(KvvefError::NotIsolatedComponent);
+        }
         Ok(())
     }
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = Operator::<F>::check(&self, dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
         let [v1, v2] = self.vertices;
         let [e1, e2] = self.edges;
 
+        let face = e1.face(dcel);
+        let face = if dcel.is_last_boundary(e1) && dcel.bounding_face == Some(face) {
+            // the remaining isolated vertices are adopted again by the next bounding face
+            dcel.bounding_face = None;
+            Some(dcel.faces.remove(face).unwrap().weight)
+        } else {
+            dcel.retarget_boundaries(&self.edges, None);
+            None
+        };
+
+        let [w2, w1] = [v2, v1].map(|v| dcel.vertices.remove(v).unwrap().weight);
+        let [d2, d1] = [e2, e1].map(|e| dcel.edges.remove(e).unwrap().weight);
         Ok(Mvvef {
-            data: (
-                dcel.vertices.remove(v1).unwrap().weight,
-                dcel.vertices.remove(v2).unwrap().weight,
-                dcel.edges.remove(e1).unwrap().weight,
-                dcel.edges.remove(e2).unwrap().weight,
-                dcel.faces.remove(self.face).unwrap().weight,
-            ),
+            data: (w1, w2, d1, d2, face),
         })
     }
 }
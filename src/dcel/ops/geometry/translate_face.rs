@@ -0,0 +1,502 @@
+use std::collections::HashMap;
+
+use crate::{
+    arena::{ArenaBitMask, Key},
+    coord::{
+        Coordinate, Scalar, UpdateCoordinate, on_segment, polygon_encloses, segments_intersect,
+    },
+    dcel::{
+        Dcel, EdgeKey, FaceKey, HolRef, Traverser, VertexKey,
+        flavor::Flavor,
+        ops::{Operator, OperatorErr},
+    },
+    index::{Aabb, RTree, SpatialIndex, SpatialObject},
+};
+
+/// A 2d affine map in `f64`, `[[a, b, tx], [c, d, ty]]` takes `(x, y)` to `(ax + by + tx, cx + dy + ty)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Affine(pub [[f64; 3]; 2]);
+
+impl Affine {
+    pub const IDENTITY: Self = Self([[1., 0., 0.], [0., 1., 0.]]);
+
+    pub fn translation([tx, ty]: [f64; 2]) -> Self {
+        Self([[1., 0., tx], [0., 1., ty]])
+    }
+
+    /// counterclockwise rotation by `angle` radians around `center`
+    pub fn rotation(center: [f64; 2], angle: f64) -> Self {
+        let [sin, cos] = [angle.sin(), angle.cos()];
+        Self([[cos, -sin, 0.], [sin, cos, 0.]]).around(center)
+    }
+
+    pub fn scale(center: [f64; 2], [sx, sy]: [f64; 2]) -> Self {
+        Self([[sx, 0., 0.], [0., sy, 0.]]).around(center)
+    }
+
+    /// the same map, but with `center` as its origin
+    fn around(self, [cx, cy]: [f64; 2]) -> Self {
+        Self::translation([-cx, -cy])
+            .then(self)
+            .then(Self::translation([cx, cy]))
+    }
+
+    /// this map followed by `other`
+    pub fn then(self, other: Self) -> Self {
+        let [[a, b, tx], [c, d, ty]] = self.0;
+        let [[oa, ob, otx], [oc, od, oty]] = other.0;
+        Self([
+            [oa * a + ob * c, oa * b + ob * d, oa * tx + ob * ty + otx],
+            [oc * a + od * c, oc * b + od * d, oc * tx + od * ty + oty],
+        ])
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let [[a, b, _], [c, d, _]] = self.0;
+        a * d - b * c
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let [[a, b, tx], [c, d, ty]] = self.0;
+        let [ia, ib, ic, id] = [d / det, -b / det, -c / det, a / det];
+        Some(Self([
+            [ia, ib, -(ia * tx + ib * ty)],
+            [ic, id, -(ic * tx + id * ty)],
+        ]))
+    }
+
+    /// map a point, going through `f64` and back, integer grids snap to the nearest point
+    pub fn transform<S: Scalar>(&self, [x, y]: [S; 2]) -> [S; 2] {
+        let [[a, b, tx], [c, d, ty]] = self.0;
+        let [x, y] = [x.to_f64(), y.to_f64()];
+        [a * x + b * y + tx, c * x + d * y + ty].map(S::from_f64)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TransformError {
+    #[error("face does not exist")]
+    FaceDoesNotExist,
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("the bounding face has no region to move")]
+    BoundingFace,
+    #[error("transform is not finite")]
+    NonFiniteTransform,
+    #[error("transform flips or flattens the region")]
+    Degenerate,
+    #[error("would make graph non planar")]
+    WouldMakeNonPlanar {
+        /// edges that would cross or touch another edge, one half edge per pair
+        edges: Vec<Key<EdgeKey>>,
+        /// vertices that would end up on an edge, or in another face than the one they are in
+        vertices: Vec<Key<VertexKey>>,
+    },
+}
+
+/// every vertex that moves with the faces, which is their boundaries and everything nested in them
+fn region<F: Flavor>(
+    dcel: &Dcel<F>,
+    faces: &[Key<FaceKey>],
+) -> Result<ArenaBitMask<VertexKey>, TransformError> {
+    let mut visited = ArenaBitMask::new(&dcel.faces);
+    for face in faces {
+        dcel.faces
+            .get(*face)
+            .ok_or(TransformError::FaceDoesNotExist)?;
+        if dcel.bounding_face == Some(*face) {
+            return Err(TransformError::BoundingFace);
+        }
+        visited.set(*face);
+    }
+
+    let mut moved = ArenaBitMask::new(&dcel.vertices);
+    let mut stack = faces.to_vec();
+    while let Some(face) = stack.pop() {
+        for edge in Traverser::through(dcel, face.edge(dcel)).unwrap() {
+            moved.set(edge.origin(dcel));
+        }
+        for hole in &dcel.face(face).holes {
+            let start = match *hole {
+                HolRef::Vertex(vertex) => {
+                    moved.set(vertex);
+                    continue;
+                }
+                HolRef::Edge(edge) => edge.origin(dcel),
+            };
+            // the whole component of the hole, and the faces inside it
+            let mut vertices = vec![start];
+            moved.set(start);
+            while let Some(vertex) = vertices.pop() {
+                for edge in Traverser::around(dcel, vertex).unwrap() {
+                    let face = edge.face(dcel);
+                    if visited.set(face) {
+                        stack.push(face);
+                    }
+                    let next = edge.twin(dcel).origin(dcel);
+                    if moved.set(next) {
+                        vertices.push(next);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(moved)
+}
+
+/// check that moving the region of `faces` by `affine` keeps the graph planar and every hole in its face
+fn check_region<F: Flavor>(
+    dcel: &Dcel<F>,
+    faces: &[Key<FaceKey>],
+    affine: &Affine,
+) -> Result<ArenaBitMask<VertexKey>, TransformError>
+where
+    F::Vertex: Coordinate,
+{
+    if !affine.0.iter().flatten().all(|c| c.is_finite()) {
+        return Err(TransformError::NonFiniteTransform);
+    }
+    if affine.determinant() <= 0. {
+        return Err(TransformError::Degenerate);
+    }
+    let moved = region(dcel, faces)?;
+    check_moved(dcel, &moved, |_, xy| affine.transform(xy))?;
+    Ok(moved)
+}
+
+/// A segment after the move, found in an r-tree by its box
+struct Placed<S> {
+    segment: usize,
+    envelope: Aabb<S>,
+}
+
+impl<S: Scalar> SpatialObject for Placed<S> {
+    type Scalar = S;
+    fn envelope(&self) -> Aabb<S> {
+        self.envelope
+    }
+    fn distance_2(&self, point: [S; 2]) -> f64 {
+        self.envelope.distance_2(point)
+    }
+}
+
+type Point<F> = [<<F as Flavor>::Vertex as Coordinate>::Scalar; 2];
+
+/// check that moving the vertices in `moved` to where `place` puts them keeps the graph planar and
+/// every hole in its face. edges are only tested against the edges their boxes meet
+fn check_moved<F: Flavor>(
+    dcel: &Dcel<F>,
+    moved: &ArenaBitMask<VertexKey>,
+    place: impl Fn(Key<VertexKey>, Point<F>) -> Point<F>,
+) -> Result<(), TransformError>
+where
+    F::Vertex: Coordinate,
+{
+    let is_moved = |vertex: Key<VertexKey>| moved.is_flipped(vertex);
+    let position = |vertex: Key<VertexKey>| {
+        let xy = vertex.weight(dcel).xy();
+        if is_moved(vertex) {
+            place(vertex, xy)
+        } else {
+            xy
+        }
+    };
+
+    let mut edges = vec![];
+    let mut vertices = vec![];
+
+    // edges, once per pair, and whether they move along
+    let segments: Vec<_> = dcel
+        .edges
+        .iter()
+        .filter(|(edge, key)| *key < edge.twin)
+        .map(|(edge, key)| {
+            let ends = [edge.origin, edge.twin.origin(dcel)];
+            (key, ends, ends.map(position), ends.map(is_moved))
+        })
+        .collect();
+
+    let tree = RTree::bulk_load(
+        segments
+            .iter()
+            .enumerate()
+            .map(|(segment, (_, _, ends, _))| Placed {
+                segment,
+                envelope: Aabb::from_points(*ends).unwrap(),
+            })
+            .collect(),
+    );
+
+    for (i, (key, ends, [a, b], moving)) in segments.iter().enumerate() {
+        if !moving.iter().any(|m| *m) {
+            continue;
+        }
+        let envelope = Aabb::from_points([*a, *b]).unwrap();
+        for placed in tree.locate_in_envelope_intersecting(envelope) {
+            let j = placed.segment;
+            let (other, other_ends, [c, d], other_moving) = &segments[j];
+            // two edges that both move entirely keep their relation, an affine map preserves it
+            let both_rigid = moving.iter().chain(other_moving).all(|m| *m);
+            let is_counted = j > i || !other_moving.iter().any(|m| *m);
+            if i == j || both_rigid || !is_counted {
+                continue;
+            }
+            let shared = ends.iter().find(|v| other_ends.contains(v));
+            let hit = match shared {
+                // sharing a vertex is fine, lying on top of each other is not
+                Some(shared) => {
+                    let [from, to] = if ends[0] == *shared { [a, b] } else { [b, a] };
+                    let far = if other_ends[0] == *shared { d } else { c };
+                    on_segment(*far, *from, *to) || on_segment(*to, *from, *far)
+                }
+                None => segments_intersect(*a, *b, *c, *d),
+            };
+            if hit {
+                edges.push(*key);
+                edges.push(*other);
+            }
+        }
+    }
+
+    // isolated vertices running into edges
+    for (vertex, key) in dcel.vertices.iter() {
+        if vertex.edge.is_some() {
+            continue;
+        }
+        let p = position(key);
+        let hit = tree.locate_all_at_point(p).any(|placed| {
+            let (_, _, [a, b], moving) = &segments[placed.segment];
+            (is_moved(key) != moving[0] || is_moved(key) != moving[1]) && on_segment(p, *a, *b)
+        });
+        if hit {
+            vertices.push(key);
+        }
+    }
+
+    // holes staying inside the outer boundary of their face and outside of each other
+    let cycle = |edge: Key<EdgeKey>| -> Vec<_> {
+        Traverser::through(dcel, edge)
+            .unwrap()
+            .map(|edge| edge.origin(dcel))
+            .collect()
+    };
+    let segments_of = |cycle: &[Key<VertexKey>]| {
+        (0..cycle.len())
+            .map(|i| [cycle[i], cycle[(i + 1) % cycle.len()]].map(position))
+            .collect::<Vec<_>>()
+    };
+    for (face, key) in dcel.faces.iter() {
+        let is_bounding = dcel.bounding_face == Some(key);
+        let boundary = cycle(face.edge);
+
+        let mut items = vec![];
+        if is_bounding {
+            items.push((boundary[0], boundary.clone()));
+        }
+        for hole in &face.holes {
+            match *hole {
+                HolRef::Vertex(vertex) => items.push((vertex, vec![])),
+                HolRef::Edge(edge) => {
+                    let cycle = cycle(edge);
+                    items.push((cycle[0], cycle));
+                }
+            }
+        }
+
+        let boundary_moves = !is_bounding && boundary.iter().any(|v| is_moved(*v));
+        let any_moves = boundary_moves
+            || items
+                .iter()
+                .any(|(v, cycle)| is_moved(*v) || cycle.iter().any(|v| is_moved(*v)));
+        if !any_moves {
+            continue;
+        }
+
+        let boundary = segments_of(&boundary);
+        let holes: Vec<_> = items.iter().map(|(_, cycle)| segments_of(cycle)).collect();
+        for (i, (vertex, _)) in items.iter().enumerate() {
+            let p = position(*vertex);
+            let outside = !is_bounding && !polygon_encloses(boundary.iter().copied(), p);
+            let inside_other = holes
+                .iter()
+                .enumerate()
+                .any(|(j, hole)| i != j && polygon_encloses(hole.iter().copied(), p));
+            if outside || inside_other {
+                vertices.push(*vertex);
+            }
+        }
+    }
+
+    if edges.is_empty() && vertices.is_empty() {
+        Ok(())
+    } else {
+        edges.sort();
+        edges.dedup();
+        vertices.sort();
+        vertices.dedup();
+        Err(TransformError::WouldMakeNonPlanar { edges, vertices })
+    }
+}
+
+/// move the vertices of the region, and remember where they were
+fn apply_region<F: Flavor>(
+    dcel: &mut Dcel<F>,
+    moved: &ArenaBitMask<VertexKey>,
+    affine: &Affine,
+) -> Vec<(Key<VertexKey>, [f64; 3])>
+where
+    F::Vertex: Coordinate + UpdateCoordinate,
+{
+    let mut positions = Vec::with_capacity(moved.count());
+    for (vertex, key) in dcel.vertices.iter_mut() {
+        if moved.is_flipped(key) {
+            let [x, y, z] = vertex.weight.xyz();
+            positions.push((key, [x, y, z].map(Scalar::to_f64)));
+            let [x, y] = affine.transform([x, y]);
+            vertex.weight.set_xyz([x, y, z]);
+        }
+    }
+    positions
+}
+
+macro_rules! region_operator {
+    ($op:ident, |$this:ident| $affine:expr) => {
+        impl $op {
+            pub fn affine(&self) -> Affine {
+                let $this = self;
+                $affine
+            }
+        }
+
+        impl<F: Flavor> Operator<F> for $op
+        where
+            F::Vertex: Coordinate + UpdateCoordinate,
+        {
+            type Error = TransformError;
+            type Inverse = RestoreRegion;
+
+            fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+                check_region(dcel, &self.faces, &self.affine()).map(|_| ())
+            }
+
+            fn apply(
+                self,
+                dcel: &mut Dcel<F>,
+            ) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+                let affine = self.affine();
+                let moved = match check_region(dcel, &self.faces, &affine) {
+                    Ok(moved) => moved,
+                    Err(err) => return Err(OperatorErr { op: self, err }),
+                };
+                let positions = apply_region(dcel, &moved, &affine);
+                Ok(RestoreRegion {
+                    faces: self.faces,
+                    affine,
+                    positions,
+                })
+            }
+        }
+    };
+}
+
+/// Move faces, and everything inside them, by a delta
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TranslateFace {
+    pub faces: Vec<Key<FaceKey>>,
+    pub delta: [f64; 2],
+}
+
+region_operator!(TranslateFace, |op| Affine::translation(op.delta));
+
+/// Rotate faces, and everything inside them, counterclockwise by an angle in radians around a center
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotateFace {
+    pub faces: Vec<Key<FaceKey>>,
+    pub center: [f64; 2],
+    pub angle: f64,
+}
+
+region_operator!(RotateFace, |op| Affine::rotation(op.center, op.angle));
+
+/// Scale faces, and everything inside them, by a factor per axis away from a center
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScaleFace {
+    pub faces: Vec<Key<FaceKey>>,
+    pub center: [f64; 2],
+    pub factor: [f64; 2],
+}
+
+region_operator!(ScaleFace, |op| Affine::scale(op.center, op.factor));
+
+/// Map faces, and everything inside them, through any orientation preserving affine map
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AffineTransform {
+    pub faces: Vec<Key<FaceKey>>,
+    pub affine: Affine,
+}
+
+region_operator!(AffineTransform, |op| op.affine);
+
+/// Put the vertices a move of faces took elsewhere back where they were, the inverse of every
+/// region operator. Mapping the positions through the inverse map would round them a second time
+/// on integer grids, so they are kept instead, and restored exactly for every scalar that converts
+/// to `f64` without rounding
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RestoreRegion {
+    pub faces: Vec<Key<FaceKey>>,
+    /// the map the faces were moved by, applied again to redo the move
+    pub affine: Affine,
+    /// every vertex that was moved, and where it was
+    pub positions: Vec<(Key<VertexKey>, [f64; 3])>,
+}
+
+impl<F: Flavor> Operator<F> for RestoreRegion
+where
+    F::Vertex: Coordinate + UpdateCoordinate,
+{
+    type Error = TransformError;
+    type Inverse = AffineTransform;
+
+    fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        region(dcel, &self.faces)?;
+        let mut moved = ArenaBitMask::new(&dcel.vertices);
+        let mut places = HashMap::with_capacity(self.positions.len());
+        for (vertex, [x, y, z]) in &self.positions {
+            dcel.vertices
+                .get(*vertex)
+                .ok_or(TransformError::VertexDoesNotExist)?;
+            if ![x, y, z].iter().all(|c| c.is_finite()) {
+                return Err(TransformError::NonFiniteTransform);
+            }
+            moved.set(*vertex);
+            places.insert(*vertex, [*x, *y].map(Scalar::from_f64));
+        }
+        check_moved(dcel, &moved, |vertex, _| places[&vertex])
+    }
+
+    fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        if let Err(err) = self.check(dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+        for (vertex, xyz) in &self.positions {
+            dcel.vertex_mut(*vertex)
+                .weight
+                .set_xyz(xyz.map(Scalar::from_f64));
+        }
+        Ok(AffineTransform {
+            faces: self.faces,
+            affine: self.affine,
+        })
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     arena::Key,
-    coord::{Coordinate, UpdateCoordinate},
+    coord::{Coordinate, Scalar, UpdateCoordinate, in_triangle, on_segment, segments_intersect},
     dcel::{
         Dcel, Edge, EdgeKey, EdgePtrs, FaceKey, FacePtrs, Keyed, Traverser, Vertex, VertexKey,
         VertexPtrs,
@@ -10,9 +10,11 @@ use crate::{
     },
 };
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TranslateVertex {
     pub vertex: Key<VertexKey>,
-    pub delta: [f32; 3],
+    pub delta: [f64; 3],
 }
 
 impl TranslateVertex {
@@ -24,8 +26,8 @@ impl TranslateVertex {
     where
         F::Vertex: Coordinate,
     {
-        let [x, y, z] = key.weight(dcel).xyz();
-        let [tx, ty, tz] = absolute.xyz();
+        let [x, y, z] = key.weight(dcel).xyz().map(Scalar::to_f64);
+        let [tx, ty, tz] = absolute.xyz().map(Scalar::to_f64);
         let delta = [tx - x, ty - y, tz - z];
         TranslateVertex { vertex: key, delta }
     }
@@ -33,8 +35,111 @@ impl TranslateVertex {
 
 #[derive(thiserror::Error, Debug)]
 pub enum TranslateVertexError {
+    #[error("vertex does not exist")]
+    VertexDoesNotExist,
+    #[error("translation is not finite")]
+    NonFiniteDelta,
     #[error("would make graph non planar")]
-    WouldMakeNonPlanar,
+    WouldMakeNonPlanar {
+        /// edges that the vertex or its edges would run into, one half edge per pair
+        edges: Vec<Key<EdgeKey>>,
+        /// vertices that the vertex or its edges would run into
+        vertices: Vec<Key<VertexKey>>,
+    },
+}
+
+impl TranslateVertex {
+    /// everything the vertex or its edges would hit on the way from where it is to where it is
+    /// moved. moving along a line keeps the graph planar, and every face and hole intact, exactly
+    /// when nothing is hit
+    fn collisions<F: Flavor>(&self, dcel: &Dcel<F>) -> (Vec<Key<EdgeKey>>, Vec<Key<VertexKey>>)
+    where
+        F::Vertex: Coordinate,
+    {
+        let from = self.vertex.weight(dcel).xy();
+        let to = [0, 1].map(|i| Scalar::from_f64(from[i].to_f64() + self.delta[i]));
+        let mut edges = vec![];
+        let mut vertices = vec![];
+        if from == to {
+            return (edges, vertices);
+        }
+
+        let neighbours: Vec<_> = match self.vertex.edge(dcel) {
+            Some(_) => Traverser::around(dcel, self.vertex)
+                .unwrap()
+                .map(|edge| edge.twin(dcel).origin(dcel))
+                .collect(),
+            None => vec![],
+        };
+
+        for (edge, key) in dcel.edges.iter() {
+            let twin_origin = edge.twin.origin(dcel);
+            if key > edge.twin || edge.origin == self.vertex || twin_origin == self.vertex {
+                continue;
+            }
+            let [a, b] = [edge.origin, twin_origin].map(|v| v.weight(dcel).xy());
+
+            let hit = segments_intersect(from, to, a, b)
+                || neighbours.iter().any(|w| {
+                    let w_xy = w.weight(dcel).xy();
+                    if *w == edge.origin || *w == twin_origin {
+                        // sharing the neighbour is fine, lying on top of each other is not
+                        let other = if *w == edge.origin { b } else { a };
+                        on_segment(other, to, w_xy) || on_segment(to, w_xy, other)
+                    } else {
+                        segments_intersect(to, w_xy, a, b)
+                    }
+                });
+            if hit {
+                edges.push(key);
+            }
+        }
+
+        for (vertex, key) in dcel.vertices.iter() {
+            if key == self.vertex {
+                continue;
+            }
+            let p = vertex.weight.xy();
+            let hit = on_segment(p, from, to)
+                || neighbours
+                    .iter()
+                    .any(|w| *w != key && in_triangle(p, from, to, w.weight(dcel).xy()));
+            if hit {
+                vertices.push(key);
+            }
+        }
+
+        (edges, vertices)
+    }
+
+    /// shorten the move so it stops just short of the first thing it would hit
+    pub fn clamp<F: Flavor>(self, dcel: &Dcel<F>) -> Self
+    where
+        F::Vertex: Coordinate,
+    {
+        let moved = |t: f64| TranslateVertex {
+            vertex: self.vertex,
+            delta: self.delta.map(|d| d * t),
+        };
+        let is_clear = |op: &TranslateVertex| {
+            let (edges, vertices) = op.collisions(dcel);
+            edges.is_empty() && vertices.is_empty()
+        };
+        if dcel.vertices.get(self.vertex).is_none() || is_clear(&self) {
+            return self;
+        }
+
+        let [mut lo, mut hi] = [0., 1.];
+        for _ in 0..24 {
+            let mid = (lo + hi) / 2.;
+            if is_clear(&moved(mid)) {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        moved(lo)
+    }
 }
 
 impl<F: Flavor> Operator<F> for TranslateVertex
@@ -45,14 +150,28 @@ where
     type Inverse = TranslateVertex;
 
     fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        dcel.vertices
+            .get(self.vertex)
+            .ok_or(TranslateVertexError::VertexDoesNotExist)?;
+        if !self.delta.iter().all(|d| d.is_finite()) {
+            return Err(TranslateVertexError::NonFiniteDelta);
+        }
+        let (edges, vertices) = self.collisions(dcel);
+        if !edges.is_empty() || !vertices.is_empty() {
+            return Err(TranslateVertexError::WouldMakeNonPlanar { edges, vertices });
+        }
         Ok(())
     }
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
-        let [x, y, z] = self.vertex.weight(dcel).xyz();
-        let [dx, dy, dz] = self.delta.xyz();
+        if let Err(err) = self.check(dcel) {
+            return Err(OperatorErr { op: self, err });
+        }
+
+        let [x, y, z] = self.vertex.weight(dcel).xyz().map(Scalar::to_f64);
+        let [dx, dy, dz] = self.delta;
         dcel.vertex_mut(self.vertex)
             .weight
-            .set_xyz([x + dx, y + dy, z + dz]);
+            .set_xyz([x + dx, y + dy, z + dz].map(Scalar::from_f64));
         Ok(TranslateVertex {
             vertex: self.vertex,
             delta: [-dx, -dy, -dz],
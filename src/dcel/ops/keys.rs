@@ -0,0 +1,167 @@
+//! Visiting every key an operator refers to, so they can be read or rewritten
+
+use crate::{
+    arena::Key,
+    dcel::{
+        Dcel, EdgeKey, FaceKey, Flavor, VertexKey,
+        ops::{
+            AffineTransform, Kef, Kemh, Kev, Kve, Kvh, Kvvef, Mef, Mekh, Mev, Mve, Mvh, Mvvef,
+            RestoreRegion, RotateFace, ScaleFace, TranslateFace, TranslateVertex,
+        },
+    },
+};
+
+/// A key to any of the arenas of a dcel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnyKey {
+    Vertex(Key<VertexKey>),
+    Edge(Key<EdgeKey>),
+    Face(Key<FaceKey>),
+}
+
+macro_rules! any_key {
+    ($( $variant:ident($kind:ty) ),*) => {
+        $(
+            impl From<Key<$kind>> for AnyKey {
+                fn from(key: Key<$kind>) -> Self {
+                    Self::$variant(key)
+                }
+            }
+
+            impl TryFrom<AnyKey> for Key<$kind> {
+                type Error = AnyKey;
+
+                fn try_from(key: AnyKey) -> Result<Self, AnyKey> {
+                    match key {
+                        AnyKey::$variant(key) => Ok(key),
+                        key => Err(key),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+any_key!(Vertex(VertexKey), Edge(EdgeKey), Face(FaceKey));
+
+impl AnyKey {
+    pub fn exists<F: Flavor>(self, dcel: &Dcel<F>) -> bool {
+        match self {
+            Self::Vertex(key) => dcel.vertices.get(key).is_some(),
+            Self::Edge(key) => dcel.edges.get(key).is_some(),
+            Self::Face(key) => dcel.faces.get(key).is_some(),
+        }
+    }
+}
+
+pub trait KeyVisitor {
+    fn vertex(&mut self, key: &mut Key<VertexKey>);
+    fn edge(&mut self, key: &mut Key<EdgeKey>);
+    fn face(&mut self, key: &mut Key<FaceKey>);
+}
+
+/// Every key an operator refers to, always in the same order for the same kind of operator.
+/// Keys in fixed size fields come before keys in lists, so two operators of the same kind line
+/// up key by key as far as their fixed fields go.
+pub trait VisitKeys {
+    fn visit_keys(&mut self, visitor: &mut impl KeyVisitor);
+
+    /// every key, in visiting order
+    fn keys(&mut self) -> Vec<AnyKey> {
+        struct Collect(Vec<AnyKey>);
+        impl KeyVisitor for Collect {
+            fn vertex(&mut self, key: &mut Key<VertexKey>) {
+                self.0.push((*key).into());
+            }
+            fn edge(&mut self, key: &mut Key<EdgeKey>) {
+                self.0.push((*key).into());
+            }
+            fn face(&mut self, key: &mut Key<FaceKey>) {
+                self.0.push((*key).into());
+            }
+        }
+
+        let mut collect = Collect(vec![]);
+        self.visit_keys(&mut collect);
+        collect.0
+    }
+}
+
+/// a single key, an array of keys or a list of them
+trait Slots<K> {
+    fn slots(&mut self) -> &mut [Key<K>];
+}
+
+impl<K> Slots<K> for Key<K> {
+    fn slots(&mut self) -> &mut [Key<K>] {
+        std::slice::from_mut(self)
+    }
+}
+
+impl<K, const N: usize> Slots<K> for [Key<K>; N] {
+    fn slots(&mut self) -> &mut [Key<K>] {
+        self
+    }
+}
+
+impl<K> Slots<K> for Vec<Key<K>> {
+    fn slots(&mut self) -> &mut [Key<K>] {
+        self
+    }
+}
+
+macro_rules! visit_keys {
+    ($op:ty $( where $f:ident )? { $( $kind:ident: $field:ident ),* $(,)? }) => {
+        impl$(<$f: Flavor>)? VisitKeys for $op {
+            fn visit_keys(&mut self, _visitor: &mut impl KeyVisitor) {
+                $(
+                    for key in self.$field.slots() {
+                        _visitor.$kind(key);
+                    }
+                )*
+            }
+        }
+    };
+}
+
+visit_keys!(Mef<F> where F { vertex: vertices });
+visit_keys!(Kef {
+    face: face,
+    edge: edges
+});
+visit_keys!(Mekh<F> where F { vertex: vertices });
+visit_keys!(Kemh { edge: edges });
+visit_keys!(Mev<F> where F { edge: edge });
+visit_keys!(Kev {
+    vertex: vertex,
+    edge: edges
+});
+visit_keys!(Mvvef<F> where F {});
+visit_keys!(Kvvef {
+    vertex: vertices,
+    edge: edges
+});
+visit_keys!(Mvh<F> where F {});
+visit_keys!(Kvh { vertex: vertex });
+visit_keys!(Mve<F> where F { vertex: origin, edge: reparent });
+visit_keys!(Kve {
+    vertex: origin,
+    vertex: vertex,
+    edge: edges
+});
+visit_keys!(TranslateVertex { vertex: vertex });
+visit_keys!(TranslateFace { face: faces });
+visit_keys!(RotateFace { face: faces });
+visit_keys!(ScaleFace { face: faces });
+visit_keys!(AffineTransform { face: faces });
+
+impl VisitKeys for RestoreRegion {
+    fn visit_keys(&mut self, visitor: &mut impl KeyVisitor) {
+        for face in &mut self.faces {
+            visitor.face(face);
+        }
+        for (vertex, _) in &mut self.positions {
+            visitor.vertex(vertex);
+        }
+    }
+}
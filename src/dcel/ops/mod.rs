@@ -4,18 +4,22 @@ use crate::{
     arena::Key,
     coord::{Coordinate, FromCoordinate, UpdateCoordinate},
     dcel::{
-        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FacePtrs, Keyed, Vertex, VertexKey,
-        VertexPtrs, flavor::Flavor,
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FacePtrs, Keyed, RollbackErr, Vertex,
+        VertexKey, VertexPtrs, flavor::Flavor,
     },
 };
 
 mod combo;
+mod crdt;
 mod euler;
 mod geometry;
+mod keys;
 
 pub use combo::*;
+pub use crdt::*;
 pub use euler::*;
 pub use geometry::*;
+pub use keys::*;
 
 pub trait DataGenerator<F: Flavor> {
     fn make_vertex(self, dcel: &Dcel<F>) -> F::Vertex;
@@ -78,6 +82,45 @@ pub trait Operator<F: Flavor>: Sized {
     fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>>;
 }
 
+/// clone for operators that carry weights, which only needs the weights to be clone
+macro_rules! clone_with_weights {
+    ($( $op:ident { $( $field:ident ),* } ),* $(,)?) => {
+        $(
+            impl<F: Flavor> Clone for $op<F>
+            where
+                F::Vertex: Clone,
+                F::Edge: Clone,
+                F::Face: Clone,
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        $( $field: self.$field.clone(), )*
+                    }
+                }
+            }
+        )*
+    };
+}
+
+clone_with_weights!(
+    Mef { vertices, data },
+    Mekh { vertices, data },
+    Mev { edge, vertex, data },
+    Mvvef { data },
+    Mvh { vertex },
+    Mve {
+        origin,
+        edges,
+        vertex,
+        reparent
+    },
+);
+
+/// An operator that is one of the variants of [`Op`]
+pub trait OpVariant<F: Flavor>: Into<Op<F>> {
+    fn variant(op: &Op<F>) -> Option<&Self>;
+}
+
 macro_rules! op_group {
     (
         $vis:vis enum $name:ident<F: Flavor> {
@@ -86,14 +129,60 @@ macro_rules! op_group {
         }
 
     ) => {
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(bound(
+                serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+                deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+            ))
+        )]
         $vis enum $name<F: Flavor> {
             $( $variant($op), )*
         }
 
+        impl<F: Flavor> Clone for $name<F>
+        where
+            F::Vertex: Clone,
+            F::Edge: Clone,
+            F::Face: Clone,
+        {
+            fn clone(&self) -> Self {
+                match self {
+                    $( Self::$variant(op) => Self::$variant(op.clone()), )*
+                }
+            }
+        }
+
+        impl<F: Flavor> VisitKeys for $name<F> {
+            fn visit_keys(&mut self, visitor: &mut impl KeyVisitor) {
+                match self {
+                    $( Self::$variant(op) => op.visit_keys(visitor), )*
+                }
+            }
+        }
+
         $(
-            impl<F: Flavor> Into<$name<F>> for $op {
-                fn into(self) -> $name<F> {
-                    $name::$variant(self)
+            impl<F: Flavor> From<$op> for $name<F> {
+                fn from(op: $op) -> $name<F> {
+                    $name::$variant(op)
+                }
+            }
+
+            impl<F: Flavor> OpVariant<F> for $op {
+                fn variant(op: &$name<F>) -> Option<&Self> {
+                    match op {
+                        $name::$variant(op) => Some(op),
+                        _ => None,
+                    }
+                }
+            }
+
+            impl<F: Flavor> From<OperatorErr<$op, <$op as Operator<F>>::Error>> for Error<F>
+            where F::Vertex: UpdateCoordinate + Coordinate
+            {
+                fn from(err: OperatorErr<$op, <$op as Operator<F>>::Error>) -> Self {
+                    Error::$variant(err.err)
                 }
             }
         )*
@@ -104,6 +193,8 @@ macro_rules! op_group {
             $(
                 $variant(<$op as Operator<F>>::Error),
             )*
+            /// undoing a failed group failed as well
+            Rollback(Box<RollbackErr<F>>),
         }
 
         impl<F: Flavor> Operator<F> for $name<F>
@@ -137,7 +228,7 @@ macro_rules! op_group {
 op_group!(
     pub enum Op<F: Flavor> {
         Mef(Mef<F>),
-        Mekh(Mekh),
+        Mekh(Mekh<F>),
         Kemh(Kemh),
         Kef(Kef),
         Mev(Mev<F>),
@@ -149,6 +240,11 @@ op_group!(
         Mve(Mve<F>),
         Kve(Kve),
         TranslateVertex(geometry::TranslateVertex),
+        TranslateFace(geometry::TranslateFace),
+        RotateFace(geometry::RotateFace),
+        ScaleFace(geometry::ScaleFace),
+        AffineTransform(geometry::AffineTransform),
+        RestoreRegion(geometry::RestoreRegion),
         // CollapseEdge(combo::CollapseEdge),
         // UncollapseEdge(combo::UncollapseEdge<F>),
         // WeldVertex(combo::WeldVertex<F>),
@@ -157,45 +253,3 @@ op_group!(
         // UnlinkVertices(combo::UnlinkVertices),
     }
 );
-
-pub struct CrdtDcel<F: Flavor> {
-    version: usize,
-    dcel: Dcel<F>,
-    history: Vec<Op<F>>,
-}
-
-impl<F: Flavor> CrdtDcel<F>
-where
-    F::Vertex: Coordinate,
-{
-    pub fn new(dcel: Dcel<F>) -> Self {
-        Self {
-            version: 0,
-            dcel,
-            history: vec![],
-        }
-    }
-
-    pub fn apply(&mut self, command: Command<F>) -> Result<(), OperatorErr<Op<F>, Error<F>>>
-    where
-        F::Vertex: UpdateCoordinate + Coordinate,
-    {
-        match command.op.check(&self.dcel) {
-            Ok(input) => {
-                let inverse = command.op.apply(&mut self.dcel)?;
-                self.history.push(inverse);
-                self.version += 1;
-                Ok(())
-            }
-            Err(err) => Err(OperatorErr {
-                op: command.op,
-                err,
-            }),
-        }
-    }
-}
-
-pub struct Command<F: Flavor> {
-    version: usize,
-    op: Op<F>,
-}
@@ -0,0 +1,172 @@
+//! Overlaying two dcels, and the boolean operations on the faces of the overlay
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    arena::{Key, SecondaryMap},
+    coord::{Coordinate, FromCoordinate, UpdateCoordinate},
+    dcel::{Dcel, EdgeKey, FaceKey, Flavor, HolRef, SegmentError, Traverser},
+};
+
+/// How the faces of two sets combine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BooleanOp {
+    Union,
+    Intersection,
+    /// in the first and not in the second
+    Difference,
+    SymmetricDifference,
+}
+
+impl BooleanOp {
+    /// whether a face is kept, given whether it is in the first and in the second set
+    pub fn keeps(self, first: bool, second: bool) -> bool {
+        match self {
+            Self::Union => first || second,
+            Self::Intersection => first && second,
+            Self::Difference => first && !second,
+            Self::SymmetricDifference => first != second,
+        }
+    }
+}
+
+/// The overlay of two dcels, knowing for each of its faces the faces of both it lies in
+pub struct Overlay<F: Flavor> {
+    pub dcel: Dcel<F>,
+    /// the bounded face of the first and of the second dcel every face lies in, none outside all
+    /// of them
+    pub sources: SecondaryMap<FaceKey, [Option<Key<FaceKey>>; 2]>,
+}
+
+impl<F: Flavor> Overlay<F> {
+    /// the bounded faces of both dcels a face lies in
+    pub fn sources(&self, face: Key<FaceKey>) -> [Option<Key<FaceKey>>; 2] {
+        self.sources
+            .get(&self.dcel.faces, face)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// the faces op keeps, given which faces of the first and of the second dcel are in the sets
+    pub fn select(
+        &self,
+        op: BooleanOp,
+        first: impl Fn(Key<FaceKey>) -> bool,
+        second: impl Fn(Key<FaceKey>) -> bool,
+    ) -> Vec<Key<FaceKey>> {
+        self.dcel
+            .faces
+            .keys()
+            .filter(|face| {
+                let [a, b] = self.sources(*face);
+                op.keeps(a.is_some_and(&first), b.is_some_and(&second))
+            })
+            .collect()
+    }
+
+    /// the faces inside a bounded face of either dcel
+    pub fn union(&self) -> Vec<Key<FaceKey>> {
+        self.select(BooleanOp::Union, |_| true, |_| true)
+    }
+
+    /// the faces inside a bounded face of both dcels
+    pub fn intersection(&self) -> Vec<Key<FaceKey>> {
+        self.select(BooleanOp::Intersection, |_| true, |_| true)
+    }
+
+    /// the faces inside a bounded face of the first dcel only
+    pub fn difference(&self) -> Vec<Key<FaceKey>> {
+        self.select(BooleanOp::Difference, |_| true, |_| true)
+    }
+
+    /// the faces inside a bounded face of exactly one of the dcels
+    pub fn symmetric_difference(&self) -> Vec<Key<FaceKey>> {
+        self.select(BooleanOp::SymmetricDifference, |_| true, |_| true)
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    /// overlay this dcel and other, cutting both wherever their edges cross, touch or overlap in
+    /// one sweep. merge makes the weight of every face of the overlay from the weights of the
+    /// bounded faces of both it lies in, none outside all of them. vertices and edges of the
+    /// overlay are made anew from their coordinates
+    pub fn overlay(
+        &self,
+        other: &Self,
+        mut merge: impl FnMut(Option<&F::Face>, Option<&F::Face>) -> F::Face,
+    ) -> Result<Overlay<F>, SegmentError<F>> {
+        let inputs = [self, other];
+        // every edge once by one of its halves, and isolated vertices as segments of no length
+        let mut halves = vec![];
+        let mut segments = vec![];
+        for (input, dcel) in inputs.iter().enumerate() {
+            for (edge, key) in dcel.edges.iter() {
+                if key.get() > edge.twin.get() {
+                    continue;
+                }
+                halves.push(Some((input, key)));
+                segments.push(
+                    [edge.origin, edge.twin.origin(dcel)].map(|v| dcel.vertex(v).weight.xy()),
+                );
+            }
+            for (vertex, _) in dcel.vertices.iter().filter(|(v, _)| v.edge.is_none()) {
+                halves.push(None);
+                segments.push([vertex.weight.xy(); 2]);
+            }
+        }
+        let (mut dcel, along) = Self::from_segments_traced(segments)?;
+
+        // the faces of both right of the half edges running along their edges
+        let mut right: HashMap<Key<EdgeKey>, [Option<Key<FaceKey>>; 2]> = HashMap::new();
+        for (half, pieces) in halves.into_iter().zip(&along) {
+            let Some((input, half)) = half else { continue };
+            let source = inputs[input];
+            for piece in pieces {
+                right.entry(*piece).or_default()[input] = Some(half.face(source));
+                right.entry(piece.twin(&dcel)).or_default()[input] =
+                    Some(half.twin(source).face(source));
+            }
+        }
+
+        // crossing an edge that isn't one of a dcel stays in the same face of it
+        let mut sources = SecondaryMap::new();
+        let Some(bounding) = dcel.bounding_face else {
+            return Ok(Overlay { dcel, sources });
+        };
+        let mut labels = HashMap::from([(bounding, inputs.map(|input| input.bounding_face))]);
+        let mut queue = VecDeque::from([bounding]);
+        while let Some(face) = queue.pop_front() {
+            let label = labels[&face];
+            let holes = dcel.face(face).holes.iter().filter_map(|hole| match hole {
+                HolRef::Edge(edge) => Some(*edge),
+                HolRef::Vertex(_) => None,
+            });
+            for start in std::iter::once(dcel.face(face).edge).chain(holes) {
+                for half in Traverser::through(&dcel, start).unwrap() {
+                    let twin = half.twin(&dcel);
+                    let beyond = twin.face(&dcel);
+                    if labels.contains_key(&beyond) {
+                        continue;
+                    }
+                    let known = right.get(&twin).copied().unwrap_or_default();
+                    labels.insert(beyond, [0, 1].map(|i| known[i].or(label[i])));
+                    queue.push_back(beyond);
+                }
+            }
+        }
+
+        for (face, label) in labels {
+            let label: [Option<Key<FaceKey>>; 2] =
+                [0, 1].map(|i| label[i].filter(|source| Some(*source) != inputs[i].bounding_face));
+            let [a, b] = [0, 1].map(|i| label[i].map(|source| &inputs[i].face(source).weight));
+            dcel.face_mut(face).weight = merge(a, b);
+            sources.insert(&dcel.faces, face, label);
+        }
+        Ok(Overlay { dcel, sources })
+    }
+}
@@ -0,0 +1,401 @@
+//! Building a dcel from indexed polygons, and writing it back out as them
+
+use std::collections::HashMap;
+
+use crate::{
+    arena::Key,
+    coord::{Coordinate, FromCoordinate, Orientation, Scalar, polygon_encloses, sort_clockwise},
+    dcel::{
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, Flavor, HolRef,
+        Traverser, Vertex, VertexKey, VertexPtrs,
+    },
+    util::ShoeString,
+};
+
+type Point<F> = [<<F as Flavor>::Vertex as Coordinate>::Scalar; 2];
+
+/// vertices, and polygons as indices into them
+type Indexed<F> = (Vec<Point<F>>, Vec<Vec<u32>>);
+
+/// What is wrong with indexed polygons, by the indices of the polygons and vertices at fault
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum PolygonError {
+    #[error("vertex {vertex} has a coordinate that is not finite")]
+    NonFiniteCoordinate { vertex: usize },
+    #[error("polygon {polygon} has fewer than three vertices")]
+    TooFewVertices { polygon: usize },
+    #[error("polygon {polygon} refers to vertex {vertex}, which does not exist")]
+    VertexOutOfRange { polygon: usize, vertex: u32 },
+    #[error("polygon {polygon} passes vertex {vertex} more than once")]
+    RepeatedVertex { polygon: usize, vertex: u32 },
+    #[error("polygon {polygon} encloses no area")]
+    ZeroArea { polygon: usize },
+    #[error("polygon {polygon} winds the other way than polygon 0")]
+    InconsistentOrientation { polygon: usize },
+    #[error(
+        "polygons {} and {} both run from vertex {} to vertex {}",
+        polygons[0], polygons[1], edge[0], edge[1]
+    )]
+    NonManifoldEdge {
+        edge: [u32; 2],
+        polygons: [usize; 2],
+    },
+    #[error("face {polygon} has a hole, which an indexed polygon can't hold")]
+    FaceWithHole { polygon: usize },
+}
+
+/// a half edge by the index of its origin, along a polygon or around the outside of all of them
+struct Half {
+    origin: u32,
+    twin: usize,
+    next: usize,
+    polygon: Option<usize>,
+}
+
+/// the half edges of a cycle, starting at start
+fn cycle(halves: &[Half], start: usize) -> impl Iterator<Item = usize> + '_ {
+    std::iter::successors(Some(start), move |half| {
+        Some(halves[*half].next).filter(|next| *next != start)
+    })
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    /// build a dcel from polygons given as indices into vertices, all winding the same way. sides
+    /// are matched to their twins by their pair of vertices in one hash, so this is O(n) besides
+    /// placing what lies inside the gaps between the polygons. the faces of the polygons are made
+    /// in order, followed by a face for every gap the polygons enclose and the bounding face.
+    /// vertices no polygon uses are isolated vertices. polygons are taken not to overlap
+    pub fn from_polygons(
+        vertices: &[Point<F>],
+        polygons: &[impl AsRef<[u32]>],
+    ) -> Result<Self, PolygonError> {
+        if let Some(vertex) = vertices
+            .iter()
+            .position(|p| !p.iter().all(|c| c.is_finite()))
+        {
+            return Err(PolygonError::NonFiniteCoordinate { vertex });
+        }
+        let point = |vertex: u32| vertices[vertex as usize];
+
+        // check every polygon, and turn them clockwise like the faces of the dcel
+        let mut used = vec![usize::MAX; vertices.len()];
+        let mut counterclockwise = None;
+        let mut rings = Vec::with_capacity(polygons.len());
+        let mut areas = Vec::with_capacity(polygons.len());
+        for (polygon, indices) in polygons.iter().enumerate() {
+            let indices = indices.as_ref();
+            if indices.len() < 3 {
+                return Err(PolygonError::TooFewVertices { polygon });
+            }
+            for &vertex in indices {
+                let Some(user) = used.get_mut(vertex as usize) else {
+                    return Err(PolygonError::VertexOutOfRange { polygon, vertex });
+                };
+                if *user == polygon {
+                    return Err(PolygonError::RepeatedVertex { polygon, vertex });
+                }
+                *user = polygon;
+            }
+            let mut area = ShoeString::default();
+            for (i, vertex) in indices.iter().enumerate() {
+                area.add(&point(*vertex), &point(indices[(i + 1) % indices.len()]));
+            }
+            let ccw = match area.orientation() {
+                Orientation::Clockwise(_) => false,
+                Orientation::Counterclockwise(_) => true,
+                Orientation::Neutral => return Err(PolygonError::ZeroArea { polygon }),
+            };
+            if *counterclockwise.get_or_insert(ccw) != ccw {
+                return Err(PolygonError::InconsistentOrientation { polygon });
+            }
+            let mut ring = indices.to_vec();
+            if ccw {
+                ring.reverse();
+            }
+            rings.push(ring);
+            areas.push(area.area().abs());
+        }
+        let flipped = counterclockwise.unwrap_or(false);
+
+        let mut halves: Vec<Half> =
+            Vec::with_capacity(rings.iter().map(Vec::len).sum::<usize>() * 2);
+        let mut half_of: HashMap<[u32; 2], usize> = HashMap::with_capacity(halves.capacity() / 2);
+        for (polygon, ring) in rings.iter().enumerate() {
+            let first = halves.len();
+            for (i, &origin) in ring.iter().enumerate() {
+                let edge = [origin, ring[(i + 1) % ring.len()]];
+                if let Some(&other) = half_of.get(&edge) {
+                    let [from, to] = edge;
+                    return Err(PolygonError::NonManifoldEdge {
+                        edge: if flipped { [to, from] } else { edge },
+                        polygons: [halves[other].polygon.unwrap(), polygon],
+                    });
+                }
+                half_of.insert(edge, halves.len());
+                halves.push(Half {
+                    origin,
+                    twin: usize::MAX,
+                    next: first + (i + 1) % ring.len(),
+                    polygon: Some(polygon),
+                });
+            }
+        }
+
+        // sides with a polygon on one side only are run the other way around the outside
+        let inner = halves.len();
+        for half in 0..inner {
+            if halves[half].twin != usize::MAX {
+                continue;
+            }
+            let [from, to] = [halves[half].origin, halves[halves[half].next].origin];
+            let twin = match half_of.get(&[to, from]) {
+                Some(&twin) => twin,
+                None => {
+                    halves.push(Half {
+                        origin: to,
+                        twin: half,
+                        next: usize::MAX,
+                        polygon: None,
+                    });
+                    halves.len() - 1
+                }
+            };
+            halves[half].twin = twin;
+            halves[twin].twin = half;
+        }
+
+        let mut leaving = vec![vec![]; vertices.len()];
+        for outer in inner..halves.len() {
+            leaving[halves[outer].origin as usize].push(outer);
+        }
+        // where the outside passes a vertex more than once, polygons meet in a single point, and
+        // the next side is the one turning right the most
+        let mut around: HashMap<u32, Vec<usize>> = (0..vertices.len() as u32)
+            .filter(|vertex| leaving[*vertex as usize].len() > 1)
+            .map(|vertex| (vertex, vec![]))
+            .collect();
+        if !around.is_empty() {
+            for (half, Half { origin, .. }) in halves.iter().enumerate() {
+                if let Some(leaving) = around.get_mut(origin) {
+                    leaving.push(half);
+                }
+            }
+            for (vertex, leaving) in &mut around {
+                leaving.sort_by(|a, b| {
+                    let [a, b] = [a, b].map(|half| point(halves[halves[*half].twin].origin));
+                    sort_clockwise(point(*vertex), a, b)
+                });
+            }
+        }
+        for outer in inner..halves.len() {
+            let twin = halves[outer].twin;
+            let to = halves[twin].origin;
+            halves[outer].next = match leaving[to as usize].as_slice() {
+                [next] => *next,
+                _ => {
+                    let leaving = &around[&to];
+                    let position = leaving.iter().position(|half| *half == twin).unwrap();
+                    leaving[(position + leaving.len() - 1) % leaving.len()]
+                }
+            };
+        }
+
+        // the outside splits into a boundary around every component, and a gap face for every
+        // clockwise cycle the polygons enclose
+        let mut gaps = vec![];
+        let mut components = vec![];
+        let mut traced = vec![false; halves.len()];
+        for start in inner..halves.len() {
+            if traced[start] {
+                continue;
+            }
+            let mut area = ShoeString::default();
+            for half in cycle(&halves, start) {
+                traced[half] = true;
+                let twin = halves[half].twin;
+                area.add(&point(halves[half].origin), &point(halves[twin].origin));
+            }
+            match area.orientation().is_cw() {
+                true => gaps.push((start, -area.area())),
+                false => components.push(start),
+            }
+        }
+
+        let segments = |start: usize| {
+            cycle(&halves, start).map(|half| {
+                let twin = halves[half].twin;
+                [halves[half].origin, halves[twin].origin].map(point)
+            })
+        };
+        let polygon_faces = rings.len();
+        let bounding = polygon_faces + gaps.len();
+        // the innermost gap around a point, or the bounding face
+        let gap_around = |p: Point<F>| {
+            gaps.iter()
+                .enumerate()
+                .filter(|(_, (start, _))| polygon_encloses(segments(*start), p))
+                .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+                .map_or(bounding, |(gap, _)| polygon_faces + gap)
+        };
+        let component_faces: Vec<usize> = components
+            .iter()
+            .map(|start| match gaps.is_empty() {
+                true => bounding,
+                false => gap_around(point(halves[*start].origin)),
+            })
+            .collect();
+        let isolated: Vec<(u32, usize)> = (0..vertices.len() as u32)
+            .filter(|vertex| used[*vertex as usize] == usize::MAX && !rings.is_empty())
+            .map(|vertex| {
+                let p = point(vertex);
+                let polygon = (0..rings.len())
+                    .filter(|polygon| {
+                        let ring = &rings[*polygon];
+                        let sides = (0..ring.len())
+                            .map(|i| [ring[i], ring[(i + 1) % ring.len()]].map(point));
+                        polygon_encloses(sides, p)
+                    })
+                    .min_by(|a, b| areas[*a].total_cmp(&areas[*b]));
+                (vertex, polygon.unwrap_or_else(|| gap_around(p)))
+            })
+            .collect();
+
+        let mut dcel = Self::default();
+        let faces = bounding + usize::from(!rings.is_empty());
+        let vertex_keys: Vec<Key<VertexKey>> =
+            vertices.iter().map(|_| dcel.vertices.reserve()).collect();
+        let edge_keys: Vec<Key<EdgeKey>> = halves.iter().map(|_| dcel.edges.reserve()).collect();
+        let face_keys: Vec<Key<FaceKey>> = (0..faces).map(|_| dcel.faces.reserve()).collect();
+
+        let mut boundaries: Vec<Option<Key<EdgeKey>>> = vec![None; faces];
+        let mut holes = vec![vec![]; faces];
+        let mut face_of = vec![usize::MAX; halves.len()];
+        let mut first = 0;
+        for (polygon, ring) in rings.iter().enumerate() {
+            boundaries[polygon] = Some(edge_keys[first]);
+            face_of[first..first + ring.len()].fill(polygon);
+            first += ring.len();
+        }
+        for (gap, (start, _)) in gaps.iter().enumerate() {
+            boundaries[polygon_faces + gap] = Some(edge_keys[*start]);
+            for half in cycle(&halves, *start) {
+                face_of[half] = polygon_faces + gap;
+            }
+        }
+        for (start, face) in components.iter().zip(component_faces) {
+            match boundaries[face] {
+                None => boundaries[face] = Some(edge_keys[*start]),
+                Some(_) => holes[face].push(HolRef::Edge(edge_keys[*start])),
+            }
+            for half in cycle(&halves, *start) {
+                face_of[half] = face;
+            }
+        }
+        for (vertex, face) in isolated {
+            holes[face].push(HolRef::Vertex(vertex_keys[vertex as usize]));
+        }
+
+        let mut edges = vec![None; vertices.len()];
+        for (half, Half { origin, .. }) in halves.iter().enumerate() {
+            edges[*origin as usize] = Some(edge_keys[half]);
+        }
+        for (vertex, key) in vertex_keys.iter().enumerate() {
+            dcel.vertices.set(
+                *key,
+                Vertex {
+                    inner: VertexPtrs {
+                        edge: edges[vertex],
+                    },
+                    weight: F::Vertex::from_xy(vertices[vertex]),
+                },
+            );
+        }
+        let mut prev = vec![0; halves.len()];
+        for (half, Half { next, .. }) in halves.iter().enumerate() {
+            prev[*next] = half;
+        }
+        for (
+            half,
+            Half {
+                origin, twin, next, ..
+            },
+        ) in halves.iter().enumerate()
+        {
+            dcel.edges.set(
+                edge_keys[half],
+                Edge {
+                    inner: EdgePtrs {
+                        origin: vertex_keys[*origin as usize],
+                        twin: edge_keys[*twin],
+                        prev: edge_keys[prev[half]],
+                        next: edge_keys[*next],
+                        face: face_keys[face_of[half]],
+                    },
+                    weight: Default::default(),
+                },
+            );
+        }
+        for (face, holes) in holes.into_iter().enumerate() {
+            dcel.faces.set(
+                face_keys[face],
+                Face {
+                    inner: FacePtrs {
+                        edge: boundaries[face].unwrap(),
+                        holes,
+                        mask: match face == bounding {
+                            true => FaceMask::IS_OUTER,
+                            false => FaceMask::IS_BOUNDARY,
+                        },
+                    },
+                    weight: Default::default(),
+                },
+            );
+        }
+        dcel.bounding_face = face_keys.get(bounding).copied();
+        Ok(dcel)
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: Coordinate,
+{
+    /// the vertices, and the outer boundary of every bounded face as indices into them, clockwise
+    /// like the faces of the dcel. faces come in the order of the arena, so a dcel built by
+    /// [`from_polygons`](Self::from_polygons) gives its polygons back first and the gaps between
+    /// them after. isolated vertices are kept, faces with holes made of edges can't be written
+    pub fn to_polygons(&self) -> Result<Indexed<F>, PolygonError> {
+        let mut index = HashMap::with_capacity(self.vertices.len());
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        for (vertex, key) in self.vertices.iter() {
+            index.insert(key, vertices.len() as u32);
+            vertices.push(vertex.weight.xy());
+        }
+
+        let mut polygons = vec![];
+        for (face, key) in self.faces.iter() {
+            if Some(key) == self.bounding_face {
+                continue;
+            }
+            let polygon = polygons.len();
+            if face
+                .holes
+                .iter()
+                .any(|hole| matches!(hole, HolRef::Edge(_)))
+            {
+                return Err(PolygonError::FaceWithHole { polygon });
+            }
+            let boundary = Traverser::through(self, face.edge)
+                .unwrap()
+                .map(|edge| index[&edge.origin(self)])
+                .collect();
+            polygons.push(boundary);
+        }
+        Ok((vertices, polygons))
+    }
+}
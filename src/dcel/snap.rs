@@ -0,0 +1,160 @@
+//! Snap rounding. Every vertex sits at the center of a hot pixel, the values that round to it, and
+//! every segment that passes through a hot pixel is bent to go through its center. Crossings are
+//! rounded and made hot pixels of their own first. The pieces a segment is bent into are checked
+//! again, until none of them passes through a hot pixel other than those at its ends. Segments
+//! that did not cross stay uncrossed, and no vertex is left inside an edge. A corner shared by
+//! pixels belongs to the one it rounds to, so a segment through it is bent through one center
+
+use crate::{
+    coord::{Scalar, orient2d},
+    dcel::Entity,
+    index::{Aabb, SelectionFunction},
+};
+
+type Point<S> = [S; 2];
+
+/// the hot pixel around point, as the range of x and the range of y
+pub(crate) fn pixel<S: Scalar>(point: Point<S>) -> [[f64; 2]; 2] {
+    point.map(Scalar::pixel)
+}
+
+/// whether the closed segment meets the hot pixel around center, decided exactly
+pub(crate) fn meets<S: Scalar>([p, q]: [Point<S>; 2], center: Point<S>) -> bool {
+    let [[x0, x1], [y0, y1]] = pixel(center);
+    let [p, q] = [p, q].map(|p| p.map(S::to_f64));
+    if p[0].max(q[0]) < x0 || p[0].min(q[0]) > x1 || p[1].max(q[1]) < y0 || p[1].min(q[1]) > y1 {
+        return false;
+    }
+    // the line through it has corners of the pixel on both sides of it
+    let corners = [[x0, y0], [x1, y0], [x1, y1], [x0, y1]];
+    let sides = corners.map(|corner| orient2d(p, q, corner));
+    if sides.iter().any(|side| *side > 0.) && sides.iter().any(|side| *side < 0.) {
+        return true;
+    }
+    // or passes through a corner, which is in the one pixel it rounds to. segments between centers
+    // can't run along a side
+    corners
+        .into_iter()
+        .zip(sides)
+        .any(|(corner, side)| side == 0. && corner.map(S::from_f64) == center)
+}
+
+/// how far along the segment the projection of point is, to put points along it in order
+pub(crate) fn along<S: Scalar>([p, q]: [Point<S>; 2], point: Point<S>) -> f64 {
+    let [p, q, point] = [p, q, point].map(|p| p.map(S::to_f64));
+    let [dx, dy] = [q[0] - p[0], q[1] - p[1]];
+    ((point[0] - p[0]) * dx + (point[1] - p[1]) * dy) / (dx * dx + dy * dy)
+}
+
+/// where two segments cross, if they do so at a single point inside both, before rounding
+pub(crate) fn crossing<S: Scalar>(
+    [a, b]: [Point<S>; 2],
+    [p, q]: [Point<S>; 2],
+) -> Option<[f64; 2]> {
+    let opposite = |x: f64, y: f64| x != 0. && y != 0. && (x < 0.) != (y < 0.);
+    let [from_a, from_b] = [orient2d(p, q, a), orient2d(p, q, b)];
+    if !opposite(orient2d(a, b, p), orient2d(a, b, q)) || !opposite(from_a, from_b) {
+        return None;
+    }
+    let t = from_a / (from_a - from_b);
+    let [a, b] = [a, b].map(|p| p.map(S::to_f64));
+    Some([0, 1].map(|i| a[i] + t * (b[i] - a[i])))
+}
+
+/// the points a segment is snapped through, from its first end to its last. it goes through
+/// `forced`, the rounded crossings it takes part in, and through every center `hot` returns for
+/// it, which are the centers of the hot pixels a piece meets other than its ends
+pub(crate) fn route<S: Scalar>(
+    segment: [Point<S>; 2],
+    forced: &[Point<S>],
+    hot: &mut impl FnMut([Point<S>; 2]) -> Vec<Point<S>>,
+) -> Vec<Point<S>> {
+    let [p, q] = segment;
+    if p == q {
+        return vec![p];
+    }
+    let mut inner: Vec<_> = forced.iter().copied().chain(hot(segment)).collect();
+    inner.retain(|point| *point != p && *point != q);
+    let mut points = vec![p];
+    push_along(&mut points, segment, inner);
+    points.push(q);
+
+    // the pieces are straight between centers now, and may pass through other hot pixels
+    loop {
+        let mut bent = vec![points[0]];
+        for piece in points.windows(2) {
+            let piece = [piece[0], piece[1]];
+            let mut inner = hot(piece);
+            inner.retain(|point| !points.contains(point));
+            push_along(&mut bent, piece, inner);
+            bent.push(piece[1]);
+        }
+        if bent.len() == points.len() {
+            return points;
+        }
+        points = bent;
+    }
+}
+
+/// push points in the order they come along segment, each of them once
+fn push_along<S: Scalar>(
+    to: &mut Vec<Point<S>>,
+    segment: [Point<S>; 2],
+    mut points: Vec<Point<S>>,
+) {
+    points.sort_by(|a, b| along(segment, *a).total_cmp(&along(segment, *b)));
+    for point in points {
+        if to.last() != Some(&point) {
+            to.push(point);
+        }
+    }
+}
+
+/// the box around the hot pixels of every point in envelope
+fn pixels_around<S: Scalar>(envelope: &Aabb<S>) -> [[f64; 2]; 2] {
+    [0, 1].map(|i| [envelope.min[i].pixel()[0], envelope.max[i].pixel()[1]])
+}
+
+fn overlap(a: [[f64; 2]; 2], b: [[f64; 2]; 2]) -> bool {
+    (0..2).all(|i| a[i][0] <= b[i][1] && b[i][0] <= a[i][1])
+}
+
+fn bounds<S: Scalar>([p, q]: [Point<S>; 2]) -> [[f64; 2]; 2] {
+    [0, 1].map(|i| {
+        let [p, q] = [p[i].to_f64(), q[i].to_f64()];
+        [p.min(q), p.max(q)]
+    })
+}
+
+/// the vertices, or the centers, whose hot pixel a segment meets
+pub(crate) struct SelectHotAlong<S>(pub [Point<S>; 2]);
+
+impl<S: Scalar> SelectionFunction<Entity<S>> for SelectHotAlong<S> {
+    fn should_unpack_parent(&self, envelope: &Aabb<S>) -> bool {
+        overlap(pixels_around(envelope), bounds(self.0))
+    }
+    fn should_unpack_leaf(&self, leaf: &Entity<S>) -> bool {
+        matches!(leaf, Entity::Vertex(_, point) if meets(self.0, *point))
+    }
+}
+
+impl<S: Scalar> SelectionFunction<Point<S>> for SelectHotAlong<S> {
+    fn should_unpack_parent(&self, envelope: &Aabb<S>) -> bool {
+        overlap(pixels_around(envelope), bounds(self.0))
+    }
+    fn should_unpack_leaf(&self, leaf: &Point<S>) -> bool {
+        meets(self.0, *leaf)
+    }
+}
+
+/// the edges that meet the hot pixel around a point
+pub(crate) struct SelectThroughPixel<S>(pub Point<S>);
+
+impl<S: Scalar> SelectionFunction<Entity<S>> for SelectThroughPixel<S> {
+    fn should_unpack_parent(&self, envelope: &Aabb<S>) -> bool {
+        overlap(bounds([envelope.min, envelope.max]), pixel(self.0))
+    }
+    fn should_unpack_leaf(&self, leaf: &Entity<S>) -> bool {
+        matches!(leaf, Entity::Edge(_, segment) if meets(*segment, self.0))
+    }
+}
@@ -0,0 +1,692 @@
+//! Building a dcel from a soup of segments in a single Bentley-Ottmann sweep, snap rounded to the
+//! scalar of the dcel afterwards
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+use crate::{
+    arena::Key,
+    coord::{
+        Coordinate, Expansion, FromCoordinate, Scalar, UpdateCoordinate, orient2d, orient2d_exact,
+        sort_clockwise,
+    },
+    dcel::{
+        Dcel, Edge, EdgeKey, EdgePtrs, Face, FaceKey, FaceMask, FacePtrs, Flavor, HolRef,
+        SegmentError, Vertex, VertexPtrs,
+        snap::{self, SelectHotAlong},
+    },
+    index::{Aabb, RTree, SelectionFunction, SpatialIndex, SpatialObject},
+    util::ShoeString,
+};
+
+type Point<F> = [<<F as Flavor>::Vertex as Coordinate>::Scalar; 2];
+
+/// the half edges every segment was cut into
+type Pieces = Vec<Vec<Key<EdgeKey>>>;
+
+fn lex(a: [f64; 2], b: [f64; 2]) -> Ordering {
+    a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1]))
+}
+
+/// a point the sweep stops at, exactly x / d, y / d with a positive d. the ends of the segments
+/// keep their floats, to take the fast predicates
+#[derive(Clone)]
+struct Exact {
+    x: Expansion,
+    y: Expansion,
+    d: Expansion,
+    input: Option<[f64; 2]>,
+}
+
+impl Exact {
+    fn input(point: [f64; 2]) -> Self {
+        Self {
+            x: Expansion::new(point[0]),
+            y: Expansion::new(point[1]),
+            d: Expansion::new(1.),
+            input: Some(point),
+        }
+    }
+
+    /// where two properly crossing segments meet
+    fn crossing(s: &Segment, t: &Segment) -> Self {
+        let [from_a, from_b] = [s.a, s.b].map(|p| orient2d_exact(t.a, t.b, p));
+        let d = from_a.sub(&from_b);
+        let [x, y] = [0, 1].map(|i| {
+            Expansion::new(s.a[i])
+                .mul(&d)
+                .add(&from_a.mul(&Expansion::diff(s.b[i], s.a[i])))
+        });
+        let [x, y, d] = match d.signum() < 0. {
+            true => [x.neg(), y.neg(), d.neg()],
+            false => [x, y, d],
+        };
+        Self {
+            x,
+            y,
+            d,
+            input: None,
+        }
+    }
+
+    /// lexicographic, x first
+    fn cmp(&self, other: &Self) -> Ordering {
+        if let (Some(a), Some(b)) = (self.input, other.input) {
+            return lex(a, b);
+        }
+        let cross = |a: &Expansion, b: &Expansion| a.mul(&other.d).sub(&b.mul(&self.d)).signum();
+        let [x, y] = [cross(&self.x, &other.x), cross(&self.y, &other.y)];
+        x.total_cmp(&0.).then(y.total_cmp(&0.))
+    }
+
+    /// positive left of the line from a to b, negative right of it and zero on it
+    fn side(&self, a: [f64; 2], b: [f64; 2]) -> f64 {
+        if let Some(point) = self.input {
+            return orient2d(a, b, point);
+        }
+        let [dx, dy] = [0, 1].map(|i| Expansion::diff(b[i], a[i]));
+        let [px, py] =
+            [(&self.x, a[0]), (&self.y, a[1])].map(|(v, a)| v.sub(&self.d.mul(&Expansion::new(a))));
+        dx.mul(&py).sub(&dy.mul(&px)).signum()
+    }
+
+    fn round(&self) -> [f64; 2] {
+        self.input
+            .unwrap_or([&self.x, &self.y].map(|v| v.estimate() / self.d.estimate()))
+    }
+}
+
+/// a segment running left to right, or bottom to top when it is vertical
+#[derive(Clone)]
+struct Segment {
+    a: [f64; 2],
+    b: [f64; 2],
+    /// which of the segments given it is, and whether it was given the other way
+    input: usize,
+    reversed: bool,
+}
+
+impl Segment {
+    /// whether the directions of s and t turn counterclockwise, so t lies above s right of where
+    /// they meet. exact
+    fn below(&self, other: &Self) -> Ordering {
+        let [sx, sy, tx, ty] = [
+            Expansion::diff(self.b[0], self.a[0]),
+            Expansion::diff(self.b[1], self.a[1]),
+            Expansion::diff(other.b[0], other.a[0]),
+            Expansion::diff(other.b[1], other.a[1]),
+        ];
+        0f64.total_cmp(&sx.mul(&ty).sub(&sy.mul(&tx)).signum())
+    }
+
+    /// where the interiors of both cross in a single point
+    fn crossing(&self, other: &Self) -> Option<Exact> {
+        let opposite = |x: f64, y: f64| x != 0. && y != 0. && (x < 0.) != (y < 0.);
+        let sides = [
+            orient2d(self.a, self.b, other.a),
+            orient2d(self.a, self.b, other.b),
+        ];
+        let from = [
+            orient2d(other.a, other.b, self.a),
+            orient2d(other.a, other.b, self.b),
+        ];
+        (opposite(sides[0], sides[1]) && opposite(from[0], from[1]))
+            .then(|| Exact::crossing(self, other))
+    }
+}
+
+/// a segment in the sweep status, or a point to look up among them. the segments in the status
+/// never cross between events, so two of them compare by where the later one was put in, above or
+/// below the other there, or by direction when both were put in at the same point
+#[derive(Clone)]
+enum Status {
+    Segment {
+        at: Exact,
+        segment: Segment,
+        id: usize,
+    },
+    /// right below or right above every segment through the point
+    Point { point: Exact, above: bool },
+}
+
+impl Status {
+    fn id(&self) -> Option<usize> {
+        match self {
+            Self::Segment { id, .. } => Some(*id),
+            Self::Point { .. } => None,
+        }
+    }
+}
+
+impl PartialEq for Status {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Status {}
+
+impl PartialOrd for Status {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Status {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // orient2d can answer -0
+        let over = |point: &Exact, segment: &Segment| {
+            let side = point.side(segment.a, segment.b);
+            side.partial_cmp(&0.).unwrap_or(Ordering::Equal)
+        };
+        match (self, other) {
+            (Self::Point { above: a, .. }, Self::Point { above: b, .. }) => a.cmp(b),
+            (Self::Point { point, above }, Self::Segment { segment, .. }) => over(point, segment)
+                .then(match above {
+                    true => Ordering::Greater,
+                    false => Ordering::Less,
+                }),
+            (Self::Segment { .. }, Self::Point { .. }) => other.cmp(self).reverse(),
+            (
+                Self::Segment {
+                    at: s_at,
+                    segment: s,
+                    id: s_id,
+                },
+                Self::Segment {
+                    at: t_at,
+                    segment: t,
+                    id: t_id,
+                },
+            ) => match s_at.cmp(t_at) {
+                Ordering::Less => over(t_at, s).reverse(),
+                Ordering::Greater => over(s_at, t),
+                Ordering::Equal => Ordering::Equal,
+            }
+            .then_with(|| s.below(t))
+            .then(s_id.cmp(t_id)),
+        }
+    }
+}
+
+struct Event {
+    point: Exact,
+    /// the segment whose left end this is
+    starts: Option<usize>,
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Event {}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// reversed, the heap pops the leftmost point first
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.point.cmp(&self.point)
+    }
+}
+
+/// the arrangement of the segments, before it is made into a dcel
+#[derive(Default)]
+struct Sweep {
+    segments: Vec<Segment>,
+    events: BinaryHeap<Event>,
+    /// the segments the sweep line crosses, bottom to top
+    status: BTreeSet<Status>,
+    /// the vertex the sweep passed last on every segment
+    last: Vec<Option<usize>>,
+    /// every vertex in the order the sweep passed them, rounded to floats
+    vertices: Vec<[f64; 2]>,
+    /// the pieces segments are cut into, left vertex first, and the segment they are cut from.
+    /// overlapping segments repeat them
+    pieces: Vec<([usize; 2], usize)>,
+}
+
+impl Sweep {
+    fn new(segments: impl IntoIterator<Item = [[f64; 2]; 2]>) -> Self {
+        let mut sweep = Self::default();
+        for (input, [a, b]) in segments.into_iter().enumerate() {
+            if a == b {
+                sweep.events.push(Event {
+                    point: Exact::input(a),
+                    starts: None,
+                });
+                continue;
+            }
+            let reversed = lex(a, b).is_gt();
+            let [a, b] = if reversed { [b, a] } else { [a, b] };
+            let id = sweep.segments.len();
+            sweep.segments.push(Segment {
+                a,
+                b,
+                input,
+                reversed,
+            });
+            sweep.events.push(Event {
+                point: Exact::input(a),
+                starts: Some(id),
+            });
+            sweep.events.push(Event {
+                point: Exact::input(b),
+                starts: None,
+            });
+        }
+        sweep.last = vec![None; sweep.segments.len()];
+        sweep
+    }
+
+    fn run(mut self) -> Self {
+        while self.step() {}
+        self
+    }
+
+    /// handle the leftmost point left, every segment starting, ending or passing through it at once
+    fn step(&mut self) -> bool {
+        let Some(Event { mut point, starts }) = self.events.pop() else {
+            return false;
+        };
+        let mut upper: Vec<usize> = starts.into_iter().collect();
+        while let Some(event) = self.events.peek()
+            && event.point.cmp(&point).is_eq()
+        {
+            let event = self.events.pop().unwrap();
+            // the floats of an end, when a crossing lands on it
+            if event.point.input.is_some() {
+                point = event.point;
+            }
+            upper.extend(event.starts);
+        }
+
+        let [under, over] = [false, true].map(|above| Status::Point {
+            point: point.clone(),
+            above,
+        });
+        let at: Vec<Status> = self.status.range(&under..&over).cloned().collect();
+        let below = self.status.range(..&under).next_back().and_then(Status::id);
+
+        let vertex = self.vertices.len();
+        self.vertices.push(point.round());
+        let ids: Vec<usize> = at.iter().filter_map(Status::id).collect();
+        for &s in &ids {
+            if let Some(from) = self.last[s] {
+                self.pieces.push(([from, vertex], s));
+            }
+        }
+        for &s in ids.iter().chain(&upper) {
+            self.last[s] = Some(vertex);
+        }
+
+        for status in &at {
+            self.status.remove(status);
+        }
+        let through: Vec<usize> = ids
+            .into_iter()
+            .filter(|s| point.input != Some(self.segments[*s].b))
+            .chain(upper)
+            .collect();
+        for &id in &through {
+            self.status.insert(Status::Segment {
+                at: point.clone(),
+                segment: self.segments[id].clone(),
+                id,
+            });
+        }
+
+        // only segments that became neighbours can cross next
+        let first = self.status.range(&under..).next().and_then(Status::id);
+        let last = self.status.range(..&over).next_back().and_then(Status::id);
+        let above = self.status.range(&over..).next().and_then(Status::id);
+        if let (Some(s), Some(t)) = (below, first) {
+            self.check(s, t, &point);
+        }
+        if let (false, Some(s), Some(t)) = (through.is_empty(), last, above) {
+            self.check(s, t, &point);
+        }
+        true
+    }
+
+    fn check(&mut self, s: usize, t: usize, point: &Exact) {
+        if let Some(crossing) = self.segments[s].crossing(&self.segments[t])
+            && crossing.cmp(point).is_gt()
+        {
+            self.events.push(Event {
+                point: crossing,
+                starts: None,
+            });
+        }
+    }
+}
+
+/// a piece of the snapped drawing, left end first, found in an r-tree to look below a point
+struct Snapped<S> {
+    edge: usize,
+    ends: [[S; 2]; 2],
+}
+
+impl<S: Scalar> SpatialObject for Snapped<S> {
+    type Scalar = S;
+    fn envelope(&self) -> Aabb<S> {
+        Aabb::from_point(self.ends[0]).merged(&Aabb::from_point(self.ends[1]))
+    }
+    fn distance_2(&self, point: [S; 2]) -> f64 {
+        self.envelope().distance_2(point)
+    }
+}
+
+/// the pieces below a point that a vertical line just left of it crosses
+struct Under<S>([S; 2]);
+
+impl<S: Scalar> SelectionFunction<Snapped<S>> for Under<S> {
+    fn should_unpack_parent(&self, envelope: &Aabb<S>) -> bool {
+        let [x, y] = self.0;
+        envelope.min[0] < x && x <= envelope.max[0] && envelope.min[1] <= y
+    }
+    fn should_unpack_leaf(&self, leaf: &Snapped<S>) -> bool {
+        let [p, q] = leaf.ends;
+        p[0] < self.0[0] && self.0[0] <= q[0] && orient2d(p, q, self.0) > 0.
+    }
+}
+
+/// which of two pieces crossed by one vertical line is lower there. they don't cross, so an end
+/// of one inside the span of the other that they don't share tells
+fn lower<S: Scalar>(a: [[S; 2]; 2], b: [[S; 2]; 2]) -> Ordering {
+    let side = |[p, q]: [[S; 2]; 2], t: [S; 2]| {
+        let inside = p[0] <= t[0] && t[0] <= q[0] && t != p && t != q;
+        let side = orient2d(p, q, t);
+        (inside && side != 0.).then_some(side > 0.)
+    };
+    let b_above = b.into_iter().find_map(|t| side(a, t));
+    let a_above = || a.into_iter().find_map(|t| side(b, t)).map(|above| !above);
+    match b_above.or_else(a_above) {
+        Some(true) => Ordering::Less,
+        Some(false) => Ordering::Greater,
+        None => Ordering::Equal,
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate + FromCoordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    /// build a dcel from segments that may cross, touch or overlap, computing every intersection in
+    /// one Bentley-Ottmann sweep in O((n + k) log n) for n segments crossing k times. the crossings
+    /// are snap rounded to the scalar of the dcel all at once, so a crossing that needs rounding can
+    /// bend segments differently than inserting them one by one with
+    /// [`insert_segments`](Self::insert_segments) does, and the two only agree when no crossing
+    /// does. segments of zero length are isolated vertices, which stay outside any face when there
+    /// are no edges to make one
+    pub fn from_segments(
+        segments: impl IntoIterator<Item = [Point<F>; 2]>,
+    ) -> Result<Self, SegmentError<F>> {
+        Ok(Self::from_segments_traced(segments)?.0)
+    }
+
+    /// [`from_segments`](Self::from_segments), along with the half edges every segment was cut
+    /// into, each running the way the segment was given
+    pub(crate) fn from_segments_traced(
+        segments: impl IntoIterator<Item = [Point<F>; 2]>,
+    ) -> Result<(Self, Pieces), SegmentError<F>> {
+        let segments: Vec<_> = segments
+            .into_iter()
+            .map(|segment| segment.map(|p| p.map(Scalar::to_f64)))
+            .collect();
+        if !segments.iter().flatten().flatten().all(|c| c.is_finite()) {
+            return Err(SegmentError::NonFiniteCoordinate);
+        }
+        let inputs = segments.len();
+        let sweep = Sweep::new(segments).run();
+        Self::from_sweep(sweep, inputs)
+    }
+
+    fn from_sweep(sweep: Sweep, inputs: usize) -> Result<(Self, Pieces), SegmentError<F>> {
+        let round = |v: usize| sweep.vertices[v].map(Scalar::from_f64);
+
+        // every vertex the sweep passed, rounded, is the center of a hot pixel. rounding can land
+        // points it told apart on the same spot. in lexicographic order, so an edge from a lower
+        // center to a higher one runs left to right
+        let mut points: Vec<Point<F>> = (0..sweep.vertices.len()).map(round).collect();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        points.dedup();
+        let center = |point: Point<F>| {
+            points
+                .binary_search_by(|p| p.partial_cmp(&point).unwrap_or(Ordering::Equal))
+                .map_err(|_| SegmentError::Degenerate)
+        };
+        let hot_pixels = RTree::bulk_load(points.clone());
+        let mut hot = |piece| hot_pixels.select(SelectHotAlong(piece)).copied().collect();
+
+        // every segment snapped through the vertices the sweep found on it, and the hot pixels it
+        // passes, with every piece of it once as an edge
+        let mut on: Vec<Vec<Point<F>>> = vec![vec![]; sweep.segments.len()];
+        for (piece, segment) in &sweep.pieces {
+            on[*segment].extend(piece.map(round));
+        }
+        let mut edges: Vec<[usize; 2]> = vec![];
+        let mut edge_of: HashMap<[usize; 2], usize> = HashMap::new();
+        // half edge 2e runs along edge e, 2e + 1 back
+        let mut along: Vec<Vec<usize>> = vec![vec![]; inputs];
+        for (segment, on) in sweep.segments.iter().zip(&on) {
+            let ends = [segment.a, segment.b].map(|p| p.map(Scalar::from_f64));
+            let mut route = snap::route(ends, on, &mut hot);
+            if segment.reversed {
+                route.reverse();
+            }
+            for pair in route.windows(2) {
+                let [u, v] = [center(pair[0])?, center(pair[1])?];
+                let piece = [u.min(v), u.max(v)];
+                let edge = *edge_of.entry(piece).or_insert_with(|| {
+                    edges.push(piece);
+                    edges.len() - 1
+                });
+                along[segment.input].push(2 * edge + usize::from(u > v));
+            }
+        }
+
+        let halves = edges.len() * 2;
+        let origin = |h: usize| edges[h / 2][h % 2];
+        let mut outgoing = vec![vec![]; points.len()];
+        for h in 0..halves {
+            outgoing[origin(h)].push(h);
+        }
+        let mut position = vec![0; halves];
+        for (v, around) in outgoing.iter_mut().enumerate() {
+            around.sort_by(|a, b| {
+                sort_clockwise(points[v], points[origin(a ^ 1)], points[origin(b ^ 1)])
+            });
+            for (i, h) in around.iter().enumerate() {
+                position[*h] = i;
+            }
+        }
+        // a face lies right of its half edges, so the next one turns right the most
+        let next: Vec<usize> = (0..halves)
+            .map(|h| {
+                let around = &outgoing[origin(h ^ 1)];
+                around[(position[h ^ 1] + around.len() - 1) % around.len()]
+            })
+            .collect();
+        let mut prev = vec![0; halves];
+        for (h, n) in next.iter().enumerate() {
+            prev[*n] = h;
+        }
+
+        // bounded faces wind clockwise, the other cycles are holes
+        let mut cycle = vec![usize::MAX; halves];
+        let mut cycles: Vec<(usize, bool)> = vec![];
+        for start in 0..halves {
+            if cycle[start] != usize::MAX {
+                continue;
+            }
+            let mut area = ShoeString::default();
+            let mut h = start;
+            loop {
+                cycle[h] = cycles.len();
+                area.add(&points[origin(h)], &points[origin(h ^ 1)]);
+                h = next[h];
+                if h == start {
+                    break;
+                }
+            }
+            cycles.push((start, area.orientation().is_cw()));
+        }
+
+        // the bounding face comes first, then one face per clockwise cycle
+        let mut face_of: Vec<Option<usize>> = vec![None; cycles.len()];
+        let mut faces = usize::from(halves > 0);
+        for (c, (_, bounded)) in cycles.iter().enumerate() {
+            if *bounded {
+                face_of[c] = Some(faces);
+                faces += 1;
+            }
+        }
+
+        // a hole lies in the face above the edge right below its leftmost vertex, found by
+        // climbing to the first cycle that is the boundary of a face
+        let mut leftmost = vec![usize::MAX; cycles.len()];
+        for h in 0..halves {
+            leftmost[cycle[h]] = leftmost[cycle[h]].min(origin(h));
+        }
+        let snapped = RTree::bulk_load(
+            edges
+                .iter()
+                .enumerate()
+                .map(|(edge, ends)| Snapped {
+                    edge,
+                    ends: ends.map(|v| points[v]),
+                })
+                .collect(),
+        );
+        let below = |v: usize| {
+            snapped
+                .select(Under(points[v]))
+                .max_by(|a, b| lower(a.ends, b.ends))
+                .map(|piece| piece.edge)
+        };
+        let face_above = |v: usize, face_of: &mut Vec<Option<usize>>| {
+            let mut chain = vec![];
+            let mut edge = below(v);
+            let face = loop {
+                let Some(e) = edge else { break 0 };
+                let c = cycle[2 * e + 1];
+                if let Some(face) = face_of[c] {
+                    break face;
+                }
+                chain.push(c);
+                edge = below(leftmost[c]);
+            };
+            for c in chain {
+                face_of[c] = Some(face);
+            }
+            face
+        };
+        let mut holes: Vec<Vec<HolRef>> = vec![vec![]; faces];
+        let mut hole_cycles = vec![];
+        for c in 0..cycles.len() {
+            if !cycles[c].1 {
+                let face = face_above(leftmost[c], &mut face_of);
+                face_of[c] = Some(face);
+                hole_cycles.push(c);
+            }
+        }
+        // with no edges there is no face to hold them, and they stay loose as Mvh leaves them
+        let isolated: Vec<(usize, usize)> = match faces {
+            0 => vec![],
+            _ => (0..points.len())
+                .filter(|v| outgoing[*v].is_empty())
+                .map(|v| (v, face_above(v, &mut face_of)))
+                .collect(),
+        };
+
+        let mut dcel = Self::default();
+        let vertex_keys: Vec<_> = (0..points.len()).map(|_| dcel.vertices.reserve()).collect();
+        let edge_keys: Vec<Key<EdgeKey>> = (0..halves).map(|_| dcel.edges.reserve()).collect();
+        let face_keys: Vec<Key<FaceKey>> = (0..faces).map(|_| dcel.faces.reserve()).collect();
+
+        let mut outer = None;
+        for c in hole_cycles {
+            let face = face_of[c].ok_or(SegmentError::Degenerate)?;
+            let edge = edge_keys[cycles[c].0];
+            if face == 0 && outer.is_none() {
+                outer = Some(edge);
+            } else {
+                holes[face].push(HolRef::Edge(edge));
+            }
+        }
+        for (v, face) in isolated {
+            holes[face].push(HolRef::Vertex(vertex_keys[v]));
+        }
+
+        for (v, key) in vertex_keys.iter().enumerate() {
+            dcel.vertices.set(
+                *key,
+                Vertex {
+                    inner: VertexPtrs {
+                        edge: outgoing[v].first().map(|h| edge_keys[*h]),
+                    },
+                    weight: F::Vertex::from_xy(points[v]),
+                },
+            );
+        }
+        for h in 0..halves {
+            dcel.edges.set(
+                edge_keys[h],
+                Edge {
+                    inner: EdgePtrs {
+                        origin: vertex_keys[origin(h)],
+                        twin: edge_keys[h ^ 1],
+                        prev: edge_keys[prev[h]],
+                        next: edge_keys[next[h]],
+                        face: face_keys[face_of[cycle[h]].ok_or(SegmentError::Degenerate)?],
+                    },
+                    weight: Default::default(),
+                },
+            );
+        }
+        let mut boundaries = vec![None; faces];
+        if let Some(bounding) = boundaries.first_mut() {
+            *bounding = outer;
+        }
+        for (c, (start, bounded)) in cycles.iter().enumerate() {
+            if let (true, Some(face)) = (bounded, face_of[c]) {
+                boundaries[face] = Some(edge_keys[*start]);
+            }
+        }
+        for (face, holes) in holes.into_iter().enumerate() {
+            dcel.faces.set(
+                face_keys[face],
+                Face {
+                    inner: FacePtrs {
+                        // a bounding face with nothing around it, when rounding left no hole
+                        edge: boundaries[face].ok_or(SegmentError::Degenerate)?,
+                        holes,
+                        mask: match face {
+                            0 => FaceMask::IS_OUTER,
+                            _ => FaceMask::IS_BOUNDARY,
+                        },
+                    },
+                    weight: Default::default(),
+                },
+            );
+        }
+        dcel.bounding_face = face_keys.first().copied();
+
+        let along = along
+            .into_iter()
+            .map(|halves| halves.into_iter().map(|h| edge_keys[h]).collect())
+            .collect();
+        Ok((dcel, along))
+    }
+}
@@ -1,4 +1,11 @@
-use crate::dcel::{self, Dcel, Flavor, Traverser, draw::Draw, error::Error, ops, vis};
+use crate::{
+    arena::{Arena, ArenaBitMask, ComponentStore, Key, SecondaryMap},
+    coord::{Coordinate, on_segment, segments_intersect},
+    dcel::{
+        self, Dcel, EdgeKey, FaceKey, Flavor, History, HolRef, Traverser, VertexKey, draw::Draw,
+        error::Error, linker::Linker, ops, vis,
+    },
+};
 
 pub struct TestFlavor;
 impl Flavor for TestFlavor {
@@ -7,8 +14,82 @@ impl Flavor for TestFlavor {
     type Face = ();
 }
 
+pub struct GeoFlavor;
+impl Flavor for GeoFlavor {
+    type Vertex = [f64; 2];
+    type Edge = ();
+    type Face = ();
+}
+
+pub struct GridFlavor;
+impl Flavor for GridFlavor {
+    type Vertex = [i64; 2];
+    type Edge = ();
+    type Face = ();
+}
+
+pub struct SmallGridFlavor;
+impl Flavor for SmallGridFlavor {
+    type Vertex = [i32; 2];
+    type Edge = ();
+    type Face = ();
+}
+
+pub struct LabelFlavor;
+impl Flavor for LabelFlavor {
+    type Vertex = [f32; 2];
+    type Edge = ();
+    type Face = &'static str;
+}
+
 #[test]
-fn test_find_next_prev() {}
+fn test_find_next_prev() {
+    let (mut draw, [center, east]) = Draw::new(Dcel::<TestFlavor>::default(), [0., 0.], [1., 0.]);
+    let mut spokes = vec![east];
+    for point in [[0., 1.], [-1., 0.], [0., -1.]] {
+        draw.set_key(center);
+        spokes.push(draw.line_to(point));
+    }
+    let mut star = draw.finish();
+    let [east, north, west, south] = spokes[..] else {
+        unreachable!()
+    };
+
+    for (point, [prev, next]) in [
+        ([1., 1.], [east, north]),
+        ([-1., 1.], [north, west]),
+        ([-1., -1.], [west, south]),
+        ([1., -1.], [south, east]),
+    ] {
+        let reference = star.mvh(point).unwrap().vertex;
+        let [local_prev, local_next] = Linker::find_prev_next(&star, center, reference);
+        assert_eq!(local_prev, edge_between(&star, center, prev));
+        assert_eq!(local_next, edge_between(&star, center, next));
+    }
+}
+
+#[test]
+fn find_next_prev_near_collinear() {
+    // a few ulps below the diagonal, plain f32 sees the center in line with both points above
+    let [x, y] = [0.5 - 1.75 * f32::EPSILON, 0.5 - 2. * f32::EPSILON];
+    let (mut draw, [center, diagonal]) =
+        Draw::new(Dcel::<TestFlavor>::default(), [x, y], [12., 12.]);
+    let mut spokes = vec![diagonal];
+    for point in [[x, 10.], [x, -10.]] {
+        draw.set_key(center);
+        spokes.push(draw.line_to(point));
+    }
+    let mut star = draw.finish();
+    let [diagonal, _, south] = spokes[..] else {
+        unreachable!()
+    };
+
+    // further along the diagonal, but turning clockwise from it
+    let reference = star.mvh([24., 24.]).unwrap().vertex;
+    let [local_prev, local_next] = Linker::find_prev_next(&star, center, reference);
+    assert_eq!(local_prev, edge_between(&star, center, south));
+    assert_eq!(local_next, edge_between(&star, center, diagonal));
+}
 
 #[test]
 fn mev_cycle() {
@@ -27,20 +108,19 @@ fn mev_cycle() {
         right,
     );
 
-    let uncollapse = collapse.apply(&mut hourglass);
+    let report = hourglass.validate();
+    assert!(report.is_valid(), "{report}");
 
-    for (face, key) in hourglass.faces.iter() {
-        println!("------------------");
-        println!("{key}");
-        println!("------------------");
-        for edge in Traverser::through(&hourglass, face.edge).unwrap() {
-            println!("{edge} {}", edge.face(&hourglass));
-        }
-    }
+    let uncollapse = collapse.apply(&mut hourglass).ok().unwrap();
+    let report = hourglass.validate();
+    assert!(report.is_valid(), "{report}");
 
-    // uncollapse.apply(&mut hourglass);
+    uncollapse.apply(&mut hourglass).ok().unwrap();
+    let report = hourglass.validate();
+    assert!(report.is_valid(), "{report}");
 
-    std::fs::write("./test.mev_cycle.svg", vis::vis_svg(&hourglass)).unwrap();
+    let svg = std::env::temp_dir().join("test.mev_cycle.svg");
+    std::fs::write(svg, vis::vis_svg(&hourglass)).unwrap();
 }
 
 #[test]
@@ -64,15 +144,2176 @@ fn test_cycle() {
         }
     }
 
-    std::fs::write("./test.mev_cycle.svg", vis::vis_svg(&square)).unwrap();
+    let svg = std::env::temp_dir().join("test.test_cycle.svg");
+    std::fs::write(svg, vis::vis_svg(&square)).unwrap();
 }
 
 #[test]
-fn mev_kve() {}
+fn mev_kev() {
+    let (mut draw, [a, b]) = Draw::new(Dcel::<TestFlavor>::default(), [0., 0.], [2., 0.]);
+    draw.line_to([0., 2.]);
+    draw.close_path(a);
+    let mut triangle = draw.finish();
+
+    let edge = edge_between(&triangle, a, b);
+    let twin = edge.twin(&triangle);
+    let kev = triangle.mev(edge, [1., 0.]).unwrap();
+    let [e2, t2] = kev.edges;
+    let m = kev.vertex;
+
+    assert_eq!(edge.next(&triangle), e2);
+    assert_eq!(e2.next(&triangle).origin(&triangle), b);
+    assert_eq!(twin.origin(&triangle), m);
+    assert_eq!(t2.origin(&triangle), b);
+    assert_eq!(t2.next(&triangle), twin);
+    assert_eq!(Traverser::around(&triangle, m).unwrap().count(), 2);
+    assert_eq!(Traverser::through(&triangle, edge).unwrap().count(), 4);
+    assert_eq!(Traverser::through(&triangle, twin).unwrap().count(), 4);
+
+    let mev = triangle.kev(m, kev.edges).unwrap();
+    assert_eq!(mev.edge, edge);
+    assert_eq!(edge.next(&triangle).origin(&triangle), b);
+    assert_eq!(twin.origin(&triangle), b);
+    assert_eq!(Traverser::through(&triangle, edge).unwrap().count(), 3);
+    assert_eq!(Traverser::through(&triangle, twin).unwrap().count(), 3);
+    assert_eq!(triangle.vertices.iter().count(), 3);
+    assert_eq!(triangle.edges.iter().count(), 6);
+}
+
+#[test]
+fn mev_kev_dangling() {
+    let mut dcel = Dcel::<TestFlavor>::default();
+    let kvvef = dcel.mvvef([0., 0.], [2., 0.]).unwrap();
+    let [a, b] = kvvef.vertices;
+    let edge = edge_between(&dcel, a, b);
+
+    let kev = dcel.mev(edge, [1., 0.]).unwrap();
+    assert_eq!(Traverser::through(&dcel, edge).unwrap().count(), 4);
+    assert_eq!(Traverser::around(&dcel, b).unwrap().count(), 1);
+    assert!(dcel.kev(a, kev.edges).is_err());
+
+    dcel.kev(kev.vertex, kev.edges).unwrap();
+    assert_eq!(Traverser::through(&dcel, edge).unwrap().count(), 2);
+    assert_eq!(edge.twin(&dcel).origin(&dcel), b);
+}
 
 #[test]
 fn mve_kev() {}
 
+#[test]
+fn mef_diagonal() {
+    let (mut square, [a, _, c, _]) = make_square();
+    let [outer, inner] = square_faces(&square);
+
+    let kef = square.mef(a, c).unwrap();
+    let [outgoing, incoming] = kef.edges;
+
+    assert_ne!(outgoing.face(&square), incoming.face(&square));
+    assert_eq!(Traverser::through(&square, outgoing).unwrap().count(), 3);
+    assert_eq!(Traverser::through(&square, incoming).unwrap().count(), 3);
+    assert_eq!(
+        Traverser::through(&square, outer.edge(&square))
+            .unwrap()
+            .count(),
+        4
+    );
+    assert_eq!(inner.edge(&square).face(&square), inner);
+
+    square.kef(kef.face, kef.edges).unwrap();
+    assert_eq!(
+        Traverser::through(&square, inner.edge(&square))
+            .unwrap()
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn mekh_kemh_vertex() {
+    let (mut square, [a, ..]) = make_square();
+    let [_, inner] = square_faces(&square);
+    let v = square.mvh([0., 0.]).unwrap().vertex;
+
+    assert!(matches!(
+        square.mef(a, v).map_err(|err| err.err),
+        Err(ops::MefError::IsolatedVertex)
+    ));
+
+    let link = ops::LinkVertices::new(&mut square, a, v);
+    assert!(matches!(link, ops::LinkVertices::Mekh(_)));
+    let ops::UnlinkVertices::Kemh(kemh) = link.apply(&mut square) else {
+        panic!("expected kemh");
+    };
+
+    assert_eq!(Traverser::around(&square, v).unwrap().count(), 1);
+    assert_eq!(
+        Traverser::through(&square, inner.edge(&square))
+            .unwrap()
+            .count(),
+        6
+    );
+    assert!(square.face(inner).holes.is_empty());
+
+    let mekh = square.kemh(kemh.edges).unwrap();
+    assert_eq!(mekh.vertices, [a, v]);
+    assert_eq!(v.edge(&square), None);
+    assert_eq!(square.face(inner).holes, vec![HolRef::Vertex(v)]);
+    assert_eq!(
+        Traverser::through(&square, inner.edge(&square))
+            .unwrap()
+            .count(),
+        4
+    );
+}
+
+#[test]
+fn mekh_kemh_isolated_pair() {
+    let (mut square, _) = make_square();
+    let [_, inner] = square_faces(&square);
+    let v1 = square.mvh([-0.5, 0.]).unwrap().vertex;
+    let v2 = square.mvh([0.5, 0.]).unwrap().vertex;
+
+    let kemh = square.mekh(v1, v2).unwrap();
+    let [outgoing, incoming] = kemh.edges;
+    assert_eq!(square.face(inner).holes.len(), 1);
+    assert_eq!(outgoing.face(&square), inner);
+    assert_eq!(incoming.face(&square), inner);
+    assert_eq!(outgoing.next(&square), incoming);
+
+    square.kemh(kemh.edges).unwrap();
+    let holes = &square.face(inner).holes;
+    assert_eq!(holes.len(), 2);
+    assert!(holes.contains(&HolRef::Vertex(v1)));
+    assert!(holes.contains(&HolRef::Vertex(v2)));
+}
+
+#[test]
+fn mvh_kvh_holes() {
+    let (mut square, _) = make_square();
+    let [outer, inner] = square_faces(&square);
+
+    let inside = square.mvh([0., 0.]).unwrap().vertex;
+    let outside = square.mvh([5., 5.]).unwrap().vertex;
+    assert_eq!(square.face(inner).holes, vec![HolRef::Vertex(inside)]);
+    assert_eq!(square.face(outer).holes, vec![HolRef::Vertex(outside)]);
+
+    square.kvh(inside).unwrap();
+    assert!(square.face(inner).holes.is_empty());
+}
+
+#[test]
+fn mvvef_adopts_floating_vertices() {
+    let mut dcel = Dcel::<TestFlavor>::default();
+    let floating = dcel.mvh([5., 5.]).unwrap().vertex;
+    let kvvef = dcel.mvvef([0., 0.], [1., 0.]).unwrap();
+
+    let face = kvvef.edges[0].face(&dcel);
+    assert_eq!(dcel.bounding_face, Some(face));
+    assert_eq!(dcel.face(face).holes, vec![HolRef::Vertex(floating)]);
+
+    let mvvef = dcel.check_apply(kvvef).unwrap();
+    assert!(dcel.bounding_face.is_none());
+    assert!(dcel.faces.iter().next().is_none());
+    assert!(mvvef.data.4.is_some());
+}
+
+#[test]
+fn mvvef_inside_face_is_hole() {
+    let (mut square, _) = make_square();
+    let [_, inner] = square_faces(&square);
+    let faces = square.faces.iter().count();
+
+    let kvvef = square.mvvef([-1., 0.], [1., 0.]).unwrap();
+    assert_eq!(square.faces.iter().count(), faces);
+    assert_eq!(kvvef.edges[0].face(&square), inner);
+    assert_eq!(kvvef.edges[1].face(&square), inner);
+    assert_eq!(square.face(inner).holes, vec![HolRef::Edge(kvvef.edges[0])]);
+
+    square.check_apply(kvvef).unwrap();
+    assert!(square.face(inner).holes.is_empty());
+}
+
+#[test]
+fn mef_closes_nested_ring() {
+    let (square, _) = make_square();
+    let [outer, inner] = square_faces(&square);
+
+    let (mut draw, a) = Draw::start(square, [-1., -1.]);
+    assert_eq!(draw.dcel().face(inner).holes, vec![HolRef::Vertex(a)]);
+    draw.line_to([1., -1.]);
+    draw.line_to([1., 1.]);
+    draw.line_to([-1., 1.]);
+    let ring = draw.close_path(a);
+    let mut square = draw.finish();
+
+    let [HolRef::Edge(hole)] = square.face(inner).holes[..] else {
+        panic!("expected a single edge hole");
+    };
+    assert_eq!(hole.face(&square), inner);
+    assert_eq!(Traverser::through(&square, hole).unwrap().count(), 4);
+    assert!(square.face_signed_area(ring).unwrap() < 0.);
+    assert!(square.face(outer).holes.is_empty());
+
+    // a vertex inside the ring is a hole of the ring, not the square
+    let v = square.mvh([0., 0.]).unwrap().vertex;
+    assert_eq!(square.face(ring).holes, vec![HolRef::Vertex(v)]);
+}
+
+#[test]
+fn mef_kef_split_holes() {
+    let (mut square, [a, b, c, d]) = make_square();
+    let [_, inner] = square_faces(&square);
+    let left = square.mvh([-1., 0.]).unwrap().vertex;
+    let right = square.mvh([1., 0.]).unwrap().vertex;
+
+    let bottom = square.mev(edge_between(&square, a, b), [0., -2.]).unwrap();
+    let top = square.mev(edge_between(&square, c, d), [0., 2.]).unwrap();
+    let kef = square.mef(bottom.vertex, top.vertex).unwrap();
+
+    let [e1, e2] = kef.edges;
+    let holes_of = |square: &Dcel<TestFlavor>, edge: Key<EdgeKey>| {
+        square.face(edge.face(square)).holes.clone()
+    };
+    let (left_edge, right_edge) = if holes_of(&square, e1) == vec![HolRef::Vertex(left)] {
+        (e1, e2)
+    } else {
+        (e2, e1)
+    };
+    assert_eq!(holes_of(&square, left_edge), vec![HolRef::Vertex(left)]);
+    assert_eq!(holes_of(&square, right_edge), vec![HolRef::Vertex(right)]);
+    assert_eq!(inner.edge(&square).face(&square), inner);
+
+    square.kef(kef.face, kef.edges).unwrap();
+    let holes = &square.face(inner).holes;
+    assert_eq!(holes.len(), 2);
+    assert!(holes.contains(&HolRef::Vertex(left)));
+    assert!(holes.contains(&HolRef::Vertex(right)));
+}
+
+#[test]
+fn checks_reject_invalid_edits() {
+    let (mut square, [a, b, c, _]) = make_square();
+    let [outer, inner] = square_faces(&square);
+    let edge = edge_between(&square, a, b);
+    let twin = edge.twin(&square);
+    let other = edge_between(&square, b, c);
+
+    let err = square.kvh(a).err().unwrap();
+    assert!(matches!(err.err, ops::KvhError::NotIsolated));
+    let err = square.kef(outer, [edge, twin]).err().unwrap();
+    assert!(matches!(err.err, ops::KefError::BoundingFace));
+    let err = square.kef(inner, [edge, other]).err().unwrap();
+    assert!(matches!(err.err, ops::KefError::NotTwins));
+    let err = square.kve(a, [edge, twin], c).err().unwrap();
+    assert!(matches!(err.err, ops::KveError::EdgeVertexMismatch));
+    let err = square.mve(a, [-2., -2.]).err().unwrap();
+    assert!(matches!(err.err, ops::MveError::ZeroLengthEdge));
+    let err = square
+        .check_apply(ops::Kvvef {
+            vertices: [a, b],
+            edges: [edge, twin],
+        })
+        .err()
+        .unwrap();
+    assert!(matches!(err.err, ops::KvvefError::NotIsolatedComponent));
+    let err = square.mvh([f32::NAN, 0.]).err().unwrap();
+    assert!(matches!(err.err, ops::MvhError::NonFiniteCoordinate));
+
+    // nothing was touched by the rejected edits
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(square.vertices.iter().count(), 4);
+    assert_eq!(square.edges.iter().count(), 8);
+
+    let v = square.mvh([0., 0.]).unwrap().vertex;
+    square.kvh(v).unwrap();
+    let err = square.kvh(v).err().unwrap();
+    assert!(matches!(err.err, ops::KvhError::VertexDoesNotExist));
+}
+
+#[test]
+fn translate_vertex_keeps_planarity() {
+    let (mut square, [a, b, c, d]) = make_square();
+    let inside = square.mvh([0., 0.]).unwrap().vertex;
+
+    // a small move stays inside every face
+    square.translate_vertex(a, [0.5, 0.5]).unwrap();
+    square.translate_vertex(a, [-0.5, -0.5]).unwrap();
+
+    // dragging a corner over the middle sweeps its edges across the isolated vertex
+    let err = square.translate_vertex(a, [3., 3.]).err().unwrap();
+    let ops::TranslateVertexError::WouldMakeNonPlanar { edges, vertices } = err.err else {
+        panic!("expected a planarity error");
+    };
+    assert!(vertices.contains(&inside));
+    assert!(edges.is_empty());
+
+    // dragging it through the opposite side runs into the edge between c and d
+    let err = square.translate_vertex(a, [1., 6.]).err().unwrap();
+    let ops::TranslateVertexError::WouldMakeNonPlanar { edges, .. } = err.err else {
+        panic!("expected a planarity error");
+    };
+    let cd = edge_between(&square, c, d);
+    assert!(edges.contains(&cd) || edges.contains(&cd.twin(&square)));
+
+    // an isolated vertex can not leave its face
+    let err = square.translate_vertex(inside, [4., 0.]).err().unwrap();
+    let ops::TranslateVertexError::WouldMakeNonPlanar { edges, .. } = err.err else {
+        panic!("expected a planarity error");
+    };
+    let bc = edge_between(&square, b, c);
+    assert!(edges.contains(&bc) || edges.contains(&bc.twin(&square)));
+
+    // clamping stops just short of the isolated vertex
+    let clamped = ops::TranslateVertex {
+        vertex: a,
+        delta: [3., 3., 0.],
+    }
+    .clamp(&square);
+    assert!(clamped.delta[0] > 0. && clamped.delta[0] < 3.);
+    assert!(square.check_apply(clamped).is_ok());
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+}
+
+#[test]
+fn translate_face_moves_region() {
+    let (mut square, ring, [a, ..]) = make_nested_ring();
+    let inside = square.mvh([0., 0.]).unwrap().vertex;
+    let beside = square.mvh([1.5, 0.]).unwrap().vertex;
+
+    let inverse = square.translate_face(vec![ring], [0.25, 0.5]).unwrap();
+    assert_eq!(square.vertex(a).weight, [-0.75, -0.5]);
+    assert_eq!(square.vertex(inside).weight, [0.25, 0.5]);
+    assert_eq!(square.vertex(beside).weight, [1.5, 0.]);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    assert!(square.check_apply(inverse).is_ok());
+    assert_eq!(square.vertex(a).weight, [-1., -1.]);
+    assert_eq!(square.vertex(inside).weight, [0., 0.]);
+
+    // the ring would swallow the vertex beside it
+    let err = square.translate_face(vec![ring], [0.8, 0.]).err().unwrap();
+    let ops::TransformError::WouldMakeNonPlanar { vertices, .. } = err.err else {
+        panic!("expected a planarity error");
+    };
+    assert_eq!(vertices, vec![beside]);
+
+    // the ring would cross the square around it
+    let err = square.translate_face(vec![ring], [0., 2.]).err().unwrap();
+    let ops::TransformError::WouldMakeNonPlanar { edges, .. } = err.err else {
+        panic!("expected a planarity error");
+    };
+    assert!(!edges.is_empty());
+    assert_eq!(square.vertex(a).weight, [-1., -1.]);
+}
+
+#[test]
+fn rotate_and_scale_face() {
+    let (mut square, ring, [a, ..]) = make_nested_ring();
+    let [_, inner] = square_faces(&square);
+
+    let inverse = square
+        .rotate_face(vec![ring], [0., 0.], std::f64::consts::FRAC_PI_2)
+        .unwrap();
+    assert_near(square.vertex(a).weight, [1., -1.]);
+    assert!(square.check_apply(inverse).is_ok());
+
+    square.scale_face(vec![ring], [0., 0.], [1.5, 0.5]).unwrap();
+    assert_near(square.vertex(a).weight, [-1.5, -0.5]);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    let err = square
+        .scale_face(vec![ring], [0., 0.], [-1., 1.])
+        .err()
+        .unwrap();
+    assert!(matches!(err.err, ops::TransformError::Degenerate));
+    let err = square
+        .scale_face(vec![ring], [0., 0.], [2., 2.])
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err.err,
+        ops::TransformError::WouldMakeNonPlanar { .. }
+    ));
+
+    // moving the square moves the ring inside it too
+    let moved = square.check_apply(ops::AffineTransform {
+        faces: vec![inner],
+        affine: ops::Affine::rotation([0., 0.], 0.5).then(ops::Affine::translation([3., 0.])),
+    });
+    assert!(moved.is_ok());
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+}
+
+#[test]
+fn history_undo_redo() {
+    let (mut square, [a, b, ..]) = make_square();
+    let mut history = History::new();
+
+    history.begin_group();
+    let edge = edge_between(&square, a, b);
+    history
+        .apply(&mut square, ops::Mev::new(edge, [0., -2.], [(), ()]))
+        .ok()
+        .unwrap();
+    history
+        .apply(
+            &mut square,
+            ops::TranslateVertex {
+                vertex: a,
+                delta: [0., 1., 0.],
+            },
+        )
+        .ok()
+        .unwrap();
+    history.end_group();
+    assert_eq!(square.vertices.iter().count(), 5);
+
+    // the whole group is undone at once
+    assert!(history.undo(&mut square).ok().unwrap());
+    assert_eq!(square.vertices.iter().count(), 4);
+    assert_eq!(square.vertex(a).weight, [-2., -2.]);
+    assert!(!history.undo(&mut square).ok().unwrap());
+
+    assert!(history.redo(&mut square).ok().unwrap());
+    assert_eq!(square.vertices.iter().count(), 5);
+    assert_eq!(square.vertex(a).weight, [-2., -1.]);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // a new edit drops what could be redone
+    history.undo(&mut square).ok().unwrap();
+    assert!(history.can_redo());
+    history
+        .apply(&mut square, ops::Mvh { vertex: [0., 0.] })
+        .ok()
+        .unwrap();
+    assert!(!history.can_redo());
+
+    // a group that fails half way is rolled back
+    let edge = edge_between(&square, a, b);
+    let failed = history
+        .apply_group(
+            &mut square,
+            [
+                ops::Mev::new(edge, [0., -2.], [(), ()]).into(),
+                ops::Kvh { vertex: a }.into(),
+            ],
+        )
+        .err()
+        .unwrap();
+    assert_eq!(failed.op.len(), 2);
+    assert_eq!(square.vertices.iter().count(), 5);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    history.undo(&mut square).ok().unwrap();
+    assert_eq!(square.vertices.iter().count(), 4);
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn history_abort_group_keeps_what_it_could_not_undo() {
+    let (mut square, [a, ..]) = make_square();
+    let mut history = History::new();
+
+    history.begin_group();
+    history
+        .apply(&mut square, ops::Mvh { vertex: [0., 0.] })
+        .ok()
+        .unwrap();
+    let inside = square.vertices.key(5).unwrap();
+    // an edge made behind the back of the history keeps the vertex from being killed
+    let kemh = square.mekh(a, inside).ok().unwrap();
+    assert!(history.abort_group(&mut square).is_err());
+    assert_eq!(square.vertices.iter().count(), 5);
+
+    // the group is still open, and aborts once the edge is gone
+    square.check_apply(kemh).ok().unwrap();
+    history.abort_group(&mut square).ok().unwrap();
+    assert_eq!(square.vertices.iter().count(), 4);
+    assert!(!history.can_undo());
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+}
+
+#[test]
+fn history_limit_and_combo_groups() {
+    let mut hourglass = make_hourglass();
+    let mut history = History::with_limit(2);
+    let vertices = hourglass.vertices.iter().count();
+    let edges = hourglass.edges.iter().count();
+
+    let left = hourglass.vertices.key(1).unwrap();
+    let right = hourglass.vertices.key(3).unwrap();
+    let collapse = ops::CollapseEdge::new(
+        &hourglass,
+        left,
+        [
+            hourglass.edges.key(5).unwrap(),
+            hourglass.edges.key(6).unwrap(),
+        ],
+        right,
+    );
+    history
+        .apply_group(&mut hourglass, collapse.into_ops())
+        .ok()
+        .unwrap();
+    assert_eq!(hourglass.vertices.iter().count(), vertices - 1);
+
+    history.undo(&mut hourglass).ok().unwrap();
+    assert_eq!(hourglass.vertices.iter().count(), vertices);
+    assert_eq!(hourglass.edges.iter().count(), edges);
+    let report = hourglass.validate();
+    assert!(report.is_valid(), "{report}");
+
+    for x in [-0.5, 0., 0.5] {
+        history
+            .apply(&mut hourglass, ops::Mvh { vertex: [x, 3.] })
+            .ok()
+            .unwrap();
+    }
+    while history.undo(&mut hourglass).ok().unwrap() {}
+    // only the last two are remembered
+    assert_eq!(hourglass.vertices.iter().count(), vertices + 1);
+}
+
+#[test]
+fn transaction_rolls_back() {
+    let (mut square, [a, b, ..]) = make_square();
+    let edge = edge_between(&square, a, b);
+
+    let failed = square.transaction(|tx| {
+        let m = tx.apply(ops::Mev::new(edge, [0., -2.], [(), ()]))?.vertex;
+        tx.apply(ops::TranslateVertex {
+            vertex: m,
+            delta: [0., -1., 0.],
+        })?;
+        tx.apply(ops::Kvh { vertex: m })?;
+        Ok::<_, ops::Error<TestFlavor>>(m)
+    });
+    assert!(matches!(
+        failed,
+        Err(ops::Error::Kvh(ops::KvhError::NotIsolated))
+    ));
+    assert_eq!(square.vertices.iter().count(), 4);
+    assert_eq!(Traverser::through(&square, edge).unwrap().count(), 4);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    let (m, undo) = square
+        .transaction(|tx| {
+            let m = tx.apply(ops::Mev::new(edge, [0., -2.], [(), ()]))?.vertex;
+            tx.apply(ops::TranslateVertex {
+                vertex: m,
+                delta: [0., -1., 0.],
+            })?;
+            Ok::<_, ops::Error<TestFlavor>>(m)
+        })
+        .ok()
+        .unwrap();
+    assert_eq!(square.vertex(m).weight, [0., -3.]);
+
+    // the whole transaction is undone, and redone, as one
+    let redo = square.check_apply(undo).ok().unwrap();
+    assert_eq!(square.vertices.iter().count(), 4);
+    square.check_apply(redo).ok().unwrap();
+    assert_eq!(square.vertices.iter().count(), 5);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // a move too far for f32 comes back onto the other vertex, so it can't be undone
+    let mut points = Dcel::<TestFlavor>::default();
+    points
+        .check_apply(ops::Mvh { vertex: [0., 0.] })
+        .ok()
+        .unwrap();
+    let ops::Kvh { vertex } = points
+        .check_apply(ops::Mvh { vertex: [2., 0.] })
+        .ok()
+        .unwrap();
+    let failed = points.transaction(|tx| {
+        tx.apply(ops::TranslateVertex {
+            vertex,
+            delta: [1e9, 0., 0.],
+        })?;
+        tx.apply(ops::Mvh { vertex: [0., 0.] })?;
+        Ok::<_, ops::Error<TestFlavor>>(())
+    });
+    let Err(ops::Error::Rollback(rollback)) = failed else {
+        panic!("the move can't be undone");
+    };
+    assert!(matches!(
+        rollback.failed.err,
+        ops::Error::TranslateVertex(ops::TranslateVertexError::WouldMakeNonPlanar { .. })
+    ));
+    assert!(rollback.pending.is_empty());
+    assert_eq!(points.vertex(vertex).weight, [1e9, 0.]);
+}
+
+#[test]
+fn crdt_replicas_converge() {
+    let replicas = [0, 1, 2].map(|replica| {
+        let (square, _) = make_square();
+        ops::CrdtDcel::new(square, replica)
+    });
+    let [mut r0, mut r1, mut r2] = replicas;
+    let [a, b, c, d] = make_square().1;
+
+    // r0 splits the square along one diagonal, then drags a new vertex out of the bottom edge
+    let diagonal = r0
+        .edit(ops::Mef {
+            vertices: [a, c],
+            data: ((), (), ()),
+        })
+        .ok()
+        .unwrap();
+    let edge = edge_between(r0.dcel(), a, b);
+    let split = r0
+        .edit(ops::Mev::new(edge, [0., -2.], [(), ()]))
+        .ok()
+        .unwrap();
+    let (_, m) = r0
+        .dcel()
+        .vertices
+        .iter()
+        .find(|(vertex, _)| vertex.weight == [0., -2.])
+        .unwrap();
+    let drag = r0
+        .edit(ops::TranslateVertex {
+            vertex: m,
+            delta: [0., -1., 0.],
+        })
+        .ok()
+        .unwrap();
+
+    // concurrently r1 splits along the other diagonal, which crosses the first one
+    let crossing = r1
+        .edit(ops::Mef {
+            vertices: [b, d],
+            data: ((), (), ()),
+        })
+        .ok()
+        .unwrap();
+    // and puts a vertex on it, which splits it as long as it is there
+    let hole = r1.edit(ops::Mvh { vertex: [1., -1.] }).ok().unwrap();
+    let (split_diagonal, _) = r1
+        .dcel()
+        .vertices
+        .iter()
+        .find(|(vertex, _)| vertex.weight == [1., -1.])
+        .unwrap();
+    assert!(split_diagonal.inner.edge.is_some());
+
+    // and r2 moves a corner
+    let corner = r2
+        .edit(ops::TranslateVertex {
+            vertex: c,
+            delta: [1., 1., 0.],
+        })
+        .ok()
+        .unwrap();
+
+    // every replica hears about the others in a different order, the drag even arrives before
+    // the vertex it moves exists
+    for command in [&corner, &hole, &crossing] {
+        r0.receive(command.clone()).ok().unwrap();
+    }
+    for command in [&drag, &corner, &split, &diagonal] {
+        r1.receive(command.clone()).ok().unwrap();
+    }
+    for command in [&hole, &diagonal, &drag, &crossing, &split, &hole] {
+        r2.receive(command.clone()).ok().unwrap();
+    }
+
+    let snapshot = crdt_snapshot(&r0);
+    for replica in [&r0, &r1, &r2] {
+        assert!(crdt_snapshot(replica) == snapshot);
+        let report = replica.dcel().validate();
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(replica.commands().count(), 6);
+    }
+
+    // the first diagonal wins, the crossing one no longer applies once it is in
+    let dcel = r1.dcel();
+    assert_eq!(dcel.edges.iter().count(), 12);
+    assert_eq!(dcel.vertices.iter().count(), 6);
+    let m = r1.key(r0.id(m).unwrap()).unwrap();
+    let ops::AnyKey::Vertex(m) = m else {
+        unreachable!()
+    };
+    assert_eq!(dcel.vertex(m).weight, [0., -3.]);
+    assert_eq!(dcel.vertex(c).weight, [3., 3.]);
+}
+
+#[test]
+fn crdt_rollback_keeps_ids() {
+    let [mut r0, mut r1] = [0, 1].map(|replica| {
+        let (mut square, _) = make_square();
+        square.mvh([1., 1.]).unwrap();
+        ops::CrdtDcel::new(square, replica)
+    });
+    let [a, ..] = make_square().1;
+    let (_, hole) = r1
+        .dcel()
+        .vertices
+        .iter()
+        .find(|(vertex, _)| vertex.weight == [1., 1.])
+        .unwrap();
+    let hole_id = r1.id(hole).unwrap();
+
+    let kill = r1.edit(ops::Kvh { vertex: hole }).ok().unwrap();
+    let make = r1.edit(ops::Mvh { vertex: [-1., 1.] }).ok().unwrap();
+    assert!(r1.key(hole_id).is_none());
+
+    // ordered before both of r1's commands, so r1 takes them back, bringing the hole back under
+    // its old id, and replays them
+    let nudge = r0
+        .edit(ops::TranslateVertex {
+            vertex: a,
+            delta: [-1., 0., 0.],
+        })
+        .ok()
+        .unwrap();
+    r1.receive(nudge).ok().unwrap();
+    r0.receive(make).ok().unwrap();
+    r0.receive(kill).ok().unwrap();
+
+    assert!(crdt_snapshot(&r0) == crdt_snapshot(&r1));
+    assert!(r0.key(hole_id).is_none() && r1.key(hole_id).is_none());
+    assert_eq!(r1.dcel().vertices.iter().count(), 5);
+    assert_eq!(r1.dcel().vertex(a).weight, [-3., -2.]);
+}
+
+#[test]
+fn crdt_refuses_unshared_keys_and_compacts() {
+    let [mut r0, mut r1] = [0, 1].map(|replica| ops::CrdtDcel::new(make_square().0, replica));
+    let [a, b, ..] = make_square().1;
+
+    // the bounding face has no id, an edit of it is refused rather than sent
+    let bounding = r0.dcel().bounding_face.unwrap();
+    let edge = edge_between(r0.dcel(), a, b);
+    let kef = ops::Kef {
+        face: bounding,
+        edges: [edge, edge.twin(r0.dcel())],
+    };
+    assert!(r0.edit(kef).is_err());
+    let translate = ops::TranslateFace {
+        faces: vec![bounding],
+        delta: [1., 0.],
+    };
+    assert!(r0.edit(translate).is_err());
+    assert_eq!(r0.commands().count(), 0);
+
+    let first = r0.edit(ops::Mvh { vertex: [1., 1.] }).ok().unwrap();
+    r1.receive(first.clone()).ok().unwrap();
+    for replica in [&mut r0, &mut r1] {
+        replica.compact(first.id);
+        assert_eq!(replica.commands().count(), 0);
+    }
+    // what every replica has seen is not taken in twice
+    r1.receive(first).ok().unwrap();
+    let second = r1.edit(ops::Mvh { vertex: [-1., 1.] }).ok().unwrap();
+    r0.receive(second).ok().unwrap();
+    assert!(crdt_snapshot(&r0) == crdt_snapshot(&r1));
+    assert_eq!(r0.dcel().vertices.iter().count(), 6);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_round_trip_keeps_keys() {
+    let (mut square, [a, _, c, _]) = make_square();
+    // leave holes in every arena
+    let hole = square.mvh([1., 1.]).unwrap().vertex;
+    let handle = square.vertices.gen_key(hole).unwrap();
+    square.check_apply(ops::Kvh { vertex: hole }).ok().unwrap();
+    let kef = square
+        .check_apply(ops::Mef {
+            vertices: [a, c],
+            data: ((), (), ()),
+        })
+        .ok()
+        .unwrap();
+    square.check_apply(kef).ok().unwrap();
+
+    let json = serde_json::to_string(&square).unwrap();
+    let mut copy: Dcel<TestFlavor> = serde_json::from_str(&json).unwrap();
+    let report = copy.validate();
+    assert!(report.is_valid(), "{report}");
+    // the copy is a dcel of its own
+    assert!(!dcel::PointLocator::new(&square).is_current(&copy));
+
+    for (face, key) in square.faces.iter() {
+        assert_eq!(copy.face(key).edge, face.edge);
+        let edges: Vec<_> = Traverser::through(&square, face.edge).unwrap().collect();
+        let copied: Vec<_> = Traverser::through(&copy, face.edge).unwrap().collect();
+        assert_eq!(edges, copied);
+    }
+    for (vertex, key) in square.vertices.iter() {
+        assert_eq!(copy.vertex(key).weight, vertex.weight);
+    }
+
+    // freed slots are handed out in the same order
+    assert_eq!(
+        square.mvh([1., 1.]).unwrap().vertex,
+        copy.mvh([1., 1.]).unwrap().vertex
+    );
+    assert!(copy.vertices.is_stale(handle));
+    let arena = r#"{"slots":[null,1],"free":[1]}"#;
+    assert!(serde_json::from_str::<crate::arena::Arena<u32>>(arena).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_commands_over_the_wire() {
+    let [mut r0, mut r1] = [0, 1].map(|replica| ops::CrdtDcel::new(make_square().0, replica));
+    let [a, b, c, _] = make_square().1;
+
+    let edge = edge_between(r0.dcel(), a, b);
+    let commands = [
+        r0.edit(ops::Mef {
+            vertices: [a, c],
+            data: ((), (), ()),
+        }),
+        r0.edit(ops::Mev::new(edge, [0., -2.], [(), ()])),
+    ]
+    .map(|command| serde_json::to_string(&command.ok().unwrap()).unwrap());
+
+    for json in commands.iter().rev() {
+        r1.receive(serde_json::from_str(json).unwrap())
+            .ok()
+            .unwrap();
+    }
+    assert!(crdt_snapshot(&r0) == crdt_snapshot(&r1));
+
+    let op: ops::Op<TestFlavor> = ops::RotateFace {
+        faces: vec![square_faces(r0.dcel())[1]],
+        center: [0., 0.],
+        angle: 1.,
+    }
+    .into();
+    let json = serde_json::to_string(&op).unwrap();
+    let op: ops::Op<TestFlavor> = serde_json::from_str(&json).unwrap();
+    assert!(matches!(op, ops::Op::RotateFace(rotate) if rotate.angle == 1.));
+}
+
+#[test]
+fn generational_keys_go_stale() {
+    let (mut square, _) = make_square();
+    let hole = square.mvh([1., 1.]).unwrap().vertex;
+    let handle = square.vertices.gen_key(hole).unwrap();
+    assert_eq!(square.vertices[handle].weight, [1., 1.]);
+
+    square.check_apply(ops::Kvh { vertex: hole }).ok().unwrap();
+    let reused = square.mvh([-1., 1.]).unwrap().vertex;
+    // the slot is filled again, a plain key aliases the new vertex but the handle does not
+    assert_eq!(reused, hole);
+    assert_eq!(square.vertices[hole].weight, [-1., 1.]);
+    assert!(square.vertices.is_stale(handle));
+    assert!(square.vertices.get(handle).is_none());
+    assert!(square.vertices.remove(handle).is_none());
+    assert_eq!(square.vertices.generation(hole), Some(1));
+    assert!(
+        !square
+            .vertices
+            .is_stale(square.vertices.gen_key(reused).unwrap())
+    );
+}
+
+#[test]
+#[should_panic(expected = "is stale, its slot is at generation 1")]
+fn indexing_with_a_stale_key_panics() {
+    let (mut square, _) = make_square();
+    let handle = square.vertices.insert_gen(dcel::Vertex {
+        inner: dcel::VertexPtrs { edge: None },
+        weight: [1., 1.],
+    });
+    square.vertices.remove(handle);
+    let _ = &square.vertices[handle];
+}
+
+#[test]
+fn secondary_storage_follows_the_dcel() {
+    let (mut square, corners) = make_square();
+    let [_, inner] = square_faces(&square);
+    let hole = square.mvh([1., 1.]).unwrap().vertex;
+
+    let mut labels = SecondaryMap::<VertexKey, &str>::new();
+    let mut selected = ComponentStore::<VertexKey, bool>::new();
+    for (i, corner) in corners.into_iter().enumerate() {
+        labels.insert(&square.vertices, corner, ["a", "b", "c", "d"][i]);
+        selected.insert(&square.vertices, corner, i % 2 == 0);
+    }
+    labels.insert(&square.vertices, hole, "hole");
+    selected.insert(&square.vertices, hole, true);
+    let mut styles = ComponentStore::<FaceKey, u32>::new();
+    styles.insert(&square.faces, inner, 7);
+
+    assert_eq!(labels.get(&square.vertices, hole), Some(&"hole"));
+    assert_eq!(
+        labels.insert(&square.vertices, hole, "isolated"),
+        Some("hole")
+    );
+    assert_eq!(selected.len(&square.vertices), 5);
+
+    // once the hole is gone, and its slot is taken by another vertex, its entries are ignored
+    square.check_apply(ops::Kvh { vertex: hole }).ok().unwrap();
+    let reused = square.mvh([-1., 1.]).unwrap().vertex;
+    assert_eq!(reused, hole);
+    assert!(labels.get(&square.vertices, reused).is_none());
+    assert!(selected.get(&square.vertices, reused).is_none());
+    assert_eq!(labels.len(&square.vertices), 4);
+    assert!(
+        selected
+            .iter(&square.vertices)
+            .all(|(key, _)| key != reused)
+    );
+
+    selected.retain(&square.vertices, |_, selected| *selected);
+    assert_eq!(selected.len(&square.vertices), 2);
+    assert_eq!(selected.remove(&square.vertices, corners[0]), Some(true));
+    assert_eq!(
+        selected
+            .iter(&square.vertices)
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>(),
+        vec![corners[2]]
+    );
+    for (_, label) in labels.iter_mut(&square.vertices) {
+        *label = "corner";
+    }
+    assert_eq!(labels.get(&square.vertices, corners[3]), Some(&"corner"));
+    assert_eq!(styles.get(&square.faces, inner), Some(&7));
+}
+
+#[test]
+fn compact_keeps_the_structure() {
+    for spatial in [false, true] {
+        let (mut ring, _, [a, _, c, _]) = make_nested_ring();
+        // leave holes in every arena
+        for point in [[1.5, 1.5], [-1.5, -1.5]] {
+            let hole = ring.mvh(point).unwrap().vertex;
+            ring.check_apply(ops::Kvh { vertex: hole }).ok().unwrap();
+        }
+        let kef = ring
+            .check_apply(ops::Mef {
+                vertices: [a, c],
+                data: ((), (), ()),
+            })
+            .ok()
+            .unwrap();
+        ring.check_apply(kef).ok().unwrap();
+        let hole = ring.mvh([1.5, 0.]).unwrap().vertex;
+        let mut kvh: ops::Op<TestFlavor> = ops::Kvh { vertex: hole }.into();
+
+        let before = shape_of(&ring);
+        let mut remap = if spatial {
+            ring.compact_spatial()
+        } else {
+            ring.compact()
+        };
+        assert_eq!(shape_of(&ring), before);
+        let report = ring.validate();
+        assert!(report.is_valid(), "{report}");
+        assert_eq!(ring.vertices.size(), 9);
+        assert_eq!(ring.edges.size(), 16);
+        assert_eq!(ring.faces.size(), 3);
+
+        // keys kept outside the dcel follow it through the remap
+        ops::VisitKeys::visit_keys(&mut kvh, &mut remap);
+        ring.check_apply(kvh).ok().unwrap();
+        assert_eq!(ring.vertices.iter().count(), 8);
+    }
+}
+
+#[test]
+fn bit_mask_as_a_visit_set() {
+    let (mut ring, inner, [a, ..]) = make_nested_ring();
+    let mut ring_edges = ArenaBitMask::new(&ring.edges);
+    assert_eq!(ring_edges.len(), ring.edges.size());
+    for edge in Traverser::through(&ring, ring.face(inner).edge).unwrap() {
+        assert!(ring_edges.set(edge));
+    }
+    assert_eq!(ring_edges.count(), 4);
+    assert!(!ring_edges.set(ring.face(inner).edge));
+
+    let around_a: ArenaBitMask<EdgeKey> = Traverser::around(&ring, a)
+        .unwrap()
+        .flat_map(|edge| [edge, edge.twin(&ring)])
+        .collect();
+    let both = ring_edges.intersection(&around_a);
+    assert_eq!(both.count(), 2);
+    assert!(both.iter_set().all(|edge| ring.edge(edge).face == inner));
+    assert_eq!(ring_edges.union(&around_a).count(), 6);
+
+    // the arena grows past the mask, which grows with it
+    let (start, end) = (ring.vertices.size(), ring.mvh([1.5, 0.]).unwrap().vertex);
+    let mut vertices = ArenaBitMask::new(&ring.vertices);
+    assert_eq!(vertices.len(), start + 1);
+    vertices.set(end);
+    ring.mvh([0., 1.5]).unwrap();
+    for i in 0..100 {
+        ring.mvh([-1.5, i as f32 / 100.]).unwrap();
+    }
+    let last = ring.vertices.iter().map(|(_, key)| key).last().unwrap();
+    assert!(!vertices.is_flipped(last));
+    vertices.flip(last);
+    vertices.resize_for(&ring.vertices);
+    assert_eq!(vertices.len(), ring.vertices.size());
+    assert_eq!(vertices.iter_set().collect::<Vec<_>>(), vec![end, last]);
+    assert!(vertices.clear(end) && !vertices.clear(end));
+    assert_eq!(vertices.count(), 1);
+}
+
+#[test]
+fn arena_as_a_collection() {
+    let mut arena: Arena<u32> = (1..=5).collect();
+    assert_eq!(arena.len(), 5);
+    let keys: Vec<Key<()>> = arena.keys().collect();
+    assert_eq!(
+        keys.iter().map(|key| key.get()).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5]
+    );
+
+    // keys handed out after collecting follow the collected ones
+    let six = arena.insert(6);
+    assert_eq!((six.get(), arena[six]), (6, 6));
+
+    arena.retain(|_, value| *value % 2 == 0);
+    assert_eq!(arena.values().copied().collect::<Vec<_>>(), [2, 4, 6]);
+    assert!(!arena.contains_key(keys[0]) && arena.contains_key(keys[1]));
+    assert_eq!(arena.generation(keys[0]), Some(1));
+
+    for value in arena.values_mut() {
+        *value *= 10;
+    }
+    for (value, _) in &mut arena {
+        *value += 1;
+    }
+    arena.extend([70, 80]);
+    assert_eq!(arena.len(), 5);
+    assert_eq!(
+        (&arena).into_iter().map(|(value, _)| *value).sum::<u32>(),
+        21 + 41 + 61 + 70 + 80
+    );
+
+    let drained: Vec<_> = arena.drain().collect();
+    assert_eq!(drained.len(), 5);
+    assert!(arena.is_empty() && arena.iter().next().is_none());
+
+    // a drain dropped part way still empties the arena
+    arena.extend([1, 2, 3]);
+    let mut drain = arena.drain();
+    assert!(drain.next().is_some());
+    drop(drain);
+    assert!(arena.is_empty() && arena.iter().next().is_none());
+
+    // a record that points at itself, and a pair that point at each other
+    let mut cycles: Arena<(Key<()>, Key<()>)> = Arena::with_capacity(3);
+    let looped = cycles.insert_with(|key| (key, key));
+    let [a, b] = cycles.insert_many_with(|[a, b]| [(a, b), (b, a)]);
+    assert_eq!(cycles[looped], (looped, looped));
+    assert_eq!((cycles[a].1, cycles[b].1), (b, a));
+    cycles.clear();
+    assert!(cycles.is_empty() && cycles.into_iter().next().is_none());
+
+    let (square, _) = make_square();
+    assert_eq!(square.edges.len(), 8);
+    assert_eq!(square.vertices.len(), 4);
+}
+
+#[test]
+fn geographic_coordinates_keep_f64() {
+    // a square a centimetre wide in degrees, far from the origin, all of it inside one f32 step
+    let [x, y] = [13.404954, 52.520008];
+    let d = 1e-7;
+    let corners = [[x, y], [x + d, y], [x + d, y - d], [x, y - d]];
+    assert_eq!([x as f32, y as f32], [(x + d) as f32, (y - d) as f32]);
+
+    let (mut draw, [a, _]) = Draw::new(Dcel::<GeoFlavor>::default(), corners[0], corners[1]);
+    draw.line_to(corners[2]);
+    draw.line_to(corners[3]);
+    let square = draw.close_path(a);
+    let mut dcel = draw.finish();
+    assert!(dcel.validate().is_valid());
+
+    let area = dcel.face_signed_area(square).unwrap();
+    assert!(area < 0. && (area + d * d).abs() < 1e-6 * d * d);
+    let path = dcel.face_path(square).unwrap();
+    assert!(path.chunks(2).all(|p| corners.contains(&[p[0], p[1]])));
+
+    let inside = dcel.mvh([x + d / 2., y - d / 2.]).unwrap().vertex;
+    assert_eq!(dcel.face(square).holes, vec![HolRef::Vertex(inside)]);
+}
+
+#[test]
+fn integer_grid_snaps() {
+    let (mut draw, [a, b]) = Draw::new(Dcel::<GridFlavor>::default(), [0, 0], [10, 0]);
+    draw.line_to([10, -10]);
+    draw.line_to([0, -10]);
+    let square = draw.close_path(a);
+    let mut grid = draw.finish();
+    assert_eq!(grid.face_signed_area(square).unwrap(), -100.);
+
+    // moves land on the nearest grid point
+    grid.translate_vertex(b, [2.6, -0.4]).unwrap();
+    assert_eq!(grid.vertex(b).weight, [13, 0]);
+    grid.scale_face(vec![square], [0., 0.], [0.25, 0.25])
+        .unwrap();
+    assert_eq!(grid.vertex(b).weight, [3, 0]);
+    assert!(grid.validate().is_valid());
+}
+
+#[test]
+fn undo_restores_grid_points_exactly() {
+    let (mut draw, [a, b]) = Draw::new(Dcel::<SmallGridFlavor>::default(), [0, 0], [10, 0]);
+    draw.line_to([10, -10]);
+    draw.line_to([0, -10]);
+    let square = draw.close_path(a);
+    let mut grid = draw.finish();
+    let before: Vec<_> = grid.vertices.iter().map(|(v, _)| v.weight).collect();
+
+    let inverse = grid.rotate_face(vec![square], [3., -4.], 0.3).unwrap();
+    let rotated: Vec<_> = grid.vertices.iter().map(|(v, _)| v.weight).collect();
+    assert_ne!(rotated, before);
+    assert_eq!(grid.vertex(b).weight, [9, 2]);
+
+    // rotating back would round a second time, undo puts every point back where it was
+    let redo = grid.check_apply(inverse).ok().unwrap();
+    let after: Vec<_> = grid.vertices.iter().map(|(v, _)| v.weight).collect();
+    assert_eq!(after, before);
+    grid.check_apply(redo).ok().unwrap();
+    let again: Vec<_> = grid.vertices.iter().map(|(v, _)| v.weight).collect();
+    assert_eq!(again, rotated);
+    assert!(grid.validate().is_valid());
+
+    // a restore to vertices that are gone is refused
+    let restore = ops::RestoreRegion {
+        faces: vec![square],
+        affine: ops::Affine::IDENTITY,
+        positions: vec![(Key::new(99).unwrap(), [0., 0., 0.])],
+    };
+    let err = grid.check_apply(restore).err().unwrap();
+    assert!(matches!(err.err, ops::TransformError::VertexDoesNotExist));
+}
+
+#[test]
+fn point_location() {
+    let (mut ring, inside, [a, b, c, _]) = make_nested_ring();
+    let [outside, between] = square_faces(&make_square().0);
+    let locator = dcel::PointLocator::new(&ring);
+
+    for (point, expected) in [
+        ([0., 0.], dcel::Location::Face(inside)),
+        ([1.5, 0.], dcel::Location::Face(between)),
+        ([3., 0.], dcel::Location::Face(outside)),
+        ([-1., -1.], dcel::Location::Vertex(a)),
+        ([0., -1.], dcel::Location::Edge(edge_between(&ring, a, b))),
+        ([1., 0.], dcel::Location::Edge(edge_between(&ring, b, c))),
+    ] {
+        assert_eq!(ring.locate(point), Some(expected), "{point:?}");
+        assert_eq!(
+            locator.locate(&ring, point).ok(),
+            Some(Some(expected)),
+            "{point:?}"
+        );
+    }
+
+    // the walk and the slabs agree everywhere, on and off the vertices and edges
+    let hourglass = make_hourglass();
+    for dcel in [&ring, &hourglass] {
+        let locator = dcel::PointLocator::new(dcel);
+        for x in -20..=20 {
+            for y in -20..=20 {
+                let point = [x as f32 / 4., y as f32 / 4.];
+                let slabs = locator.locate(dcel, point).ok().unwrap();
+                assert_eq!(dcel.locate(point), slabs, "{point:?}");
+            }
+        }
+    }
+
+    // the slabs refuse to answer for a dcel edited since, until they are built again
+    let mut locator = dcel::PointLocator::new(&ring);
+    ring.mvh([0.5, 0.5]).ok().unwrap();
+    assert!(matches!(
+        locator.locate(&ring, [0., 0.]),
+        Err(dcel::LocateError::Stale { .. })
+    ));
+    locator.update(&ring);
+    assert_eq!(
+        locator.locate(&ring, [0., 0.]).ok(),
+        Some(Some(dcel::Location::Face(inside)))
+    );
+
+    // or for another dcel made by the same edits
+    let (mut other, ..) = make_nested_ring();
+    other.mvh([0.5, 0.5]).ok().unwrap();
+    assert!(matches!(
+        locator.locate(&other, [0., 0.]),
+        Err(dcel::LocateError::Stale { .. })
+    ));
+}
+
+#[test]
+fn mvh_places_by_location() {
+    let (mut ring, inside, [a, b, ..]) = make_nested_ring();
+
+    // new vertices land in the face they are in
+    let hole = ring.mvh([0.5, 0.5]).ok().unwrap().vertex;
+    assert_eq!(ring.face(inside).holes, vec![HolRef::Vertex(hole)]);
+    // and not on a vertex that is already there
+    let err = ring.mvh([-1., -1.]).err().unwrap().err;
+    assert!(matches!(err, ops::MvhError::OnVertex(vertex) if vertex == a));
+    // or on an edge
+    let err = ring.mvh([0., -1.]).err().unwrap().err;
+    assert!(matches!(err, ops::MvhError::OnEdge(edge) if edge == edge_between(&ring, a, b)));
+}
+
+#[test]
+fn spatial_index_follows_the_dcel() {
+    use crate::index::{Aabb, SpatialIndex};
+
+    let (mut ring, inside, [a, b, c, _]) = make_nested_ring();
+    let mut index = dcel::SpacialIndex::new(&ring);
+    // 8 vertices, 8 edges and the faces inside the square and the ring
+    assert_eq!(index.len(), 18);
+
+    let assert_fresh = |index: &dcel::SpacialIndex<TestFlavor>, dcel: &Dcel<TestFlavor>| {
+        let fresh = dcel::SpacialIndex::new(dcel);
+        assert_eq!(index.len(), fresh.len());
+        assert!(fresh.iter().all(|entity| index.contains(entity)));
+    };
+
+    let hole = index
+        .apply(&mut ring, ops::Mvh { vertex: [0.5, 0.5] })
+        .ok()
+        .unwrap()
+        .vertex;
+    assert_fresh(&index, &ring);
+    let spoke = index.apply(
+        &mut ring,
+        ops::Mekh {
+            vertices: [c, hole],
+            data: ((), ()),
+        },
+    );
+    assert!(spoke.is_ok());
+    assert_fresh(&index, &ring);
+    let edge = edge_between(&ring, a, b);
+    let split = index
+        .apply(&mut ring, ops::Mev::new(edge, [0., -1.], [(), ()]))
+        .ok()
+        .unwrap();
+    assert_fresh(&index, &ring);
+    let midpoint = split.vertex;
+    index.apply(&mut ring, split).ok().unwrap();
+    assert_fresh(&index, &ring);
+    assert!(index.get(midpoint).is_none());
+    index
+        .apply(
+            &mut ring,
+            ops::TranslateFace {
+                faces: vec![inside],
+                delta: [0.25, 0.25],
+            },
+        )
+        .ok()
+        .unwrap();
+    assert_fresh(&index, &ring);
+
+    // the ring moved, and the boxes of its face with it
+    assert_eq!(
+        index.get(inside),
+        Some(&dcel::Entity::Face(
+            inside,
+            Aabb {
+                min: [-0.75, -0.75],
+                max: [1.25, 1.25]
+            }
+        ))
+    );
+    // b, its two edges and the boxes of the faces on either side
+    let nearest: Vec<_> = index
+        .nearest_neighbors([1.25, -0.75])
+        .into_iter()
+        .map(|entity| entity.key())
+        .collect();
+    assert_eq!(nearest.len(), 5);
+    assert!(nearest.contains(&b.into()));
+    let at_center: Vec<_> = index
+        .locate_all_at_point([0.75, 0.75])
+        .map(|entity| entity.key())
+        .collect();
+    assert!(at_center.contains(&hole.into()));
+    assert!(at_center.contains(&inside.into()));
+    let near_spoke = index
+        .locate_within_distance([1., 1.], 0.01)
+        .filter(|entity| matches!(entity, dcel::Entity::Edge(..)))
+        .count();
+    assert_eq!(near_spoke, 1);
+}
+
+#[test]
+fn insert_crossing_segments() {
+    // a hash sign, crossing in four places around a square in the middle
+    let mut hash = Dcel::<TestFlavor>::default();
+    let (paths, group) = hash
+        .insert_segments([
+            [[0., 1.], [3., 1.]],
+            [[0., 2.], [3., 2.]],
+            [[1., 0.], [1., 3.]],
+            [[2., 0.], [2., 3.]],
+        ])
+        .ok()
+        .unwrap();
+    assert_eq!(paths.iter().map(Vec::len).collect::<Vec<_>>(), [2, 2, 4, 4]);
+    assert_eq!(hash.vertices.len(), 12);
+    assert_eq!(hash.edges.len(), 24);
+    assert_eq!(hash.faces.len(), 2);
+    let report = hash.validate();
+    assert!(report.is_valid(), "{report}");
+    let middle = hash.face_containing([1.5, 1.5]).unwrap();
+    assert_eq!(hash.face_signed_area(middle).unwrap(), -1.);
+
+    // all of it is taken out again as one
+    hash.check_apply(group).ok().unwrap();
+    assert!(hash.vertices.is_empty() && hash.edges.is_empty());
+
+    // along an edge, through a corner and into the middle of the next edge
+    let (mut square, [a, ..]) = make_square();
+    let (path, _) = square.insert_segment([-3., -2.], [0., -2.]).ok().unwrap();
+    assert_eq!(path.len(), 3);
+    assert_eq!(path[1], a);
+    assert_eq!(square.edges.len(), 12);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // a diagonal through two corners cuts the square in two
+    let (mut square, [a, _, c, _]) = make_square();
+    let (path, _) = square.insert_segment([-3., -3.], [3., 3.]).ok().unwrap();
+    assert_eq!(path[1..3], [a, c]);
+    assert_eq!(square.faces.len(), 3);
+    let report = square.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // on a grid the crossing snaps to the closest grid point, and the edges bend to meet there
+    let mut grid = Dcel::<GridFlavor>::default();
+    grid.insert_segment([1, -5], [1, 5]).ok().unwrap();
+    let (path, _) = grid.insert_segment([0, 0], [3, 1]).ok().unwrap();
+    assert_eq!(path.len(), 3);
+    assert_eq!(grid.vertex(path[1]).weight, [1, 0]);
+    let report = grid.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // at a t-junction both crossings round onto the upright, and the crossed edges bend through
+    // the rounded point, while the path also passes the corner of the pixel at the foot of the stem
+    let mut grid = Dcel::<GridFlavor>::default();
+    grid.insert_segment([1, -5], [1, 5]).ok().unwrap();
+    let (stem, _) = grid.insert_segment([1, 0], [3, 5]).ok().unwrap();
+    let (path, _) = grid.insert_segment([3, 2], [0, 0]).ok().unwrap();
+    let points: Vec<_> = path.iter().map(|v| grid.vertex(*v).weight).collect();
+    assert_eq!(points, [[3, 2], [1, 1], [1, 0], [0, 0]]);
+    assert_eq!(path[2], stem[0]);
+    assert!(grid.edge_between(path[1], stem[1]).is_some());
+    let report = grid.validate();
+    assert!(report.is_valid(), "{report}");
+
+    // the diagonals of a pixel cross at a corner of four, which rounds into one of them only
+    let mut grid = Dcel::<GridFlavor>::default();
+    let (rising, _) = grid.insert_segment([-2, -4], [-1, -3]).ok().unwrap();
+    let (falling, _) = grid.insert_segment([-1, -4], [-2, -3]).ok().unwrap();
+    assert_eq!(rising.len(), 2);
+    assert_eq!(falling, [falling[0], rising[0], falling[2]]);
+    assert_eq!(grid.edges.len(), 6);
+    assert_planar(&grid);
+
+    let mut empty = Dcel::<TestFlavor>::default();
+    assert!(matches!(
+        empty.insert_segment([0., f32::NAN], [1., 1.]),
+        Err(dcel::SegmentError::NonFiniteCoordinate)
+    ));
+}
+
+#[test]
+fn insert_segment_after_points() {
+    // a point makes no bounding face, the segment to it has to make one
+    let mut dcel = Dcel::<TestFlavor>::default();
+    let (point, _) = dcel.insert_segment([1., 1.], [1., 1.]).ok().unwrap();
+    let (path, group) = dcel.insert_segment([0., 0.], [1., 1.]).ok().unwrap();
+    assert_eq!(path[1], point[0]);
+    assert_eq!((dcel.vertices.len(), dcel.edges.len()), (2, 2));
+    assert_planar(&dcel);
+    dcel.check_apply(group).ok().unwrap();
+    assert_eq!((dcel.vertices.len(), dcel.edges.len()), (1, 0));
+
+    // and a segment through it is split there
+    let (path, _) = dcel.insert_segment([0., 0.], [2., 2.]).ok().unwrap();
+    assert_eq!(path, [path[0], point[0], path[2]]);
+    assert_planar(&dcel);
+}
+
+#[test]
+fn insert_random_segments() {
+    let mut seed = 0x2545f4914f6cdd1du64;
+    let mut next = |range: i64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (2 * range as u64 + 1)) as i64 - range
+    };
+    for _ in 0..40 {
+        let segments: Vec<[[i64; 2]; 2]> = (0..8)
+            .map(|_| [[next(4), next(4)], [next(4), next(4)]])
+            .collect();
+        let mut grid = Dcel::<GridFlavor>::default();
+        for [a, b] in &segments {
+            grid.insert_segment(*a, *b).ok().unwrap();
+            assert_planar(&grid);
+        }
+        let mut grid = Dcel::<GridFlavor>::default();
+        let (_, group) = grid.insert_segments(segments).ok().unwrap();
+        assert_planar(&grid);
+        grid.check_apply(group).ok().unwrap();
+        assert!(grid.vertices.is_empty() && grid.edges.is_empty());
+
+        // in thirds, so crossings have to be rounded to the nearest float
+        let segments: Vec<[[f32; 2]; 2]> = (0..8)
+            .map(|_| [0; 4].map(|_| next(12) as f32 / 3.))
+            .map(|[ax, ay, bx, by]| [[ax, ay], [bx, by]])
+            .collect();
+        let mut dcel = Dcel::<TestFlavor>::default();
+        let (_, group) = dcel.insert_segments(segments).ok().unwrap();
+        assert_planar(&dcel);
+        dcel.check_apply(group).ok().unwrap();
+        assert!(dcel.vertices.is_empty() && dcel.edges.is_empty());
+    }
+}
+
+#[test]
+fn sweep_builds_what_insertion_builds() {
+    let soups: [Vec<[[f32; 2]; 2]>; 4] = [
+        // a hash sign
+        vec![
+            [[0., 1.], [3., 1.]],
+            [[0., 2.], [3., 2.]],
+            [[1., 0.], [1., 3.]],
+            [[2., 0.], [2., 3.]],
+        ],
+        // a square with a square hole holding a point, a point outside and a loose stick
+        vec![
+            [[-4., -4.], [-4., 4.]],
+            [[-4., 4.], [4., 4.]],
+            [[4., 4.], [4., -4.]],
+            [[4., -4.], [-4., -4.]],
+            [[-1., -1.], [-1., 1.]],
+            [[-1., 1.], [1., 1.]],
+            [[1., 1.], [1., -1.]],
+            [[1., -1.], [-1., -1.]],
+            [[0., 0.], [0., 0.]],
+            [[6., 6.], [6., 6.]],
+            [[5., 0.], [7., 1.]],
+        ],
+        // overlapping along the base of a triangle
+        vec![
+            [[0., 0.], [4., 0.]],
+            [[2., 0.], [6., 0.]],
+            [[1., 0.], [3., 3.]],
+            [[3., 3.], [5., 0.]],
+        ],
+        // four lines through one point
+        vec![
+            [[-2., -2.], [2., 2.]],
+            [[-2., 2.], [2., -2.]],
+            [[-2., 0.], [2., 0.]],
+            [[0., -2.], [0., 2.]],
+        ],
+    ];
+    let shape = |dcel: &Dcel<TestFlavor>| {
+        let mut faces: Vec<_> = dcel
+            .faces
+            .iter()
+            .map(|(face, key)| (dcel.face_signed_area(key).unwrap(), face.holes.len()))
+            .collect();
+        faces.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        (dcel.vertices.len(), dcel.edges.len(), faces)
+    };
+    // none of them cross where a float can't hold it, which is when the two agree
+    for soup in soups {
+        let swept = Dcel::<TestFlavor>::from_segments(soup.clone())
+            .ok()
+            .unwrap();
+        let report = swept.validate();
+        assert!(report.is_valid(), "{report}");
+        let mut inserted = Dcel::<TestFlavor>::default();
+        inserted.insert_segments(soup).ok().unwrap();
+        assert_eq!(shape(&swept), shape(&inserted));
+    }
+
+    // the hole in the square is found where it is
+    let swept = Dcel::<TestFlavor>::from_segments([
+        [[-4., -4.], [-4., 4.]],
+        [[-4., 4.], [4., 4.]],
+        [[4., 4.], [4., -4.]],
+        [[4., -4.], [-4., -4.]],
+        [[0., 0.], [0., 0.]],
+    ])
+    .ok()
+    .unwrap();
+    let Some(dcel::Location::Face(face)) = swept.locate([0., 1.]) else {
+        panic!("the middle of the square is in a face");
+    };
+    assert_eq!(swept.face_signed_area(face).unwrap(), -64.);
+    assert_eq!(swept.face(face).holes.len(), 1);
+
+    assert!(matches!(
+        Dcel::<TestFlavor>::from_segments([[[0., f32::INFINITY], [1., 1.]]]),
+        Err(dcel::SegmentError::NonFiniteCoordinate)
+    ));
+    assert!(
+        Dcel::<TestFlavor>::from_segments([])
+            .ok()
+            .unwrap()
+            .faces
+            .is_empty()
+    );
+
+    // points alone make no face, but every one of them is kept
+    let points = [
+        [[0., 0.], [0., 0.]],
+        [[1., 2.], [1., 2.]],
+        [[0., 0.], [0., 0.]],
+    ];
+    let swept = Dcel::<TestFlavor>::from_segments(points).ok().unwrap();
+    let report = swept.validate();
+    assert!(report.is_valid(), "{report}");
+    let mut inserted = Dcel::<TestFlavor>::default();
+    inserted.insert_segments(points).ok().unwrap();
+    assert_eq!(shape(&swept), shape(&inserted));
+    assert_eq!(swept.vertices.len(), 2);
+    assert!(matches!(
+        swept.locate([1., 2.]),
+        Some(dcel::Location::Vertex(_))
+    ));
+}
+
+#[test]
+fn sweep_random_segments() {
+    let mut seed = 0x9e3779b97f4a7c15u64;
+    let mut next = |range: i64| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % (2 * range as u64 + 1)) as i64 - range
+    };
+    for round in 0..120 {
+        let count = 3 + round % 6;
+        let segments: Vec<[[i64; 2]; 2]> = (0..count)
+            .map(|_| [[next(4), next(4)], [next(4), next(4)]])
+            .collect();
+        let grid = Dcel::<GridFlavor>::from_segments(segments).ok().unwrap();
+        assert_planar(&grid);
+
+        // in thirds, so crossings have to be rounded to the nearest float
+        let segments: Vec<[[f32; 2]; 2]> = (0..count)
+            .map(|_| [0; 4].map(|_| next(12) as f32 / 3.))
+            .map(|[ax, ay, bx, by]| [[ax, ay], [bx, by]])
+            .collect();
+        let swept = Dcel::<TestFlavor>::from_segments(segments).ok().unwrap();
+        assert_planar(&swept);
+    }
+
+    // the first passes a hair from where the other two meet, and crosses both right there
+    let third = 1. / 3.;
+    let swept = Dcel::<TestFlavor>::from_segments([
+        [[2., -2.], [-2., -1.]],
+        [[-2. * third, -4. * third], [third, -third]],
+        [[-2. * third, -4. * third], [1., -4. * third]],
+    ])
+    .ok()
+    .unwrap();
+    assert_planar(&swept);
+}
+
+#[test]
+fn build_from_indexed_polygons() {
+    // a two by two grid of squares, counterclockwise
+    let grid: Vec<[f32; 2]> = (0..9).map(|i| [(i % 3) as f32, (i / 3) as f32]).collect();
+    let cells = [[0, 1, 4, 3], [1, 2, 5, 4], [3, 4, 7, 6], [4, 5, 8, 7]];
+    let dcel = Dcel::<TestFlavor>::from_polygons(&grid, &cells).unwrap();
+    assert_eq!(dcel.vertices.len(), 9);
+    assert_eq!(dcel.edges.len(), 24);
+    assert_eq!(dcel.faces.len(), 5);
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    let Some(dcel::Location::Face(cell)) = dcel.locate([1.5, 0.5]) else {
+        panic!("the cell is a face");
+    };
+    assert_eq!(dcel.face_signed_area(cell).unwrap(), -1.);
+
+    // written out clockwise, and built again the same
+    let (vertices, polygons) = dcel.to_polygons().unwrap();
+    assert_eq!(vertices, grid);
+    assert_eq!(polygons.len(), 4);
+    for (polygon, cell) in polygons.iter().zip(cells) {
+        let mut clockwise: Vec<u32> = cell.iter().rev().copied().collect();
+        let first = clockwise.iter().position(|v| *v == polygon[0]).unwrap();
+        clockwise.rotate_left(first);
+        assert_eq!(*polygon, clockwise);
+    }
+    let again = Dcel::<TestFlavor>::from_polygons(&vertices, &polygons).unwrap();
+    assert_eq!(again.edges.len(), 24);
+    assert!(again.validate().is_valid());
+
+    // a ring of squares around a gap, with a triangle and a point inside the gap
+    let mut points: Vec<[f32; 2]> = (0..16).map(|i| [(i % 4) as f32, (i / 4) as f32]).collect();
+    points.extend([[1.2, 1.2], [1.8, 1.2], [1.5, 1.8], [1.2, 1.7]]);
+    let ring: Vec<Vec<u32>> = [0, 1, 2, 4, 6, 8, 9, 10]
+        .into_iter()
+        .map(|i| vec![i, i + 1, i + 5, i + 4])
+        .chain([vec![16, 17, 18]])
+        .collect();
+    let dcel = Dcel::<TestFlavor>::from_polygons(&points, &ring).unwrap();
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    // the eight squares, the triangle, the gap and the bounding face
+    assert_eq!(dcel.faces.len(), 11);
+    let Some(dcel::Location::Face(gap)) = dcel.locate([1.1, 1.9]) else {
+        panic!("the gap is a face");
+    };
+    assert_eq!(dcel.face_signed_area(gap).unwrap(), -1.);
+    assert_eq!(dcel.face(gap).holes.len(), 2);
+    assert!(matches!(
+        dcel.to_polygons(),
+        Err(dcel::PolygonError::FaceWithHole { polygon: 9 })
+    ));
+
+    // two triangles meeting in a single corner
+    let bowtie = [[0., 0.], [1., 1.], [0., 2.], [2., 0.], [2., 2.]];
+    let dcel = Dcel::<TestFlavor>::from_polygons(&bowtie, &[[0, 1, 2], [1, 3, 4]]).unwrap();
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(dcel.faces.len(), 3);
+
+    use dcel::PolygonError::*;
+    let square = [[0., 0.], [1., 0.], [1., 1.], [0., 1.], [f32::NAN, 0.]];
+    let build = |polygons: &[Vec<u32>]| Dcel::<TestFlavor>::from_polygons(&square[..4], polygons);
+    assert_eq!(
+        Dcel::<TestFlavor>::from_polygons(&square, &[[0, 1, 2]]).err(),
+        Some(NonFiniteCoordinate { vertex: 4 })
+    );
+    assert_eq!(
+        build(&[vec![0, 1]]).err(),
+        Some(TooFewVertices { polygon: 0 })
+    );
+    assert_eq!(
+        build(&[vec![0, 1, 2], vec![0, 2, 7]]).err(),
+        Some(VertexOutOfRange {
+            polygon: 1,
+            vertex: 7
+        })
+    );
+    assert_eq!(
+        build(&[vec![0, 1, 2, 1]]).err(),
+        Some(RepeatedVertex {
+            polygon: 0,
+            vertex: 1
+        })
+    );
+    assert_eq!(
+        Dcel::<TestFlavor>::from_polygons(&[[0., 0.], [1., 0.], [2., 0.]], &[[0, 1, 2]]).err(),
+        Some(ZeroArea { polygon: 0 })
+    );
+    assert_eq!(
+        build(&[vec![0, 1, 2], vec![0, 2, 3]])
+            .ok()
+            .map(|dcel| dcel.faces.len()),
+        Some(3)
+    );
+    assert_eq!(
+        build(&[vec![0, 1, 2], vec![0, 3, 2]]).err(),
+        Some(InconsistentOrientation { polygon: 1 })
+    );
+    assert_eq!(
+        build(&[vec![0, 1, 2], vec![0, 1, 3]]).err(),
+        Some(NonManifoldEdge {
+            edge: [0, 1],
+            polygons: [0, 1]
+        })
+    );
+}
+
+#[test]
+fn overlay_and_boolean_operations() {
+    let polygon = |corners: &[[f32; 2]], label: &'static str| {
+        let cell: Vec<u32> = (0..corners.len() as u32).collect();
+        let mut dcel = Dcel::<LabelFlavor>::from_polygons(corners, &[cell]).unwrap();
+        let face = dcel
+            .faces
+            .keys()
+            .find(|face| dcel.face(*face).is_bounding());
+        dcel.face_mut(face.unwrap()).weight = label;
+        dcel
+    };
+    let square = |[x, y]: [f32; 2], size: f32, label: &'static str| {
+        let corners = [[x, y], [x + size, y], [x + size, y + size], [x, y + size]];
+        polygon(&corners, label)
+    };
+    let area = |overlay: &dcel::Overlay<LabelFlavor>, faces: Vec<Key<FaceKey>>| -> f64 {
+        faces
+            .into_iter()
+            .map(|face| overlay.dcel.face_signed_area(face).unwrap())
+            .sum()
+    };
+    let merge = |a: Option<&&'static str>, b: Option<&&'static str>| match (a, b) {
+        (Some(a), Some(b)) => {
+            if *a == "parcel" && *b == "zone" {
+                "both"
+            } else {
+                "?"
+            }
+        }
+        (Some(a), None) | (None, Some(a)) => *a,
+        (None, None) => "",
+    };
+
+    // two squares overlapping in a corner
+    let parcels = square([0., 0.], 2., "parcel");
+    let zoning = square([1., 1.], 2., "zone");
+    let overlay = parcels.overlay(&zoning, merge).ok().unwrap();
+    let report = overlay.dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(overlay.dcel.faces.len(), 4);
+    assert_eq!(area(&overlay, overlay.union()), -7.);
+    assert_eq!(area(&overlay, overlay.intersection()), -1.);
+    assert_eq!(area(&overlay, overlay.difference()), -3.);
+    assert_eq!(area(&overlay, overlay.symmetric_difference()), -6.);
+    let [both] = overlay.intersection()[..] else {
+        panic!("the squares meet in one face");
+    };
+    assert_eq!(overlay.dcel.face(both).weight, "both");
+    let [parcel, zone] = overlay.sources(both).map(Option::unwrap);
+    assert_eq!(parcels.face(parcel).weight, "parcel");
+    assert_eq!(zoning.face(zone).weight, "zone");
+    assert!(
+        overlay
+            .select(dcel::BooleanOp::Union, |_| false, |face| face == zone)
+            .iter()
+            .all(|face| overlay.sources(*face)[1] == Some(zone))
+    );
+
+    // a zone inside a parcel, touching nothing, leaves the parcel with a hole
+    let zoning = square([1., 1.], 1., "zone");
+    let parcels = square([0., 0.], 4., "parcel");
+    let overlay = parcels.overlay(&zoning, merge).ok().unwrap();
+    let report = overlay.dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(area(&overlay, overlay.intersection()), -1.);
+    let [rest] = overlay.difference()[..] else {
+        panic!("the parcel is left with one face");
+    };
+    assert_eq!(overlay.dcel.face(rest).holes.len(), 1);
+    assert_eq!(overlay.dcel.face(rest).weight, "parcel");
+
+    // side by side, sharing an edge
+    let parcels = square([0., 0.], 1., "parcel");
+    let zoning = square([1., 0.], 1., "zone");
+    let overlay = parcels.overlay(&zoning, merge).ok().unwrap();
+    assert_eq!(overlay.dcel.edges.len(), 14);
+    assert!(overlay.intersection().is_empty());
+    assert_eq!(area(&overlay, overlay.union()), -2.);
+    assert_eq!(area(&overlay, overlay.symmetric_difference()), -2.);
+
+    // crossings a float can't hold are rounded, and every face still gets the label of where it is
+    let labelled = |overlay: &dcel::Overlay<LabelFlavor>, parcels: &Dcel<LabelFlavor>, zoning| {
+        let report = overlay.dcel.validate();
+        assert!(report.is_valid(), "{report}");
+        overlay.dcel.faces.keys().all(|face| {
+            let [parcel, zone] = overlay.sources(face);
+            let [a, b] = [(parcel, parcels), (zone, zoning)]
+                .map(|(source, dcel)| source.map(|source| &dcel.face(source).weight));
+            overlay.dcel.face(face).weight == merge(a, b)
+        })
+    };
+    let label_at = |overlay: &dcel::Overlay<LabelFlavor>, point| match overlay.dcel.locate(point) {
+        Some(dcel::Location::Face(face)) => overlay.dcel.face(face).weight,
+        _ => panic!("the point is in a face"),
+    };
+    let parcels = square([0., 0.], 1., "parcel");
+    let zoning = polygon(&[[-1., 0.5], [2., 0.6], [0.5, 2.]], "zone");
+    let overlay = parcels.overlay(&zoning, merge).ok().unwrap();
+    assert!(labelled(&overlay, &parcels, &zoning));
+    assert_eq!(label_at(&overlay, [0.5, 0.9]), "both");
+    assert_eq!(label_at(&overlay, [0.5, 0.2]), "parcel");
+    assert_eq!(label_at(&overlay, [0.5, 1.5]), "zone");
+    assert_eq!(label_at(&overlay, [1.5, 0.2]), "");
+
+    // a side of the zone passes a hair from a corner of the parcel, crossing both sides there
+    let third = 1. / 3.;
+    let parcels = polygon(
+        &[
+            [-2. * third, -4. * third],
+            [1., -4. * third],
+            [third, -third],
+        ],
+        "parcel",
+    );
+    let zoning = polygon(&[[2., -2.], [0., 2.], [-2., -1.]], "zone");
+    let overlay = parcels.overlay(&zoning, merge).ok().unwrap();
+    assert!(labelled(&overlay, &parcels, &zoning));
+    assert_eq!(label_at(&overlay, [2. * third / 3., -1.]), "both");
+    assert!((area(&overlay, overlay.intersection()) + 5. / 6.).abs() < 1e-5);
+}
+
+#[test]
+fn triangulate_faces() {
+    let area = |dcel: &Dcel<TestFlavor>, [a, b, c]: [Key<VertexKey>; 3]| {
+        let [a, b, c] = [a, b, c].map(|v| dcel.vertex(v).weight);
+        crate::coord::orient2d(a, b, c) / 2.
+    };
+    let all_triangles = |dcel: &Dcel<TestFlavor>| {
+        dcel.faces
+            .keys()
+            .filter(|face| dcel.face(*face).is_bounding())
+            .all(|face| {
+                Traverser::through(dcel, dcel.face(face).edge)
+                    .unwrap()
+                    .count()
+                    == 3
+            })
+    };
+
+    // a comb, concave and with corners in a straight line
+    let comb = [
+        [0., 0.],
+        [2., 0.],
+        [4., 0.],
+        [4., 3.],
+        [3., 3.],
+        [3., 1.],
+        [2., 1.],
+        [2., 3.],
+        [1., 3.],
+        [1., 1.],
+        [0., 1.],
+    ];
+    let mut dcel =
+        Dcel::<TestFlavor>::from_polygons(&comb, &[(0..11).collect::<Vec<u32>>()]).unwrap();
+    let face = dcel
+        .faces
+        .keys()
+        .find(|face| dcel.face(*face).is_bounding())
+        .unwrap();
+    let triangles = dcel.face_triangles(face).ok().unwrap();
+    assert_eq!(triangles.len(), 9);
+    let total: f64 = triangles
+        .iter()
+        .map(|triangle| area(&dcel, *triangle))
+        .sum();
+    assert_eq!(total, -8.);
+    assert!(triangles.iter().all(|triangle| area(&dcel, *triangle) < 0.));
+
+    let group = dcel.triangulate_face(face).ok().unwrap();
+    assert_eq!(dcel.faces.len(), 10);
+    assert!(all_triangles(&dcel));
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    dcel.check_apply(group).ok().unwrap();
+    assert_eq!(dcel.faces.len(), 2);
+    assert_eq!(dcel.edges.len(), 22);
+
+    // a square with a square hole and a loose point between them
+    let mut dcel = Dcel::<TestFlavor>::from_segments([
+        [[-4., -4.], [-4., 4.]],
+        [[-4., 4.], [4., 4.]],
+        [[4., 4.], [4., -4.]],
+        [[4., -4.], [-4., -4.]],
+        [[-1., -1.], [-1., 1.]],
+        [[-1., 1.], [1., 1.]],
+        [[1., 1.], [1., -1.]],
+        [[1., -1.], [-1., -1.]],
+        [[2.5, 0.], [2.5, 0.]],
+    ])
+    .ok()
+    .unwrap();
+    let Some(dcel::Location::Face(around)) = dcel.locate([3., 3.]) else {
+        panic!("the square is a face");
+    };
+    let triangles = dcel.face_triangles(around).ok().unwrap();
+    assert_eq!(triangles.len(), 10);
+    let total: f64 = triangles
+        .iter()
+        .map(|triangle| area(&dcel, *triangle))
+        .sum();
+    assert_eq!(total, -60.);
+
+    dcel.triangulate_all().ok().unwrap();
+    assert!(all_triangles(&dcel));
+    assert_eq!(dcel.faces.len(), 13);
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    assert!(
+        dcel.faces
+            .iter()
+            .all(|(face, _)| face.holes.is_empty() || !face.is_bounding())
+    );
+
+    // an index buffer, with the ring in the square and a dangling edge between two triangles
+    for dcel in [make_nested_ring().0, make_hourglass()] {
+        let (vertices, indices) = dcel.triangle_buffer().ok().unwrap();
+        assert_eq!(vertices.len(), dcel.vertices.len());
+        assert_eq!(indices.len() % 3, 0);
+        let covered: f64 = indices
+            .chunks(3)
+            .map(|t| {
+                crate::coord::orient2d(
+                    vertices[t[0] as usize],
+                    vertices[t[1] as usize],
+                    vertices[t[2] as usize],
+                ) / 2.
+            })
+            .sum();
+        let outer: f64 = dcel
+            .faces
+            .keys()
+            .filter(|face| dcel.face(*face).is_bounding())
+            .map(|face| dcel.face_signed_area(face).unwrap())
+            .sum();
+        // holes are counted twice by the outer boundaries, once by the triangles
+        let holes: f64 = dcel
+            .faces
+            .iter()
+            .filter(|(face, _)| face.is_bounding())
+            .flat_map(|(face, _)| face.holes.clone())
+            .map(|hole| match hole {
+                HolRef::Edge(edge) => Traverser::signed_area(&dcel, edge).unwrap(),
+                HolRef::Vertex(_) => 0.,
+            })
+            .sum();
+        assert_eq!(covered, outer + holes);
+    }
+
+    // without the loose point the square and its hole go through y-monotone pieces
+    let mut dcel = Dcel::<TestFlavor>::from_segments([
+        [[-4., -4.], [-4., 4.]],
+        [[-4., 4.], [4., 4.]],
+        [[4., 4.], [4., -4.]],
+        [[4., -4.], [-4., -4.]],
+        [[-1., -1.], [-1., 1.]],
+        [[-1., 1.], [1., 1.]],
+        [[1., 1.], [1., -1.]],
+        [[1., -1.], [-1., -1.]],
+    ])
+    .ok()
+    .unwrap();
+    let Some(dcel::Location::Face(around)) = dcel.locate([3., 3.]) else {
+        panic!("the square is a face");
+    };
+    let triangles = dcel.face_triangles(around).ok().unwrap();
+    assert_eq!(triangles.len(), 8);
+    assert!(triangles.iter().all(|triangle| area(&dcel, *triangle) < 0.));
+    let total: f64 = triangles
+        .iter()
+        .map(|triangle| area(&dcel, *triangle))
+        .sum();
+    assert_eq!(total, -60.);
+    dcel.triangulate_all().ok().unwrap();
+    assert!(all_triangles(&dcel));
+    assert_eq!(dcel.faces.len(), 11);
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+
+    let (square, _) = make_square();
+    let bounding = square
+        .faces
+        .keys()
+        .find(|face| !square.face(*face).is_bounding());
+    assert!(matches!(
+        square.face_triangles(bounding.unwrap()),
+        Err(dcel::TriangulationError::BoundingFace)
+    ));
+
+    // a hole the face does not hold has nothing to be bridged to
+    let (mut square, _) = make_square();
+    let inner = square
+        .faces
+        .keys()
+        .find(|face| square.face(*face).is_bounding())
+        .unwrap();
+    let ops::Kvh { vertex } = square
+        .check_apply(ops::Mvh { vertex: [10., 10.] })
+        .ok()
+        .unwrap();
+    square.face_mut(inner).holes.push(HolRef::Vertex(vertex));
+    assert!(matches!(
+        square.face_triangles(inner),
+        Err(dcel::TriangulationError::HoleNotBridged)
+    ));
+}
+
+#[test]
+fn validate_shapes() {
+    let (square, _) = make_square();
+    assert!(square.validate().is_valid());
+    assert!(make_hourglass().validate().is_valid());
+    assert!(Dcel::<TestFlavor>::default().validate().is_valid());
+}
+
+#[test]
+fn validate_reports_broken_pointers() {
+    let (mut square, [a, b, c, _]) = make_square();
+    let edge = edge_between(&square, a, b);
+    let other = edge_between(&square, b, c);
+    square.edge_mut(edge).twin = other;
+
+    let report = square.validate();
+    assert!(!report.is_valid());
+    assert!(
+        report
+            .violations
+            .contains(&dcel::Violation::TwinMismatch { edge })
+    );
+}
+
+#[test]
+fn validate_reports_broken_faces() {
+    let (mut square, [a, b, ..]) = make_square();
+    let [outer, inner] = square_faces(&square);
+    let edge = edge_between(&square, a, b);
+    square.edge_mut(edge).face = if edge.face(&square) == inner {
+        outer
+    } else {
+        inner
+    };
+
+    let report = square.validate();
+    assert!(
+        report
+            .violations
+            .iter()
+            .any(|v| matches!(v, dcel::Violation::CycleFaceMismatch { .. }))
+    );
+
+    let (mut square, [a, b, ..]) = make_square();
+    let v = square.mvh([0., 0.]).unwrap().vertex;
+    square.vertex_mut(v).edge = Some(edge_between(&square, a, b));
+    let report = square.validate();
+    assert!(
+        report
+            .violations
+            .iter()
+            .any(|v| matches!(v, dcel::Violation::VertexEdgeNotOutgoing { .. }))
+    );
+}
+
+/// every vertex position and every edge's origin, twin and next, by their replicated ids
+#[allow(clippy::type_complexity)]
+fn crdt_snapshot(
+    crdt: &ops::CrdtDcel<TestFlavor>,
+) -> (
+    Vec<(ops::ElementId, [f32; 2])>,
+    Vec<(ops::ElementId, [ops::ElementId; 3])>,
+) {
+    let dcel = crdt.dcel();
+    let mut vertices: Vec<_> = dcel
+        .vertices
+        .iter()
+        .map(|(vertex, key)| (crdt.id(key).unwrap(), vertex.weight))
+        .collect();
+    let mut edges: Vec<_> = dcel
+        .edges
+        .iter()
+        .map(|(edge, key)| {
+            let ids = [
+                crdt.id(edge.origin).unwrap(),
+                crdt.id(edge.twin).unwrap(),
+                crdt.id(edge.next).unwrap(),
+            ];
+            (crdt.id(key).unwrap(), ids)
+        })
+        .collect();
+    vertices.sort_by_key(|(id, _)| *id);
+    edges.sort_by_key(|(id, _)| *id);
+    (vertices, edges)
+}
+
+/// every half edge as the positions of its origin, its twin's origin and its next's origin
+fn shape_of(dcel: &Dcel<TestFlavor>) -> Vec<[[f32; 2]; 3]> {
+    let position = |vertex| dcel.vertex(vertex).weight;
+    let mut shape: Vec<_> = dcel
+        .edges
+        .iter()
+        .map(|(edge, _)| {
+            [
+                position(edge.origin),
+                position(dcel.edge(edge.twin).origin),
+                position(dcel.edge(edge.next).origin),
+            ]
+        })
+        .collect();
+    shape.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    shape
+}
+
+fn assert_near([x1, y1]: [f32; 2], [x2, y2]: [f32; 2]) {
+    assert!(
+        (x1 - x2).abs() < 1e-5 && (y1 - y2).abs() < 1e-5,
+        "{:?} != {:?}",
+        [x1, y1],
+        [x2, y2]
+    );
+}
+
+fn edge_between(dcel: &Dcel<TestFlavor>, from: Key<VertexKey>, to: Key<VertexKey>) -> Key<EdgeKey> {
+    Traverser::around(dcel, from)
+        .unwrap()
+        .find(|edge| edge.twin(dcel).origin(dcel) == to)
+        .unwrap()
+}
+
+/*
+
+makes this shape:
+
+   O______O
+   |      |
+   |      |
+   O______O
+
+*/
+fn make_square() -> (Dcel<TestFlavor>, [Key<VertexKey>; 4]) {
+    let (mut draw, [a, b]) = Draw::new(Dcel::default(), [-2., -2.], [2., -2.]);
+    let c = draw.line_to([2., 2.]);
+    let d = draw.line_to([-2., 2.]);
+    draw.close_path(a);
+    (draw.finish(), [a, b, c, d])
+}
+
+/// the unbounded face and the face inside a square
+fn square_faces(square: &Dcel<TestFlavor>) -> [Key<FaceKey>; 2] {
+    let mut faces = square.faces.iter().map(|(_, key)| key);
+    let [outer, inner] = [faces.next().unwrap(), faces.next().unwrap()];
+    if square.face_signed_area(outer).unwrap() < 0. {
+        [inner, outer]
+    } else {
+        [outer, inner]
+    }
+}
+
+/// a square with a smaller square ring inside, and the face inside the ring
+fn make_nested_ring() -> (Dcel<TestFlavor>, Key<FaceKey>, [Key<VertexKey>; 4]) {
+    let (square, _) = make_square();
+    let (mut draw, a) = Draw::start(square, [-1., -1.]);
+    let b = draw.line_to([1., -1.]);
+    let c = draw.line_to([1., 1.]);
+    let d = draw.line_to([-1., 1.]);
+    let ring = draw.close_path(a);
+    (draw.finish(), ring, [a, b, c, d])
+}
+
 /*
 
 makes this shape:
@@ -115,3 +2356,43 @@ makes this shape:
     0    0    0     0
 */
 fn make_grid() {}
+
+/// valid, and drawn without crossings: no two vertices in one place, and no vertex on an edge it
+/// doesn't end
+fn assert_planar<F: Flavor>(dcel: &Dcel<F>)
+where
+    F::Vertex: Coordinate,
+{
+    let report = dcel.validate();
+    assert!(report.is_valid(), "{report}");
+    let point = |vertex: Key<VertexKey>| {
+        let weight = &dcel.vertex(vertex).weight;
+        [weight.x(), weight.y()]
+    };
+    let mut points: Vec<_> = dcel.vertices.iter().map(|(_, v)| point(v)).collect();
+    points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    assert!(
+        points.windows(2).all(|pair| pair[0] != pair[1]),
+        "vertices share a place"
+    );
+    let edges: Vec<_> = dcel
+        .edges
+        .iter()
+        .map(|(_, edge)| [edge.origin(dcel), edge.twin(dcel).origin(dcel)])
+        .collect();
+    for [a, b] in &edges {
+        for (_, vertex) in dcel.vertices.iter() {
+            assert!(
+                vertex == *a || vertex == *b || !on_segment(point(vertex), point(*a), point(*b)),
+                "a vertex is on an edge it doesn't end"
+            );
+        }
+        for [c, d] in &edges {
+            assert!(
+                [a, b].iter().any(|v| [c, d].contains(v))
+                    || !segments_intersect(point(*a), point(*b), point(*c), point(*d)),
+                "two edges cross"
+            );
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Several operators applied as one, rolled back as a whole when any of them fails
+
+use std::fmt::Debug;
+
+use crate::{
+    coord::{Coordinate, UpdateCoordinate},
+    dcel::{
+        Dcel, Flavor, Op, Operator, OperatorErr,
+        ops::{self, OpVariant},
+    },
+};
+
+/// a failed group, with the ops that would apply the whole group again
+pub type GroupErr<F> = OperatorErr<Vec<Op<F>>, ops::Error<F>>;
+
+/// undoing a failed group failed as well. the dcel is left with every op after the one whose
+/// inverse failed undone, and every op before it still applied
+#[derive(thiserror::Error)]
+#[error("could not roll back a failed group of operators")]
+pub struct RollbackErr<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    /// the inverse that could not be applied, and why
+    pub failed: OperatorErr<Op<F>, ops::Error<F>>,
+    /// the inverses still to apply after it, in order
+    pub pending: Vec<Op<F>>,
+}
+
+impl<F: Flavor> Debug for RollbackErr<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl<F: Flavor> From<RollbackErr<F>> for ops::Error<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn from(err: RollbackErr<F>) -> Self {
+        Self::Rollback(Box::new(err))
+    }
+}
+
+/// apply ops in order, returning their inverses in the order they were made. if one fails every
+/// op before it is undone, and the error carries the ops that would apply the whole sequence again.
+/// if undoing fails too, the error is [`ops::Error::Rollback`] and carries the op that failed and
+/// the ones after it
+pub(crate) fn replay<F: Flavor>(
+    dcel: &mut Dcel<F>,
+    ops: Vec<Op<F>>,
+) -> Result<Vec<Op<F>>, GroupErr<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    let mut inverses = vec![];
+    let mut ops = ops.into_iter();
+    while let Some(op) = ops.next() {
+        match dcel.check_apply(op) {
+            Ok(inverse) => inverses.push(inverse),
+            Err(OperatorErr { op, err }) => {
+                let (mut restored, err) = match rollback(dcel, inverses) {
+                    Ok(restored) => (restored, err),
+                    Err(rollback) => (vec![], ops::Error::Rollback(rollback)),
+                };
+                restored.push(op);
+                restored.extend(ops);
+                return Err(OperatorErr { op: restored, err });
+            }
+        }
+    }
+    Ok(inverses)
+}
+
+/// undo inverses made in order, returning the ops that would make them again
+fn rollback<F: Flavor>(
+    dcel: &mut Dcel<F>,
+    mut inverses: Vec<Op<F>>,
+) -> Result<Vec<Op<F>>, Box<RollbackErr<F>>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    let mut restored = vec![];
+    while let Some(inverse) = inverses.pop() {
+        match dcel.check_apply(inverse) {
+            Ok(op) => restored.push(op),
+            Err(failed) => {
+                inverses.reverse();
+                return Err(Box::new(RollbackErr {
+                    failed,
+                    pending: inverses,
+                }));
+            }
+        }
+    }
+    restored.reverse();
+    Ok(restored)
+}
+
+/// A sequence of ops applied in order, as a single operator
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound(
+        serialize = "F::Vertex: serde::Serialize, F::Edge: serde::Serialize, F::Face: serde::Serialize",
+        deserialize = "F::Vertex: serde::Deserialize<'de>, F::Edge: serde::Deserialize<'de>, F::Face: serde::Deserialize<'de>"
+    ))
+)]
+pub struct OpGroup<F: Flavor> {
+    pub ops: Vec<Op<F>>,
+}
+
+impl<F: Flavor> Operator<F> for OpGroup<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    type Error = ops::Error<F>;
+    type Inverse = OpGroup<F>;
+
+    /// only the first op can be checked up front, every later one depends on the ones before it.
+    /// [`OpGroup::apply`] rolls back if any of them fails
+    fn check(&self, dcel: &Dcel<F>) -> Result<(), Self::Error> {
+        match self.ops.first() {
+            Some(op) => op.check(dcel),
+            None => Ok(()),
+        }
+    }
+
+    fn apply(self, dcel: &mut Dcel<F>) -> Result<Self::Inverse, OperatorErr<Self, Self::Error>> {
+        match replay(dcel, self.ops) {
+            Ok(mut inverses) => {
+                inverses.reverse();
+                Ok(OpGroup { ops: inverses })
+            }
+            Err(OperatorErr { op, err }) => Err(OperatorErr {
+                op: OpGroup { ops: op },
+                err,
+            }),
+        }
+    }
+}
+
+/// The ops applied so far in [`Dcel::transaction`]
+pub struct Transaction<'a, F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    dcel: &'a mut Dcel<F>,
+    inverses: Vec<Op<F>>,
+}
+
+impl<F: Flavor> Transaction<'_, F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    pub fn dcel(&self) -> &Dcel<F> {
+        self.dcel
+    }
+
+    /// apply an op, returning its inverse. a failed op leaves the dcel untouched, so the
+    /// transaction can carry on or bail out
+    pub fn apply<O>(&mut self, op: O) -> Result<&O::Inverse, OperatorErr<O, O::Error>>
+    where
+        O: Operator<F>,
+        O::Inverse: OpVariant<F>,
+    {
+        let inverse = self.dcel.check_apply(op)?;
+        self.inverses.push(inverse.into());
+        Ok(O::Inverse::variant(self.inverses.last().unwrap()).unwrap())
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    /// apply ops through `f` as one edit. if `f` fails every op it applied is undone, otherwise
+    /// the group that undoes all of them at once is returned with its result. when undoing fails
+    /// as well, the error is the [`RollbackErr`] instead
+    pub fn transaction<R, E: From<RollbackErr<F>>>(
+        &mut self,
+        f: impl FnOnce(&mut Transaction<'_, F>) -> Result<R, E>,
+    ) -> Result<(R, OpGroup<F>), E> {
+        let mut tx = Transaction {
+            dcel: self,
+            inverses: vec![],
+        };
+        match f(&mut tx) {
+            Ok(res) => {
+                let mut inverses = tx.inverses;
+                inverses.reverse();
+                Ok((res, OpGroup { ops: inverses }))
+            }
+            Err(err) => match rollback(tx.dcel, tx.inverses) {
+                Ok(_) => Err(err),
+                Err(rollback) => Err((*rollback).into()),
+            },
+        }
+    }
+}
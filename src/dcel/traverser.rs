@@ -2,7 +2,7 @@ use std::marker::PhantomData;
 
 use super::{Dcel, EdgeKey, Key, VertexKey, error::Error};
 use crate::{
-    coord::{Coordinate, Orientation},
+    coord::{Coordinate, Orientation, polygon_encloses},
     dcel::{FaceKey, FaceMask, flavor::Flavor, traverser},
     util::ShoeString,
 };
@@ -113,7 +113,7 @@ impl<F: Flavor> Traverser<F> {
         TraverseThrough::new(dcel, edge)
     }
 
-    pub fn signed_area(dcel: &Dcel<F>, edge: Key<EdgeKey>) -> Result<f32, Error>
+    pub fn signed_area(dcel: &Dcel<F>, edge: Key<EdgeKey>) -> Result<f64, Error>
     where
         F::Vertex: Coordinate,
     {
@@ -136,6 +136,20 @@ impl<F: Flavor> Traverser<F> {
         Ok(calc)
     }
 
+    /// even-odd test of a point against the cycle through edge
+    pub fn encloses(
+        dcel: &Dcel<F>,
+        edge: Key<EdgeKey>,
+        point: [<F::Vertex as Coordinate>::Scalar; 2],
+    ) -> Result<bool, Error>
+    where
+        F::Vertex: Coordinate,
+    {
+        let segments = Traverser::through(dcel, edge)?
+            .map(|edge| [edge, edge.twin(dcel)].map(|edge| edge.origin(dcel).weight(dcel).xy()));
+        Ok(polygon_encloses(segments, point))
+    }
+
     pub fn around<'a>(
         dcel: &'a Dcel<F>,
         vertex: Key<VertexKey>,
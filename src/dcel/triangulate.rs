@@ -0,0 +1,630 @@
+//! Splitting faces into triangles, through y-monotone pieces or by ear clipping with their holes
+//! bridged to the outer boundary
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::Debug,
+};
+
+use crate::{
+    arena::Key,
+    coord::{Coordinate, Scalar, UpdateCoordinate, in_triangle, orient2d, sort_clockwise},
+    dcel::{
+        Dcel, FaceKey, Flavor, HolRef, OpGroup, Operator, OperatorErr, RollbackErr, Transaction,
+        Traverser, VertexKey, arrangement::join, ops,
+    },
+};
+
+type Point<F> = [<<F as Flavor>::Vertex as Coordinate>::Scalar; 2];
+
+/// the corners of triangles, clockwise
+type Triangles = Vec<[Key<VertexKey>; 3]>;
+
+/// vertices, and the triangles as three indices into them each
+type Buffer<F> = (Vec<Point<F>>, Vec<u32>);
+
+#[derive(thiserror::Error)]
+pub enum TriangulationError<F: Flavor>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    #[error("face does not exist")]
+    FaceDoesNotExist,
+    #[error("the bounding face has no outer boundary to triangulate")]
+    BoundingFace,
+    #[error("no ear is left to clip, the face is degenerate")]
+    NoEar,
+    #[error("a hole sees no corner of the boundary to be bridged to, it lies outside the face")]
+    HoleNotBridged,
+    #[error("an operator failed")]
+    Op(ops::Error<F>),
+}
+
+impl<F: Flavor> Debug for TriangulationError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self}")
+    }
+}
+
+impl<F: Flavor> From<RollbackErr<F>> for TriangulationError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    fn from(err: RollbackErr<F>) -> Self {
+        Self::Op(err.into())
+    }
+}
+
+impl<F: Flavor, O: Operator<F>> From<OperatorErr<O, O::Error>> for TriangulationError<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    ops::Error<F>: From<OperatorErr<O, O::Error>>,
+{
+    fn from(err: OperatorErr<O, O::Error>) -> Self {
+        Self::Op(err.into())
+    }
+}
+
+/// whether the interiors of a b and c d cross in a single point
+fn crosses<S: Scalar>(a: [S; 2], b: [S; 2], c: [S; 2], d: [S; 2]) -> bool {
+    let opposite = |x: f64, y: f64| x != 0. && y != 0. && (x < 0.) != (y < 0.);
+    opposite(orient2d(a, b, c), orient2d(a, b, d)) && opposite(orient2d(c, d, a), orient2d(c, d, b))
+}
+
+/// whether p lies in the corner of a clockwise polygon at b, between the sides from a and to c
+fn in_corner<S: Scalar>(p: [S; 2], a: [S; 2], b: [S; 2], c: [S; 2]) -> bool {
+    let [from, to] = [orient2d(a, b, p) < 0., orient2d(b, c, p) < 0.];
+    match orient2d(a, b, c) < 0. {
+        true => from && to,
+        false => from || to,
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+{
+    /// the triangles a bounded face splits into. a face whose boundary and holes are simple
+    /// polygons touching nowhere is cut into y-monotone pieces, which takes O(n log n) for n
+    /// vertices around the face. any other face, with edges dangling into it, boundaries that
+    /// touch or isolated vertices in it, has every hole bridged to the outer boundary and the one
+    /// polygon left cut by ear clipping, which takes up to O(n³). those edges and vertices end up
+    /// as sides and corners of triangles
+    pub fn face_triangles(&self, face: Key<FaceKey>) -> Result<Triangles, TriangulationError<F>> {
+        if Some(face) == self.bounding_face {
+            return Err(TriangulationError::BoundingFace);
+        }
+        let face = self
+            .faces
+            .get(face)
+            .ok_or(TriangulationError::FaceDoesNotExist)?;
+        let cycle = |edge| {
+            Traverser::through(self, edge)
+                .unwrap()
+                .map(|edge| edge.origin(self))
+                .collect::<Vec<_>>()
+        };
+        let ring = cycle(face.edge);
+        let holes: Vec<Vec<Key<VertexKey>>> = face
+            .holes
+            .iter()
+            .map(|hole| match hole {
+                HolRef::Edge(edge) => cycle(*edge),
+                HolRef::Vertex(vertex) => vec![*vertex],
+            })
+            .collect();
+        if let Some(triangles) = self.monotone_triangles(&ring, &holes) {
+            return Ok(triangles);
+        }
+        self.clipped_triangles(ring, holes)
+    }
+
+    /// the triangles of the boundary and holes through y-monotone pieces, or none when they are
+    /// not all simple polygons apart from each other
+    fn monotone_triangles(
+        &self,
+        ring: &[Key<VertexKey>],
+        holes: &[Vec<Key<VertexKey>>],
+    ) -> Option<Triangles> {
+        // every ring reversed, to run counterclockwise around the face
+        let mut vertices = vec![];
+        let mut next = vec![];
+        for ring in std::iter::once(ring).chain(holes.iter().map(Vec::as_slice)) {
+            if ring.len() < 3 {
+                return None;
+            }
+            let first = vertices.len();
+            vertices.extend(ring.iter().rev());
+            next.extend((first + 1..vertices.len()).chain([first]));
+        }
+        let points: Vec<_> = vertices
+            .iter()
+            .map(|vertex| self.vertex(*vertex).weight.xy())
+            .collect();
+        let mut sorted = points.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        if sorted.windows(2).any(|pair| pair[0] == pair[1]) {
+            return None;
+        }
+        let triangles = monotone(&points, &next)?
+            .into_iter()
+            .map(
+                |[a, b, c]| match orient2d(points[a], points[b], points[c]) > 0. {
+                    true => [vertices[a], vertices[c], vertices[b]],
+                    false => [vertices[a], vertices[b], vertices[c]],
+                },
+            )
+            .collect();
+        Some(triangles)
+    }
+
+    /// the triangles of the boundary and holes by ear clipping, once every hole is bridged to the
+    /// boundary
+    fn clipped_triangles(
+        &self,
+        mut ring: Vec<Key<VertexKey>>,
+        holes: Vec<Vec<Key<VertexKey>>>,
+    ) -> Result<Triangles, TriangulationError<F>> {
+        let point = |vertex: Key<VertexKey>| self.vertex(vertex).weight.xy();
+        // every hole starting at its rightmost vertex, the holes reaching furthest right first
+        let mut holes: Vec<Vec<Key<VertexKey>>> = holes
+            .into_iter()
+            .map(|mut hole| {
+                let rightmost = (0..hole.len())
+                    .max_by(|a, b| point(hole[*a]).partial_cmp(&point(hole[*b])).unwrap())
+                    .unwrap();
+                hole.rotate_left(rightmost);
+                hole
+            })
+            .collect();
+        holes.sort_by(|a, b| point(b[0]).partial_cmp(&point(a[0])).unwrap());
+        for mut hole in holes {
+            let m = point(hole[0]);
+            let at = bridge(&ring.iter().map(|v| point(*v)).collect::<Vec<_>>(), m)
+                .ok_or(TriangulationError::HoleNotBridged)?;
+            if hole.len() > 1 {
+                hole.push(hole[0]);
+            }
+            hole.push(ring[at]);
+            ring.splice(at + 1..at + 1, hole);
+        }
+
+        let points: Vec<_> = ring.iter().map(|vertex| point(*vertex)).collect();
+        let mut prev: Vec<usize> = (0..ring.len())
+            .map(|i| (i + ring.len() - 1) % ring.len())
+            .collect();
+        let mut next: Vec<usize> = (0..ring.len()).map(|i| (i + 1) % ring.len()).collect();
+        let mut left = ring.len();
+        let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2));
+        let mut i = 0;
+        let mut tried = 0;
+        while left > 3 {
+            if is_ear(&points, &next, [prev[i], i, next[i]]) {
+                triangles.push([ring[prev[i]], ring[i], ring[next[i]]]);
+                let [p, n] = [prev[i], next[i]];
+                next[p] = n;
+                prev[n] = p;
+                left -= 1;
+                tried = 0;
+                i = p;
+                continue;
+            }
+            tried += 1;
+            if tried > left {
+                return Err(TriangulationError::NoEar);
+            }
+            i = next[i];
+        }
+        let [p, n] = [prev[i], next[i]];
+        if orient2d(points[p], points[i], points[n]) < 0. {
+            triangles.push([ring[p], ring[i], ring[n]]);
+        }
+        Ok(triangles)
+    }
+
+    /// every bounded face in triangles, as an index buffer into the vertices of the dcel in the
+    /// order of the arena, for rendering
+    pub fn triangle_buffer(&self) -> Result<Buffer<F>, TriangulationError<F>> {
+        let mut index = HashMap::with_capacity(self.vertices.len());
+        let mut vertices = Vec::with_capacity(self.vertices.len());
+        for (vertex, key) in self.vertices.iter() {
+            index.insert(key, vertices.len() as u32);
+            vertices.push(vertex.weight.xy());
+        }
+        let mut indices = vec![];
+        for face in self.faces.keys() {
+            if Some(face) == self.bounding_face {
+                continue;
+            }
+            for triangle in self.face_triangles(face)? {
+                indices.extend(triangle.map(|vertex| index[&vertex]));
+            }
+        }
+        Ok((vertices, indices))
+    }
+}
+
+impl<F: Flavor> Dcel<F>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    /// split a bounded face into the triangles of [`face_triangles`](Self::face_triangles), adding
+    /// every diagonal with [`Mef`](ops::Mef), or with [`Mekh`](ops::Mekh) where it joins a hole to
+    /// the boundary. returns the group that takes the diagonals out again
+    pub fn triangulate_face(
+        &mut self,
+        face: Key<FaceKey>,
+    ) -> Result<OpGroup<F>, TriangulationError<F>> {
+        Ok(self.transaction(|tx| triangulate(tx, face))?.1)
+    }
+
+    /// split every bounded face into triangles, as a single edit
+    pub fn triangulate_all(&mut self) -> Result<OpGroup<F>, TriangulationError<F>> {
+        let faces: Vec<_> = self
+            .faces
+            .keys()
+            .filter(|face| Some(*face) != self.bounding_face)
+            .collect();
+        Ok(self
+            .transaction(|tx| faces.into_iter().try_for_each(|face| triangulate(tx, face)))?
+            .1)
+    }
+}
+
+fn triangulate<F: Flavor>(
+    tx: &mut Transaction<'_, F>,
+    face: Key<FaceKey>,
+) -> Result<(), TriangulationError<F>>
+where
+    F::Vertex: UpdateCoordinate + Coordinate,
+    F::Edge: Default,
+    F::Face: Default,
+{
+    for [a, b, c] in tx.dcel().face_triangles(face)? {
+        for [from, to] in [[a, b], [b, c], [c, a]] {
+            if tx.dcel().edge_between(from, to).is_some() {
+                continue;
+            }
+            // a diagonal to a hole joins it to the boundary, and makes no new face
+            join::<F, TriangulationError<F>>(tx, [from, to])?;
+        }
+    }
+    Ok(())
+}
+
+/// the corner of the ring a hole can be bridged to from its rightmost point m, after Eberly's
+/// "Triangulation by Ear Clipping": the closest side right of m, or the reflex corner in the way
+/// seen at the smallest angle
+fn bridge<S: Scalar>(ring: &[[S; 2]], m: [S; 2]) -> Option<usize> {
+    let [mx, my] = m.map(S::to_f64);
+    let n = ring.len();
+    // the closest side the ray from m to the right hits, and where
+    let mut hit: Option<(f64, usize)> = None;
+    for i in 0..n {
+        let [a, b] = [ring[i], ring[(i + 1) % n]].map(|p| p.map(S::to_f64));
+        if (a[1] > my) == (b[1] > my) && a[1] != my && b[1] != my {
+            continue;
+        }
+        let x = match a[1] == b[1] {
+            true => a[0].min(b[0]),
+            false => a[0] + (my - a[1]) * (b[0] - a[0]) / (b[1] - a[1]),
+        };
+        if x >= mx && hit.is_none_or(|(closest, _)| x < closest) {
+            hit = Some((x, i));
+        }
+    }
+    let (x, side) = hit?;
+    let [a, b] = [side, (side + 1) % n];
+    let candidate = match [a, b].map(|i| ring[i].map(S::to_f64)) {
+        [pa, _] if pa == [x, my] => a,
+        [_, pb] if pb == [x, my] => b,
+        [pa, pb] => match pa[0] > pb[0] {
+            true => a,
+            false => b,
+        },
+    };
+
+    // reflex corners inside the triangle m, hit, candidate hide the candidate
+    let p = ring[candidate];
+    let hit = [S::from_f64(x), m[1]];
+    let angle = |q: [S; 2]| {
+        let [dx, dy] = [q[0].to_f64() - mx, q[1].to_f64() - my];
+        (dy.abs().atan2(dx), dx * dx + dy * dy)
+    };
+    let mut best = candidate;
+    let mut best_angle = angle(p);
+    for i in 0..n {
+        let [prev, q, next] = [ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]];
+        if q == p || orient2d(prev, q, next) < 0. || !in_triangle(q, m, hit, p) {
+            continue;
+        }
+        let seen = angle(q);
+        if seen < best_angle {
+            best = i;
+            best_angle = seen;
+        }
+    }
+
+    // a point the ring passes more than once is bridged in the corner that faces m
+    let target = ring[best];
+    (0..n)
+        .filter(|i| ring[*i] == target)
+        .find(|i| in_corner(m, ring[(i + n - 1) % n], target, ring[(i + 1) % n]))
+        .or(Some(best))
+}
+
+/// whether the corner at i can be cut off: it turns clockwise, no other corner lies in the
+/// triangle and no side crosses the diagonal. corners the ring passes again are left out, a bridge
+/// runs to them
+fn is_ear<S: Scalar>(points: &[[S; 2]], next: &[usize], [p, i, n]: [usize; 3]) -> bool {
+    let [a, b, c] = [points[p], points[i], points[n]];
+    if orient2d(a, b, c) >= 0. {
+        return false;
+    }
+    let mut j = next[n];
+    while j != p {
+        let q = points[j];
+        let after = points[next[j]];
+        if ![a, b, c].contains(&q) && in_triangle(q, a, b, c) {
+            return false;
+        }
+        if crosses(a, c, q, after) {
+            return false;
+        }
+        j = next[j];
+    }
+    true
+}
+
+/// how the sweep down a polygon handles a corner, by whether its neighbours are below or above it
+/// and whether it turns into the polygon
+#[derive(Clone, Copy, PartialEq)]
+enum Corner {
+    Start,
+    Split,
+    End,
+    Merge,
+    Regular,
+}
+
+/// a side the sweep line crosses with the polygon right of it, or a point to look up among them.
+/// the sides never cross, so two of them compare by where the later one starts, right or left of
+/// the other there
+enum Status<S> {
+    Side {
+        /// upper end first
+        ends: [[S; 2]; 2],
+        /// how far down the sweep its upper end is
+        rank: usize,
+        side: usize,
+    },
+    /// just left or just right of every side through the point
+    Point { point: [S; 2], right: bool },
+}
+
+impl<S: Scalar> PartialEq for Status<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl<S: Scalar> Eq for Status<S> {}
+
+impl<S: Scalar> PartialOrd for Status<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<S: Scalar> Ord for Status<S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // orient2d can answer -0
+        let right = |point: [S; 2], [a, b]: [[S; 2]; 2]| {
+            orient2d(a, b, point)
+                .partial_cmp(&0.)
+                .unwrap_or(Ordering::Equal)
+        };
+        match (self, other) {
+            (Self::Point { right: a, .. }, Self::Point { right: b, .. }) => a.cmp(b),
+            (Self::Point { point, right: r }, Self::Side { ends, .. }) => right(*point, *ends)
+                .then(match r {
+                    true => Ordering::Greater,
+                    false => Ordering::Less,
+                }),
+            (Self::Side { .. }, Self::Point { .. }) => other.cmp(self).reverse(),
+            (
+                Self::Side {
+                    ends: s,
+                    rank: s_rank,
+                    side: s_side,
+                },
+                Self::Side {
+                    ends: t,
+                    rank: t_rank,
+                    side: t_side,
+                },
+            ) => match s_rank.cmp(t_rank) {
+                Ordering::Less => right(t[0], *s).reverse(),
+                Ordering::Greater => right(s[0], *t),
+                Ordering::Equal => Ordering::Equal,
+            }
+            .then(s_side.cmp(t_side)),
+        }
+    }
+}
+
+/// the triangles of a polygon with holes, its rings simple, apart from each other and running
+/// counterclockwise around the inside, after de Berg et al., "Computational Geometry", chapter 3.
+/// a sweep from the top adds the diagonals that cut it into y-monotone pieces, and every piece is
+/// then triangulated in one pass down its two chains. none when the rings are not as promised
+fn monotone<S: Scalar>(points: &[[S; 2]], next: &[usize]) -> Option<Vec<[usize; 3]>> {
+    let n = points.len();
+    let mut prev = vec![0; n];
+    for (i, j) in next.iter().enumerate() {
+        prev[*j] = i;
+    }
+    // down the sweep: higher first, and left first at the same height
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|a, b| {
+        let [[ax, ay], [bx, by]] = [points[*a], points[*b]];
+        by.partial_cmp(&ay)
+            .unwrap_or(Ordering::Equal)
+            .then(ax.partial_cmp(&bx).unwrap_or(Ordering::Equal))
+    });
+    let mut rank = vec![0; n];
+    for (r, v) in order.iter().enumerate() {
+        rank[*v] = r;
+    }
+    let corner = |v: usize| {
+        let [p, q] = [prev[v], next[v]];
+        let convex = orient2d(points[p], points[v], points[q]) > 0.;
+        match (rank[p] > rank[v], rank[q] > rank[v], convex) {
+            (true, true, true) => Corner::Start,
+            (true, true, false) => Corner::Split,
+            (false, false, true) => Corner::End,
+            (false, false, false) => Corner::Merge,
+            _ => Corner::Regular,
+        }
+    };
+    // side i runs from corner i to the next
+    let side = |i: usize| Status::Side {
+        ends: [points[i], points[next[i]]],
+        rank: rank[i],
+        side: i,
+    };
+
+    let mut status = BTreeSet::new();
+    let mut helper = vec![0; n];
+    let mut diagonals = vec![];
+    for v in order.iter().copied() {
+        let probe = Status::Point {
+            point: points[v],
+            right: false,
+        };
+        let left = |status: &BTreeSet<Status<S>>| match status.range(..&probe).next_back() {
+            Some(Status::Side { side, .. }) => Some(*side),
+            _ => None,
+        };
+        let corner_v = corner(v);
+        // the side above leaves the status, joined to its helper when that merged two pieces
+        if matches!(corner_v, Corner::End | Corner::Merge)
+            || (corner_v == Corner::Regular && rank[prev[v]] < rank[v])
+        {
+            let above = prev[v];
+            if corner(helper[above]) == Corner::Merge {
+                diagonals.push([v, helper[above]]);
+            }
+            status.remove(&side(above));
+        }
+        match corner_v {
+            Corner::Start => {}
+            Corner::Split => {
+                let left = left(&status)?;
+                diagonals.push([v, helper[left]]);
+                helper[left] = v;
+            }
+            Corner::Regular if rank[prev[v]] < rank[v] => {}
+            Corner::End => continue,
+            Corner::Merge | Corner::Regular => {
+                let left = left(&status)?;
+                if corner(helper[left]) == Corner::Merge {
+                    diagonals.push([v, helper[left]]);
+                }
+                helper[left] = v;
+                continue;
+            }
+        }
+        // the side below starts here
+        status.insert(side(v));
+        helper[v] = v;
+    }
+
+    // the pieces lie left of their sides, so the next side turns left the most
+    let mut outgoing: Vec<Vec<usize>> = next.iter().map(|v| vec![*v]).collect();
+    for [a, b] in diagonals {
+        outgoing[a].push(b);
+        outgoing[b].push(a);
+    }
+    for (v, around) in outgoing.iter_mut().enumerate() {
+        around.sort_by(|a, b| sort_clockwise(points[v], points[*a], points[*b]));
+    }
+    let turn = |from: usize, at: usize| {
+        let around = &outgoing[at];
+        let after = around.partition_point(|to| {
+            *to == from || sort_clockwise(points[at], points[*to], points[from]).is_lt()
+        });
+        around[after % around.len()]
+    };
+    let mut seen = HashSet::new();
+    let mut triangles = Vec::with_capacity(n);
+    for (v, around) in outgoing.iter().enumerate() {
+        for to in around {
+            if seen.contains(&[v, *to]) {
+                continue;
+            }
+            let mut piece = vec![];
+            let [mut from, mut at] = [v, *to];
+            while seen.insert([from, at]) {
+                piece.push(from);
+                [from, at] = [at, turn(from, at)];
+            }
+            triangulate_monotone(points, &rank, &piece, &mut triangles);
+        }
+    }
+    Some(triangles)
+}
+
+/// the triangles of a y-monotone polygon running counterclockwise, cut off going down both chains
+/// at once while the corners passed on one chain wait on a stack
+fn triangulate_monotone<S: Scalar>(
+    points: &[[S; 2]],
+    rank: &[usize],
+    piece: &[usize],
+    triangles: &mut Vec<[usize; 3]>,
+) {
+    let k = piece.len();
+    if k < 3 {
+        return;
+    }
+    let by_rank = |i: &usize| rank[piece[*i]];
+    let [top, bottom] =
+        [(0..k).min_by_key(by_rank), (0..k).max_by_key(by_rank)].map(Option::unwrap);
+    // counterclockwise from the top runs down the left chain
+    let mut corners: Vec<(usize, bool)> = (0..k)
+        .map(|i| (piece[i], (i + k - top) % k < (bottom + k - top) % k))
+        .collect();
+    corners.sort_by_key(|(v, _)| rank[*v]);
+
+    let mut stack = corners[..2].to_vec();
+    for &(v, on_left) in &corners[2..k - 1] {
+        let last = stack[stack.len() - 1];
+        if last.1 != on_left {
+            triangles.extend(stack.windows(2).map(|pair| [v, pair[0].0, pair[1].0]));
+            stack = vec![last, (v, on_left)];
+            continue;
+        }
+        let mut last = stack.pop().unwrap();
+        while let Some(&before) = stack.last() {
+            let [a, b, c] = [before.0, last.0, v].map(|i| points[i]);
+            let inside = match on_left {
+                true => orient2d(a, b, c),
+                false => orient2d(c, b, a),
+            };
+            if inside <= 0. {
+                break;
+            }
+            triangles.push([v, last.0, before.0]);
+            last = stack.pop().unwrap();
+        }
+        stack.push(last);
+        stack.push((v, on_left));
+    }
+    let v = corners[k - 1].0;
+    triangles.extend(stack.windows(2).map(|pair| [v, pair[0].0, pair[1].0]));
+}
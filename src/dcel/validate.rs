@@ -0,0 +1,298 @@
+//! Topological invariant checks for a [`Dcel`]
+
+use std::fmt::{Display, Write};
+
+use crate::{
+    arena::{ArenaBitMask, Key},
+    dcel::{Dcel, EdgeKey, FaceKey, Flavor, HolRef, VertexKey},
+};
+
+/// A single broken invariant
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// an edge points at a vertex that does not exist
+    MissingVertex { edge: Key<EdgeKey> },
+    /// an edge points at an edge that does not exist
+    MissingEdge { edge: Key<EdgeKey> },
+    /// an edge points at a face that does not exist
+    MissingFace { edge: Key<EdgeKey> },
+    /// `edge.twin.twin != edge`
+    TwinMismatch { edge: Key<EdgeKey> },
+    /// `edge.next.prev != edge` or `edge.prev.next != edge`
+    NextPrevMismatch { edge: Key<EdgeKey> },
+    /// an edge and its twin leave the same vertex
+    TwinSameOrigin { edge: Key<EdgeKey> },
+    /// `edge.next` does not leave the vertex the edge arrives at
+    NextOriginMismatch { edge: Key<EdgeKey> },
+    /// following next from an edge never comes back to it
+    OpenCycle { edge: Key<EdgeKey> },
+    /// an edge in a cycle has a different face than the first edge of the cycle
+    CycleFaceMismatch {
+        edge: Key<EdgeKey>,
+        face: Key<FaceKey>,
+    },
+    /// the edge of a vertex does not leave it
+    VertexEdgeNotOutgoing {
+        vertex: Key<VertexKey>,
+        edge: Key<EdgeKey>,
+    },
+    /// the edge of a vertex does not exist
+    VertexEdgeMissing {
+        vertex: Key<VertexKey>,
+        edge: Key<EdgeKey>,
+    },
+    /// there are edges but no bounding face, or the bounding face does not exist
+    MissingBoundingFace,
+    /// the edge of a face does not exist, or is not on the face
+    FaceBoundaryMismatch { face: Key<FaceKey> },
+    /// a hole of a face does not exist, is not on the face, or is a vertex with edges
+    HoleMismatch { face: Key<FaceKey>, hole: HolRef },
+    /// a cycle is not the boundary or a hole of its face
+    UnreachableCycle { edge: Key<EdgeKey> },
+    /// a cycle is the boundary or a hole of its face more than once
+    DuplicateBoundary {
+        face: Key<FaceKey>,
+        edge: Key<EdgeKey>,
+    },
+    /// an isolated vertex is not a hole of any face even though there is a bounding face
+    UnreachableVertex { vertex: Key<VertexKey> },
+    /// V - E + F of a connected component, counting the faces whose outer boundary is in it, is not 1
+    EulerCharacteristic {
+        vertex: Key<VertexKey>,
+        characteristic: i64,
+    },
+}
+
+/// Every invariant broken in a [`Dcel`]
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_valid() {
+            return write!(f, "valid");
+        }
+        for violation in &self.violations {
+            writeln!(f, "{violation:?}")?;
+        }
+        Ok(())
+    }
+}
+
+struct Components {
+    parent: Vec<usize>,
+}
+
+impl Components {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn root(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let [a, b] = [self.root(a), self.root(b)];
+        self.parent[a] = b;
+    }
+}
+
+impl<F: Flavor> Dcel<F> {
+    /// check every topological invariant, reporting all that are broken
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        let violations = &mut report.violations;
+
+        // pointers, every later check relies on these
+        for (edge, key) in self.edges.iter() {
+            let len = violations.len();
+            if self.vertices.get(edge.origin).is_none() {
+                violations.push(Violation::MissingVertex { edge: key });
+            }
+            if [edge.twin, edge.next, edge.prev]
+                .iter()
+                .any(|e| self.edges.get(*e).is_none())
+            {
+                violations.push(Violation::MissingEdge { edge: key });
+            }
+            if self.faces.get(edge.face).is_none() {
+                violations.push(Violation::MissingFace { edge: key });
+            }
+            if violations.len() > len {
+                continue;
+            }
+
+            if self.edges[edge.twin].twin != key {
+                violations.push(Violation::TwinMismatch { edge: key });
+            }
+            if self.edges[edge.next].prev != key || self.edges[edge.prev].next != key {
+                violations.push(Violation::NextPrevMismatch { edge: key });
+            }
+            if self.edges[edge.twin].origin == edge.origin {
+                violations.push(Violation::TwinSameOrigin { edge: key });
+            }
+            if self.edges[edge.next].origin != self.edges[edge.twin].origin {
+                violations.push(Violation::NextOriginMismatch { edge: key });
+            }
+        }
+        if !report.is_valid() {
+            return report;
+        }
+        let violations = &mut report.violations;
+
+        for (vertex, key) in self.vertices.iter() {
+            if let Some(edge) = vertex.edge {
+                match self.edges.get(edge) {
+                    Some(e) if e.origin != key => {
+                        violations.push(Violation::VertexEdgeNotOutgoing { vertex: key, edge })
+                    }
+                    None => violations.push(Violation::VertexEdgeMissing { vertex: key, edge }),
+                    _ => {}
+                }
+            }
+        }
+
+        let has_edges = self.edges.iter().next().is_some();
+        match self.bounding_face {
+            Some(face) if self.faces.get(face).is_none() => {
+                violations.push(Violation::MissingBoundingFace)
+            }
+            None if has_edges => violations.push(Violation::MissingBoundingFace),
+            _ => {}
+        }
+
+        // cycles, each labelled with the first edge it was reached from
+        let mut cycle_of = vec![None; self.edges.size() + 1];
+        let mut cycles = vec![];
+        for (_, start) in self.edges.iter() {
+            if cycle_of[start.get() as usize].is_some() {
+                continue;
+            }
+            let face = self.edges[start].face;
+            let mut edge = start;
+            let mut steps = 0;
+            loop {
+                cycle_of[edge.get() as usize] = Some(cycles.len());
+                if self.edges[edge].face != face {
+                    violations.push(Violation::CycleFaceMismatch { edge, face });
+                }
+                edge = self.edges[edge].next;
+                steps += 1;
+                if edge == start {
+                    break;
+                }
+                if steps > self.edges.size() || cycle_of[edge.get() as usize].is_some() {
+                    violations.push(Violation::OpenCycle { edge: start });
+                    break;
+                }
+            }
+            cycles.push((start, face));
+        }
+
+        // every cycle is referenced exactly once by its face
+        let mut referenced = vec![false; cycles.len()];
+        let mut reference = |face: Key<FaceKey>, edge: Key<EdgeKey>, violations: &mut Vec<_>| {
+            let Some(cycle) = cycle_of[edge.get() as usize] else {
+                return false;
+            };
+            if referenced[cycle] {
+                violations.push(Violation::DuplicateBoundary { face, edge });
+            }
+            referenced[cycle] = true;
+            true
+        };
+        let mut isolated = ArenaBitMask::new(&self.vertices);
+        for (face, key) in self.faces.iter() {
+            match self.edges.get(face.edge) {
+                Some(edge) if edge.face == key => {
+                    reference(key, face.edge, violations);
+                }
+                _ => violations.push(Violation::FaceBoundaryMismatch { face: key }),
+            }
+            for hole in &face.holes {
+                let is_valid = match *hole {
+                    HolRef::Edge(edge) => match self.edges.get(edge) {
+                        Some(e) if e.face == key => reference(key, edge, violations),
+                        _ => false,
+                    },
+                    HolRef::Vertex(vertex) => match self.vertices.get(vertex) {
+                        Some(v) if v.edge.is_none() => {
+                            isolated.set(vertex);
+                            true
+                        }
+                        _ => false,
+                    },
+                };
+                if !is_valid {
+                    violations.push(Violation::HoleMismatch {
+                        face: key,
+                        hole: *hole,
+                    });
+                }
+            }
+        }
+        for (cycle, (edge, _)) in cycles.iter().enumerate() {
+            if !referenced[cycle] {
+                violations.push(Violation::UnreachableCycle { edge: *edge });
+            }
+        }
+        if self.bounding_face.is_some() {
+            for (vertex, key) in self.vertices.iter() {
+                if vertex.edge.is_none() && !isolated.is_flipped(key) {
+                    violations.push(Violation::UnreachableVertex { vertex: key });
+                }
+            }
+        }
+
+        // euler poincare, per connected component
+        let mut components = Components::new(self.vertices.size() + 1);
+        for (edge, _) in self.edges.iter() {
+            let twin_origin = self.edges[edge.twin].origin;
+            components.union(edge.origin.get() as usize, twin_origin.get() as usize);
+        }
+        let mut characteristic = vec![0i64; self.vertices.size() + 1];
+        for (_, key) in self.vertices.iter() {
+            characteristic[components.root(key.get() as usize)] += 1;
+        }
+        for (edge, key) in self.edges.iter() {
+            // half edges come in pairs
+            if key < edge.twin {
+                characteristic[components.root(edge.origin.get() as usize)] -= 1;
+            }
+        }
+        for (face, key) in self.faces.iter() {
+            if Some(key) == self.bounding_face {
+                continue;
+            }
+            if let Some(edge) = self.edges.get(face.edge) {
+                characteristic[components.root(edge.origin.get() as usize)] += 1;
+            }
+        }
+        for (_, key) in self.vertices.iter() {
+            let root = key.get() as usize;
+            if components.root(root) == root && characteristic[root] != 1 {
+                violations.push(Violation::EulerCharacteristic {
+                    vertex: key,
+                    characteristic: characteristic[root],
+                });
+            }
+        }
+
+        report
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    coord::Coordinate,
+    coord::{Coordinate, Scalar},
     dcel::{Traverser, flavor::Flavor},
 };
 use std::fmt::Write;
@@ -100,8 +100,8 @@ where
         let from = &dcel.vertices[edge.origin];
         let to = &dcel.vertices[dcel.edges[edge.next].origin];
 
-        let [x1, y1] = from.weight.xy();
-        let [x2, y2] = to.weight.xy();
+        let [x1, y1] = from.weight.xy().map(|c| c.to_f64() as f32);
+        let [x2, y2] = to.weight.xy().map(|c| c.to_f64() as f32);
 
         let ((x1, y1), (x2, y2)) = offset_line(x1, y1, x2, y2, 0.2, 0.2);
 
@@ -1,34 +1,441 @@
+//! Spatial queries over anything with a bounding box, answered by an [`RTree`]
+
+use std::ops::ControlFlow;
+
+use crate::coord::Scalar;
+
+mod rtree;
+
+pub use rtree::RTree;
+
+/// An axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb<S> {
+    pub min: [S; 2],
+    pub max: [S; 2],
+}
+
+impl<S: Scalar> Aabb<S> {
+    pub fn from_point(point: [S; 2]) -> Self {
+        Self {
+            min: point,
+            max: point,
+        }
+    }
+
+    /// the box around every point, none when there are none
+    pub fn from_points(points: impl IntoIterator<Item = [S; 2]>) -> Option<Self> {
+        points
+            .into_iter()
+            .map(Self::from_point)
+            .reduce(|a, b| a.merged(&b))
+    }
+
+    pub fn merged(&self, other: &Self) -> Self {
+        let pick = |a: S, b: S, less: bool| if (a < b) == less { a } else { b };
+        Self {
+            min: [0, 1].map(|i| pick(self.min[i], other.min[i], true)),
+            max: [0, 1].map(|i| pick(self.max[i], other.max[i], false)),
+        }
+    }
+
+    pub fn contains_point(&self, [x, y]: [S; 2]) -> bool {
+        self.min[0] <= x && x <= self.max[0] && self.min[1] <= y && y <= self.max[1]
+    }
+
+    /// whether other lies entirely inside
+    pub fn contains(&self, other: &Self) -> bool {
+        self.contains_point(other.min) && self.contains_point(other.max)
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        (0..2).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    pub fn area(&self) -> f64 {
+        let [w, h] = [0, 1].map(|i| self.max[i].to_f64() - self.min[i].to_f64());
+        w * h
+    }
+
+    pub fn center(&self) -> [f64; 2] {
+        [0, 1].map(|i| (self.min[i].to_f64() + self.max[i].to_f64()) / 2.)
+    }
+
+    /// the squared distance from point to the closest point of the box, zero inside
+    pub fn distance_2(&self, point: [S; 2]) -> f64 {
+        [0, 1]
+            .map(|i| {
+                let [p, min, max] = [point[i], self.min[i], self.max[i]].map(S::to_f64);
+                (min - p).max(p - max).max(0.)
+            })
+            .iter()
+            .map(|d| d * d)
+            .sum()
+    }
+}
+
+/// Something that can be put in a spatial index
+pub trait SpatialObject {
+    type Scalar: Scalar;
+    fn envelope(&self) -> Aabb<Self::Scalar>;
+    /// the squared distance to point, zero when the object covers it
+    fn distance_2(&self, point: [Self::Scalar; 2]) -> f64;
+    fn contains_point(&self, point: [Self::Scalar; 2]) -> bool {
+        self.distance_2(point) == 0.
+    }
+}
+
+impl<S: Scalar> SpatialObject for [S; 2] {
+    type Scalar = S;
+    fn envelope(&self) -> Aabb<S> {
+        Aabb::from_point(*self)
+    }
+    fn distance_2(&self, point: [S; 2]) -> f64 {
+        Aabb::from_point(*self).distance_2(point)
+    }
+    fn contains_point(&self, point: [S; 2]) -> bool {
+        *self == point
+    }
+}
+
+/// Which parts of an index a query walks into, and which objects it picks there
+pub trait SelectionFunction<T: SpatialObject> {
+    /// whether anything under a node with this envelope can be selected
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool;
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        true
+    }
+}
+
+/// a predicate on the objects alone, which has to look at every one of them
+impl<T: SpatialObject, P: Fn(&T) -> bool> SelectionFunction<T> for P {
+    fn should_unpack_parent(&self, _: &Aabb<T::Scalar>) -> bool {
+        true
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        self(leaf)
+    }
+}
+
+pub struct SelectAtPoint<S>(pub [S; 2]);
+
+impl<T: SpatialObject> SelectionFunction<T> for SelectAtPoint<T::Scalar> {
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool {
+        envelope.contains_point(self.0)
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        leaf.contains_point(self.0)
+    }
+}
+
+/// objects whose envelope lies entirely inside
+pub struct SelectInEnvelope<S>(pub Aabb<S>);
+
+impl<T: SpatialObject> SelectionFunction<T> for SelectInEnvelope<T::Scalar> {
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool {
+        envelope.intersects(&self.0)
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        self.0.contains(&leaf.envelope())
+    }
+}
+
+/// objects whose envelope overlaps
+pub struct SelectInEnvelopeIntersecting<S>(pub Aabb<S>);
+
+impl<T: SpatialObject> SelectionFunction<T> for SelectInEnvelopeIntersecting<T::Scalar> {
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool {
+        envelope.intersects(&self.0)
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        leaf.envelope().intersects(&self.0)
+    }
+}
+
+pub struct SelectWithinDistance<S> {
+    pub point: [S; 2],
+    pub max_distance_2: f64,
+}
+
+impl<T: SpatialObject> SelectionFunction<T> for SelectWithinDistance<T::Scalar> {
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool {
+        envelope.distance_2(self.point) <= self.max_distance_2
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        leaf.distance_2(self.point) <= self.max_distance_2
+    }
+}
+
+/// objects equal to the one given
+pub struct SelectEqual<'a, T>(pub &'a T);
+
+impl<T: SpatialObject + PartialEq> SelectionFunction<T> for SelectEqual<'_, T> {
+    fn should_unpack_parent(&self, envelope: &Aabb<T::Scalar>) -> bool {
+        envelope.contains(&self.0.envelope())
+    }
+    fn should_unpack_leaf(&self, leaf: &T) -> bool {
+        leaf == self.0
+    }
+}
+
+/// call visitor on every object until it breaks
+fn visit<T, B>(
+    objects: impl Iterator<Item = T>,
+    mut visitor: impl FnMut(T) -> ControlFlow<B>,
+) -> ControlFlow<B> {
+    for object in objects {
+        if let ControlFlow::Break(value) = visitor(object) {
+            return ControlFlow::Break(value);
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Spatial queries, after the interface of the `rstar` crate. Everything is built on selection
+/// functions and a nearest neighbour walk. The `_int` variants iterate internally, calling a
+/// visitor until it breaks. Mutable access must not move an object, or it won't be found again
 pub trait SpatialIndex {
-    fn locate_all_at_point(&mut self);
-    fn locate_all_at_point_int(&mut self);
-    fn locate_all_at_point_int_mut(&mut self);
-    fn locate_in_envelope(&mut self);
-    fn locate_in_envelope_mut(&mut self);
-    fn locate_in_envelope_int(&mut self);
-    fn locate_in_envelope_int_mut(&mut self);
-    fn drain_in_envelope(&mut self);
-
-    fn locate_in_envelope_intersecting(&mut self);
-    fn locate_in_envelope_intersecting_mut(&mut self);
-    fn locate_in_envelope_intersecting_int(&mut self);
-    fn locate_in_envelope_intersecting_int_mut(&mut self);
-    fn drain_in_envelope_intersecting(&mut self);
-
-    fn remove(&mut self);
-    fn remove_at_point(&mut self);
-
-    fn contains(&mut self);
-
-    fn nearest_neighbor(&mut self);
-    fn nearest_neighbors(&mut self);
-
-    fn locate_within_distance(&mut self);
-    fn drain_within_distance(&mut self);
-
-    fn nearest_neighbors_iter(&mut self);
-    fn nearest_neighbor_iter_with_distance(&mut self);
-    fn pop_nearest_neighbor(&mut self);
-
-    fn remove_with_selection_function(&mut self);
-    fn drain_with_selection_function(&mut self);
+    type Scalar: Scalar;
+    type Object: SpatialObject<Scalar = Self::Scalar>;
+
+    fn select<'a>(
+        &'a self,
+        selection: impl SelectionFunction<Self::Object> + 'a,
+    ) -> impl Iterator<Item = &'a Self::Object>;
+
+    fn select_mut<'a>(
+        &'a mut self,
+        selection: impl SelectionFunction<Self::Object> + 'a,
+    ) -> impl Iterator<Item = &'a mut Self::Object>;
+
+    /// every object, ordered by its distance to point, along with the squared distance
+    fn nearest_neighbor_iter_with_distance(
+        &self,
+        point: [Self::Scalar; 2],
+    ) -> impl Iterator<Item = (&Self::Object, f64)>;
+
+    /// remove the first object selected
+    fn remove_with_selection_function(
+        &mut self,
+        selection: impl SelectionFunction<Self::Object>,
+    ) -> Option<Self::Object>;
+
+    fn drain_with_selection_function(
+        &mut self,
+        selection: impl SelectionFunction<Self::Object>,
+    ) -> Vec<Self::Object>;
+
+    fn locate_all_at_point(&self, point: [Self::Scalar; 2]) -> impl Iterator<Item = &Self::Object> {
+        self.select(SelectAtPoint(point))
+    }
+
+    fn locate_all_at_point_int<B>(
+        &self,
+        point: [Self::Scalar; 2],
+        visitor: impl FnMut(&Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.locate_all_at_point(point), visitor)
+    }
+
+    fn locate_all_at_point_int_mut<B>(
+        &mut self,
+        point: [Self::Scalar; 2],
+        visitor: impl FnMut(&mut Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.select_mut(SelectAtPoint(point)), visitor)
+    }
+
+    fn locate_in_envelope(
+        &self,
+        envelope: Aabb<Self::Scalar>,
+    ) -> impl Iterator<Item = &Self::Object> {
+        self.select(SelectInEnvelope(envelope))
+    }
+
+    fn locate_in_envelope_mut(
+        &mut self,
+        envelope: Aabb<Self::Scalar>,
+    ) -> impl Iterator<Item = &mut Self::Object> {
+        self.select_mut(SelectInEnvelope(envelope))
+    }
+
+    fn locate_in_envelope_int<B>(
+        &self,
+        envelope: Aabb<Self::Scalar>,
+        visitor: impl FnMut(&Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.locate_in_envelope(envelope), visitor)
+    }
+
+    fn locate_in_envelope_int_mut<B>(
+        &mut self,
+        envelope: Aabb<Self::Scalar>,
+        visitor: impl FnMut(&mut Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.locate_in_envelope_mut(envelope), visitor)
+    }
+
+    fn drain_in_envelope(&mut self, envelope: Aabb<Self::Scalar>) -> Vec<Self::Object> {
+        self.drain_with_selection_function(SelectInEnvelope(envelope))
+    }
+
+    fn locate_in_envelope_intersecting(
+        &self,
+        envelope: Aabb<Self::Scalar>,
+    ) -> impl Iterator<Item = &Self::Object> {
+        self.select(SelectInEnvelopeIntersecting(envelope))
+    }
+
+    fn locate_in_envelope_intersecting_mut(
+        &mut self,
+        envelope: Aabb<Self::Scalar>,
+    ) -> impl Iterator<Item = &mut Self::Object> {
+        self.select_mut(SelectInEnvelopeIntersecting(envelope))
+    }
+
+    fn locate_in_envelope_intersecting_int<B>(
+        &self,
+        envelope: Aabb<Self::Scalar>,
+        visitor: impl FnMut(&Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.locate_in_envelope_intersecting(envelope), visitor)
+    }
+
+    fn locate_in_envelope_intersecting_int_mut<B>(
+        &mut self,
+        envelope: Aabb<Self::Scalar>,
+        visitor: impl FnMut(&mut Self::Object) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        visit(self.locate_in_envelope_intersecting_mut(envelope), visitor)
+    }
+
+    fn drain_in_envelope_intersecting(
+        &mut self,
+        envelope: Aabb<Self::Scalar>,
+    ) -> Vec<Self::Object> {
+        self.drain_with_selection_function(SelectInEnvelopeIntersecting(envelope))
+    }
+
+    fn remove(&mut self, object: &Self::Object) -> Option<Self::Object>
+    where
+        Self::Object: PartialEq,
+    {
+        self.remove_with_selection_function(SelectEqual(object))
+    }
+
+    fn remove_at_point(&mut self, point: [Self::Scalar; 2]) -> Option<Self::Object> {
+        self.remove_with_selection_function(SelectAtPoint(point))
+    }
+
+    fn contains(&self, object: &Self::Object) -> bool
+    where
+        Self::Object: PartialEq,
+    {
+        self.select(SelectEqual(object)).next().is_some()
+    }
+
+    fn nearest_neighbor(&self, point: [Self::Scalar; 2]) -> Option<&Self::Object> {
+        self.nearest_neighbors_iter(point).next()
+    }
+
+    /// every object tied for the closest to point
+    fn nearest_neighbors(&self, point: [Self::Scalar; 2]) -> Vec<&Self::Object> {
+        let mut nearest = self.nearest_neighbor_iter_with_distance(point).peekable();
+        let Some((_, closest)) = nearest.peek().copied() else {
+            return vec![];
+        };
+        nearest
+            .take_while(|(_, distance)| *distance == closest)
+            .map(|(object, _)| object)
+            .collect()
+    }
+
+    fn locate_within_distance(
+        &self,
+        point: [Self::Scalar; 2],
+        max_distance_2: f64,
+    ) -> impl Iterator<Item = &Self::Object> {
+        self.select(SelectWithinDistance {
+            point,
+            max_distance_2,
+        })
+    }
+
+    fn drain_within_distance(
+        &mut self,
+        point: [Self::Scalar; 2],
+        max_distance_2: f64,
+    ) -> Vec<Self::Object> {
+        self.drain_with_selection_function(SelectWithinDistance {
+            point,
+            max_distance_2,
+        })
+    }
+
+    fn nearest_neighbors_iter(
+        &self,
+        point: [Self::Scalar; 2],
+    ) -> impl Iterator<Item = &Self::Object> {
+        self.nearest_neighbor_iter_with_distance(point)
+            .map(|(object, _)| object)
+    }
+
+    fn pop_nearest_neighbor(&mut self, point: [Self::Scalar; 2]) -> Option<Self::Object> {
+        let (_, closest) = self.nearest_neighbor_iter_with_distance(point).next()?;
+        // nothing is closer, so anything this close is one of the nearest
+        self.remove_with_selection_function(SelectWithinDistance {
+            point,
+            max_distance_2: closest,
+        })
+    }
+}
+
+#[test]
+fn rtree_queries() {
+    // a scrambled grid, so neither insertion order nor packing follow the layout
+    let points: Vec<[i32; 2]> = (0..400)
+        .map(|i| [(i * 37) % 20, (i * 37) / 20 % 20])
+        .collect();
+    let mut inserted = RTree::new();
+    for point in &points {
+        inserted.insert(*point);
+    }
+    let loaded: RTree<_> = points.iter().copied().collect();
+
+    for mut tree in [inserted, loaded] {
+        assert_eq!(tree.len(), 400);
+        let envelope = Aabb {
+            min: [3, 4],
+            max: [6, 5],
+        };
+        let mut found: Vec<_> = tree.locate_in_envelope(envelope).copied().collect();
+        found.sort();
+        let expected: Vec<_> = (3..=6).flat_map(|x| [[x, 4], [x, 5]]).collect();
+        assert_eq!(found, expected);
+        assert_eq!(tree.locate_all_at_point([7, 7]).count(), 1);
+
+        // the nearest neighbours come closest first, and ties come together
+        let nearest: Vec<_> = tree
+            .nearest_neighbor_iter_with_distance([-2, 0])
+            .map(|(_, distance)| distance)
+            .take(10)
+            .collect();
+        assert!(nearest.is_sorted());
+        assert_eq!(nearest[0], 4.);
+        assert_eq!(tree.nearest_neighbor([-2, 0]), Some(&[0, 0]));
+        assert_eq!(tree.nearest_neighbors([10, -1]), vec![&[10, 0]]);
+        assert_eq!(tree.locate_within_distance([10, 10], 1.).count(), 5);
+
+        // removals keep every other object findable
+        assert_eq!(tree.pop_nearest_neighbor([-2, 0]), Some([0, 0]));
+        assert_eq!(tree.remove(&[5, 5]), Some([5, 5]));
+        assert_eq!(tree.remove(&[5, 5]), None);
+        assert_eq!(tree.drain_in_envelope(envelope).len(), 7);
+        let odd = tree.drain_with_selection_function(|p: &[i32; 2]| p[0] % 2 == 1);
+        assert_eq!(odd.len(), 200 - 4);
+        assert_eq!(tree.len(), 400 - 2 - 7 - 196);
+        assert!(tree.iter().all(|p| tree.contains(p)));
+        assert!(!tree.contains(&[1, 1]));
+        assert_eq!(tree.nearest_neighbor([-2, 0]), Some(&[0, 1]));
+    }
 }
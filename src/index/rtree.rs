@@ -0,0 +1,441 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{
+    coord::Scalar,
+    index::{Aabb, SelectionFunction, SpatialIndex, SpatialObject},
+};
+
+/// the most children a node holds before it splits
+const MAX_CHILDREN: usize = 8;
+
+enum Node<T: SpatialObject> {
+    Leaf(T),
+    Parent(Parent<T>),
+}
+
+impl<T: SpatialObject> Node<T> {
+    fn envelope(&self) -> Aabb<T::Scalar> {
+        match self {
+            Node::Leaf(object) => object.envelope(),
+            Node::Parent(parent) => parent.envelope,
+        }
+    }
+}
+
+/// A node with either only leaves or only parents under it, so every leaf is as deep as any other
+struct Parent<T: SpatialObject> {
+    /// around every child, meaningless while there are none
+    envelope: Aabb<T::Scalar>,
+    children: Vec<Node<T>>,
+}
+
+impl<T: SpatialObject> Parent<T> {
+    fn new(children: Vec<Node<T>>) -> Self {
+        let mut parent = Self {
+            envelope: Aabb::from_point([T::Scalar::ZERO; 2]),
+            children,
+        };
+        parent.update_envelope();
+        parent
+    }
+
+    fn update_envelope(&mut self) {
+        if let Some(envelope) = self
+            .children
+            .iter()
+            .map(Node::envelope)
+            .reduce(|a, b| a.merged(&b))
+        {
+            self.envelope = envelope;
+        }
+    }
+
+    fn holds_leaves(&self) -> bool {
+        !matches!(self.children.first(), Some(Node::Parent(_)))
+    }
+
+    /// insert below, returning the second half when this node had to split
+    fn insert(&mut self, object: T) -> Option<Parent<T>> {
+        let envelope = object.envelope();
+        self.envelope = if self.children.is_empty() {
+            envelope
+        } else {
+            self.envelope.merged(&envelope)
+        };
+
+        if self.holds_leaves() {
+            self.children.push(Node::Leaf(object));
+        } else {
+            // the child that grows the least, and is the smallest among those
+            let growth = |node: &Node<T>| {
+                let area = node.envelope().area();
+                (node.envelope().merged(&envelope).area() - area, area)
+            };
+            let best = self
+                .children
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    let [a, b] = [growth(a), growth(b)];
+                    a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1))
+                })
+                .map(|(i, _)| i)
+                .unwrap();
+            let Node::Parent(child) = &mut self.children[best] else {
+                unreachable!()
+            };
+            if let Some(split) = child.insert(object) {
+                self.children.push(Node::Parent(split));
+            }
+        }
+
+        (self.children.len() > MAX_CHILDREN).then(|| self.split())
+    }
+
+    /// halve the children along the axis their centers spread over the most
+    fn split(&mut self) -> Parent<T> {
+        let centers = self.children.iter().map(|node| node.envelope().center());
+        let spread = |axis: usize| {
+            let (min, max) = centers
+                .clone()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), c| {
+                    (min.min(c[axis]), max.max(c[axis]))
+                });
+            max - min
+        };
+        let axis = if spread(0) >= spread(1) { 0 } else { 1 };
+        sort_by_center(&mut self.children, axis);
+
+        let half = self.children.split_off(self.children.len() / 2);
+        self.update_envelope();
+        Parent::new(half)
+    }
+
+    /// remove the first object selected below
+    fn remove_first(&mut self, selection: &impl SelectionFunction<T>) -> Option<T> {
+        for i in 0..self.children.len() {
+            let removed = match &mut self.children[i] {
+                Node::Leaf(object) if selection.should_unpack_leaf(object) => {
+                    let Node::Leaf(object) = self.children.swap_remove(i) else {
+                        unreachable!()
+                    };
+                    Some(object)
+                }
+                Node::Parent(child) if selection.should_unpack_parent(&child.envelope) => {
+                    let removed = child.remove_first(selection);
+                    if removed.is_some() && child.children.is_empty() {
+                        self.children.swap_remove(i);
+                    }
+                    removed
+                }
+                _ => None,
+            };
+            if removed.is_some() {
+                self.update_envelope();
+                return removed;
+            }
+        }
+        None
+    }
+
+    fn drain(&mut self, selection: &impl SelectionFunction<T>, out: &mut Vec<T>) {
+        let mut i = 0;
+        while i < self.children.len() {
+            match &mut self.children[i] {
+                Node::Leaf(object) if selection.should_unpack_leaf(object) => {
+                    if let Node::Leaf(object) = self.children.swap_remove(i) {
+                        out.push(object);
+                    }
+                    continue;
+                }
+                Node::Parent(child) if selection.should_unpack_parent(&child.envelope) => {
+                    child.drain(selection, out);
+                    if child.children.is_empty() {
+                        self.children.swap_remove(i);
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        self.update_envelope();
+    }
+}
+
+fn sort_by_center<T: SpatialObject>(nodes: &mut [Node<T>], axis: usize) {
+    nodes.sort_by(|a, b| a.envelope().center()[axis].total_cmp(&b.envelope().center()[axis]));
+}
+
+/// A bounding volume hierarchy of objects, with every node holding up to a handful of children
+/// and the box around them
+pub struct RTree<T: SpatialObject> {
+    root: Parent<T>,
+    len: usize,
+}
+
+impl<T: SpatialObject> Default for RTree<T> {
+    fn default() -> Self {
+        Self {
+            root: Parent::new(vec![]),
+            len: 0,
+        }
+    }
+}
+
+impl<T: SpatialObject> RTree<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// build a tree of all objects at once, packing the leaves into tiles of nearby objects. this
+    /// gives a better tree than inserting them one by one, and is faster
+    pub fn bulk_load(objects: Vec<T>) -> Self {
+        let len = objects.len();
+        let mut nodes: Vec<_> = objects.into_iter().map(Node::Leaf).collect();
+        while nodes.len() > MAX_CHILDREN {
+            nodes = pack(nodes);
+        }
+        Self {
+            root: Parent::new(nodes),
+            len,
+        }
+    }
+
+    pub fn insert(&mut self, object: T) {
+        self.len += 1;
+        if let Some(split) = self.root.insert(object) {
+            let root = std::mem::replace(&mut self.root, Parent::new(vec![]));
+            self.root = Parent::new(vec![Node::Parent(root), Node::Parent(split)]);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.select(|_: &T| true)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.select_mut(|_: &T| true)
+    }
+
+    /// drop a root that has a single parent under it, after removals
+    fn shrink(&mut self) {
+        while let [Node::Parent(_)] = self.root.children.as_slice() {
+            let Some(Node::Parent(child)) = self.root.children.pop() else {
+                unreachable!()
+            };
+            self.root = child;
+        }
+    }
+}
+
+/// group nodes into parents of nearby nodes: sorted by x into vertical strips, and each strip
+/// sorted by y and cut into parents, after the sort tile recursive algorithm
+fn pack<T: SpatialObject>(mut nodes: Vec<Node<T>>) -> Vec<Node<T>> {
+    let parents = nodes.len().div_ceil(MAX_CHILDREN);
+    let strips = (parents as f64).sqrt().ceil() as usize;
+    let strip_len = strips * MAX_CHILDREN;
+
+    sort_by_center(&mut nodes, 0);
+    let mut packed = Vec::with_capacity(parents);
+    let mut nodes = nodes.into_iter().peekable();
+    while nodes.peek().is_some() {
+        let mut strip: Vec<_> = nodes.by_ref().take(strip_len).collect();
+        sort_by_center(&mut strip, 1);
+        let mut strip = strip.into_iter().peekable();
+        while strip.peek().is_some() {
+            let children = strip.by_ref().take(MAX_CHILDREN).collect();
+            packed.push(Node::Parent(Parent::new(children)));
+        }
+    }
+    packed
+}
+
+impl<T: SpatialObject> FromIterator<T> for RTree<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::bulk_load(iter.into_iter().collect())
+    }
+}
+
+/// The objects a selection function picks
+struct SelectionIter<'a, T: SpatialObject, Sel> {
+    stack: Vec<&'a Node<T>>,
+    selection: Sel,
+}
+
+impl<'a, T: SpatialObject, Sel: SelectionFunction<T>> Iterator for SelectionIter<'a, T, Sel> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf(object) if self.selection.should_unpack_leaf(object) => {
+                    return Some(object);
+                }
+                Node::Parent(parent) if self.selection.should_unpack_parent(&parent.envelope) => {
+                    self.stack.extend(&parent.children);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+struct SelectionIterMut<'a, T: SpatialObject, Sel> {
+    stack: Vec<&'a mut Node<T>>,
+    selection: Sel,
+}
+
+impl<'a, T: SpatialObject, Sel: SelectionFunction<T>> Iterator for SelectionIterMut<'a, T, Sel> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            match node {
+                Node::Leaf(object) => {
+                    if self.selection.should_unpack_leaf(object) {
+                        return Some(object);
+                    }
+                }
+                Node::Parent(parent) => {
+                    if self.selection.should_unpack_parent(&parent.envelope) {
+                        self.stack.extend(&mut parent.children);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// a node waiting to be visited, by how close it can get
+struct Queued<'a, T: SpatialObject> {
+    distance: f64,
+    node: &'a Node<T>,
+}
+
+impl<T: SpatialObject> PartialEq for Queued<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: SpatialObject> Eq for Queued<'_, T> {}
+
+impl<T: SpatialObject> PartialOrd for Queued<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: SpatialObject> Ord for Queued<'_, T> {
+    /// closest first out of the max heap, and objects before boxes as far away
+    fn cmp(&self, other: &Self) -> Ordering {
+        let is_leaf = |queued: &Self| matches!(queued.node, Node::Leaf(_));
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then(is_leaf(self).cmp(&is_leaf(other)))
+    }
+}
+
+/// Every object by its distance to a point, closest first. A node is only opened once nothing that
+/// could be closer is left, with its box as the bound on how close anything inside it gets
+struct NearestIter<'a, T: SpatialObject> {
+    point: [T::Scalar; 2],
+    queue: BinaryHeap<Queued<'a, T>>,
+}
+
+impl<'a, T: SpatialObject> NearestIter<'a, T> {
+    fn push(&mut self, node: &'a Node<T>) {
+        let distance = match node {
+            Node::Leaf(object) => object.distance_2(self.point),
+            Node::Parent(parent) => parent.envelope.distance_2(self.point),
+        };
+        self.queue.push(Queued { distance, node });
+    }
+}
+
+impl<'a, T: SpatialObject> Iterator for NearestIter<'a, T> {
+    type Item = (&'a T, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Queued { distance, node }) = self.queue.pop() {
+            match node {
+                Node::Leaf(object) => return Some((object, distance)),
+                Node::Parent(parent) => {
+                    for child in &parent.children {
+                        self.push(child);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl<T: SpatialObject> SpatialIndex for RTree<T> {
+    type Scalar = T::Scalar;
+    type Object = T;
+
+    fn select<'a>(
+        &'a self,
+        selection: impl SelectionFunction<T> + 'a,
+    ) -> impl Iterator<Item = &'a T> {
+        SelectionIter {
+            stack: self.root.children.iter().collect(),
+            selection,
+        }
+    }
+
+    fn select_mut<'a>(
+        &'a mut self,
+        selection: impl SelectionFunction<T> + 'a,
+    ) -> impl Iterator<Item = &'a mut T> {
+        SelectionIterMut {
+            stack: self.root.children.iter_mut().collect(),
+            selection,
+        }
+    }
+
+    fn nearest_neighbor_iter_with_distance(
+        &self,
+        point: [T::Scalar; 2],
+    ) -> impl Iterator<Item = (&T, f64)> {
+        let mut nearest = NearestIter {
+            point,
+            queue: BinaryHeap::new(),
+        };
+        for child in &self.root.children {
+            nearest.push(child);
+        }
+        nearest
+    }
+
+    fn remove_with_selection_function(
+        &mut self,
+        selection: impl SelectionFunction<T>,
+    ) -> Option<T> {
+        let removed = self.root.remove_first(&selection)?;
+        self.len -= 1;
+        self.shrink();
+        Some(removed)
+    }
+
+    fn drain_with_selection_function(&mut self, selection: impl SelectionFunction<T>) -> Vec<T> {
+        let mut drained = vec![];
+        self.root.drain(&selection, &mut drained);
+        self.len -= drained.len();
+        self.shrink();
+        drained
+    }
+}
@@ -10,7 +10,7 @@ pub mod dcel;
 pub mod flavor;
 #[cfg(feature = "graph")]
 pub mod graph;
-mod index;
+pub mod index;
 pub mod traverser;
 pub mod util;
 pub mod weighted;
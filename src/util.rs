@@ -1,28 +1,38 @@
-use crate::coord::{Coordinate, Orientation};
+use std::cmp::Ordering;
+
+use crate::coord::{Coordinate, Expansion, Orientation, Scalar};
 
 #[derive(Default)]
 pub struct ShoeString {
-    area_sum: f32,
+    /// twice the area, summed without rounding
+    area_sum: Expansion,
 }
 
 impl ShoeString {
-    pub fn add(&mut self, v1: &impl Coordinate, v2: &impl Coordinate) {
-        let [x, y] = v1.xy();
-        let [x1, y1] = v2.xy();
-        self.area_sum += x * y1 - y * x1;
+    pub fn add<C: Coordinate>(&mut self, v1: &C, v2: &C) {
+        let [x, y] = v1.xy().map(Scalar::to_f64);
+        let [x1, y1] = v2.xy().map(Scalar::to_f64);
+        self.area_sum = self
+            .area_sum
+            .add(&Expansion::product(x, y1))
+            .sub(&Expansion::product(y, x1));
+    }
+
+    /// compare the signed areas exactly
+    pub fn cmp_area(&self, other: &Self) -> Ordering {
+        self.area_sum.sub(&other.area_sum).signum().total_cmp(&0.)
     }
 
-    pub fn area(&self) -> f32 {
-        self.area_sum / 2.
+    pub fn area(&self) -> f64 {
+        self.area_sum.estimate() / 2.
     }
 
+    /// the winding of the polygon, exact even when the rounded area is not
     pub fn orientation(&self) -> Orientation {
-        if self.area_sum > 0. {
-            Orientation::Counterclockwise(0.)
-        } else if self.area_sum < 0. {
-            Orientation::Clockwise(0.)
-        } else {
-            Orientation::Neutral
+        match self.area_sum.signum() {
+            sign if sign > 0. => Orientation::Counterclockwise(self.area()),
+            sign if sign < 0. => Orientation::Clockwise(self.area()),
+            _ => Orientation::Neutral,
         }
     }
 }
@@ -1,5 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Weighted<T, W> {
     pub inner: T,
     pub weight: W,